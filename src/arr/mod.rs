@@ -0,0 +1,682 @@
+use crate::config::{AmbiguousMatch, PayloadMode};
+use crate::http::{self, HttpClient, HttpStatusError};
+use crate::models::{QualityProfile, RootFolder, SkipReason, SystemStatus, Tag};
+use crate::template::{self, TemplateValues};
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, instrument, warn};
+
+pub const DEFAULT_API_VERSION: &str = "v3";
+
+/// External ids of everything already in a Sonarr/Radarr library, loaded
+/// once per sync so duplicate checks are set lookups rather than scans
+#[derive(Debug, Default)]
+pub struct LibraryIds {
+    tvdb_ids: HashMap<i32, Option<i64>>,
+    tmdb_ids: HashMap<i32, Option<i64>>,
+    /// Lowercased, IMDb ids compare case-insensitively
+    imdb_ids: HashMap<String, Option<i64>>,
+    len: usize,
+}
+
+/// A library entry found by one of its external ids
+#[derive(Debug, PartialEq)]
+pub struct ExistingMatch {
+    /// Which id matched, e.g. "TMDB: 603"
+    pub matched_by: String,
+    /// Sonarr/Radarr id of the entry
+    pub id: Option<i64>,
+}
+
+impl std::fmt::Display for ExistingMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.matched_by)
+    }
+}
+
+impl LibraryIds {
+    /// Record the entry with Sonarr/Radarr id `id` under its external ids
+    pub fn insert(&mut self, id: Option<i64>, tvdb_id: Option<i32>, tmdb_id: Option<i32>, imdb_id: Option<&str>) {
+        self.tvdb_ids.extend(tvdb_id.map(|tvdb_id| (tvdb_id, id)));
+        self.tmdb_ids.extend(tmdb_id.map(|tmdb_id| (tmdb_id, id)));
+        self.imdb_ids.extend(imdb_id.map(|imdb_id| (imdb_id.to_ascii_lowercase(), id)));
+        self.len += 1;
+    }
+
+    /// Number of library entries inserted
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The entry carrying one of the given ids, if it is already in the
+    /// library. TVDB is checked first, then TMDB, then each IMDb id in turn.
+    pub fn matching(&self, tvdb_id: Option<i32>, tmdb_id: Option<i32>, imdb_ids: &[&str]) -> Option<ExistingMatch> {
+        let found = |matched_by: String, id: &Option<i64>| ExistingMatch { matched_by, id: *id };
+        if let Some(tvdb_id) = tvdb_id {
+            if let Some(id) = self.tvdb_ids.get(&tvdb_id) {
+                return Some(found(format!("TVDB: {}", tvdb_id), id));
+            }
+        }
+        if let Some(tmdb_id) = tmdb_id {
+            if let Some(id) = self.tmdb_ids.get(&tmdb_id) {
+                return Some(found(format!("TMDB: {}", tmdb_id), id));
+            }
+        }
+        imdb_ids.iter().find_map(|imdb_id| {
+            self.imdb_ids
+                .get(&imdb_id.to_ascii_lowercase())
+                .map(|id| found(format!("IMDb: {}", imdb_id), id))
+        })
+    }
+}
+
+/// Search commands queued by nonBlockingAdd that haven't been waited on yet
+pub type QueuedSearches = Mutex<Vec<JoinHandle<()>>>;
+
+/// The parts of the Sonarr and Radarr v3 APIs both clients use the same way,
+/// borrowed from whichever client is calling
+pub struct ArrApi<'a> {
+    /// "Sonarr" or "Radarr", for log messages
+    pub service: &'static str,
+    pub http: &'a HttpClient,
+    pub base_url: &'a str,
+    pub api_key: &'a str,
+    /// API version segment in request URLs, e.g. "v3"
+    pub api_version: &'a str,
+    pub searches: &'a QueuedSearches,
+}
+
+impl ArrApi<'_> {
+    /// Full URL for an API `path` under the configured API version, with
+    /// `query` encoded and the API key appended. Every request goes through
+    /// here so slashes and escaping are handled in one place.
+    pub fn build_url(&self, path: &str, query: &[(&str, &str)]) -> String {
+        let mut url = format!("{}/api/{}/{}",
+                              self.base_url.trim_end_matches('/'),
+                              self.api_version.trim_matches('/'),
+                              path.trim_start_matches('/'));
+
+        let mut separator = '?';
+        for (key, value) in query.iter().chain(&[("apikey", self.api_key)]) {
+            url.push(separator);
+            url.push_str(&urlencoding::encode(key));
+            url.push('=');
+            url.push_str(&urlencoding::encode(value));
+            separator = '&';
+        }
+        url
+    }
+
+    /// A 404 here almost always means the wrong baseUrl or apiVersion, so
+    /// that is said outright rather than left as a bare status
+    #[instrument(skip(self), fields(service = self.service))]
+    pub async fn get_system_status(&self) -> Result<SystemStatus> {
+        let url = self.build_url("system/status", &[]);
+
+        self.http.get_json(&url).await.map_err(|e| {
+            if HttpStatusError::is_status(&e, StatusCode::NOT_FOUND) {
+                e.context(format!("API version mismatch: {} has no /api/{}/system/status, check baseUrl and apiVersion",
+                                  self.service, self.api_version.trim_matches('/')))
+            } else {
+                e
+            }
+        })
+    }
+
+    #[instrument(skip(self), fields(service = self.service))]
+    pub async fn get_tags(&self) -> Result<Vec<Tag>> {
+        let url = self.build_url("tag", &[]);
+
+        self.http.get_json(&url).await
+    }
+
+    /// Ids of the existing tags named `tag_names`, warning about any that don't exist
+    pub async fn resolve_tag_ids(&self, tag_names: &[String]) -> Result<Vec<i32>> {
+        let tags = self.get_tags().await?;
+        let mut tag_ids: Vec<i32> = tag_names
+            .iter()
+            .filter_map(|name| match tags.iter().find(|t| t.label == *name) {
+                Some(tag) => Some(tag.id),
+                None => {
+                    warn!("Tag '{}' does not exist in {}, ignoring", name, self.service);
+                    None
+                }
+            })
+            .collect();
+
+        // Sorted and unique so identical configs always produce identical payloads
+        tag_ids.sort_unstable();
+        tag_ids.dedup();
+        Ok(tag_ids)
+    }
+
+    #[instrument(skip(self), fields(service = self.service))]
+    pub async fn create_tag(&self, label: &str) -> Result<Tag> {
+        let url = self.build_url("tag", &[]);
+
+        info!("Creating {} tag '{}'", self.service, label);
+        self.http.post_json(&url, &serde_json::json!({ "label": label })).await
+    }
+
+    /// Resolve tags by label, creating any that don't exist yet
+    pub async fn ensure_tag_ids(&self, labels: &[String]) -> Result<Vec<i32>> {
+        let mut tags = self.get_tags().await?;
+        let mut tag_ids = Vec::new();
+
+        for label in labels {
+            let label = tag_label(label);
+            let tag_id = match tags.iter().find(|t| t.label.eq_ignore_ascii_case(&label)) {
+                Some(tag) => tag.id,
+                None => {
+                    let tag = self.create_tag(&label).await?;
+                    let tag_id = tag.id;
+                    tags.push(tag);
+                    tag_id
+                }
+            };
+            tag_ids.push(tag_id);
+        }
+
+        Ok(tag_ids)
+    }
+
+    /// Move the existing `resource` entry `id`, a "movie" or "series", to the
+    /// quality profile named `profile_name` when it is on another one
+    pub async fn update_quality_profile(&self, resource: &str, id: i64, profile_name: &str, title: &str) -> Result<()> {
+        let profiles: Vec<QualityProfile> = self.http.get_json(&self.build_url("qualityprofile", &[])).await?;
+        let Some(profile) = profiles.iter().find(|p| p.name == profile_name) else {
+            warn!("Quality profile '{}' not found in {}, not updating '{}'", profile_name, self.service, title);
+            return Ok(());
+        };
+
+        let url = self.build_url(&format!("{}/{}", resource, id), &[]);
+        let mut entry: Value = self.http.get_json(&url).await?;
+        if entry.get("qualityProfileId").and_then(|p| p.as_i64()) == Some(i64::from(profile.id)) {
+            return Ok(());
+        }
+
+        entry["qualityProfileId"] = profile.id.into();
+        let _: Value = self.http.put_json(&url, &entry).await?;
+        info!("Moved existing {} '{}' to quality profile '{}'", resource, title, profile_name);
+        Ok(())
+    }
+
+    /// Queue a search `command` in the background without waiting on it
+    pub async fn queue_search(&self, command: Value) {
+        let http = self.http.clone();
+        let url = self.build_url("command", &[]);
+        let service = self.service;
+
+        let search = http::spawn_with_request_id(async move {
+            match http.post_json::<Value, _>(&url, &command).await {
+                Ok(_) => debug!("Queued {} command {}", service, command),
+                Err(e) => warn!("Failed to queue {} command {}: {}", service, command, e),
+            }
+        });
+        self.searches.lock().await.push(search);
+    }
+
+    /// Wait for the searches queued by nonBlockingAdd. Adds don't wait on
+    /// them, the sync does, so a one-shot run doesn't exit and drop them.
+    pub async fn wait_for_searches(&self) {
+        let searches = std::mem::take(&mut *self.searches.lock().await);
+        for search in searches {
+            if let Err(e) = search.await {
+                warn!("{} search task did not finish: {}", self.service, e);
+            }
+        }
+    }
+}
+
+/// `http` asking for JSON explicitly; some reverse proxies and CDNs pick
+/// the response format from Accept
+pub fn accept_json(http: HttpClient) -> HttpClient {
+    http.with_header("accept", "application/json").expect("static header value is valid")
+}
+
+/// A Sonarr or Radarr lookup result, as far as telling same-title results apart
+pub trait LookupCandidate {
+    fn title(&self) -> &str;
+    fn year(&self) -> Option<i32>;
+    /// Whether it carries the id the backend adds by
+    fn has_id(&self) -> bool;
+    fn popularity(&self) -> f64;
+}
+
+/// The lookup result to add for `title`. Remakes share a title, so several
+/// same-title results are narrowed to those from `year` that carry an id,
+/// and `policy` picks
+/// when that still leaves more than one.
+pub fn pick_lookup_result<'a, T: LookupCandidate>(
+    results: &'a [T],
+    title: &str,
+    year: Option<i32>,
+    policy: AmbiguousMatch,
+) -> Result<&'a T, SkipReason> {
+    let Some(first) = results.first() else {
+        return Err(SkipReason::NotFound);
+    };
+
+    let wanted = normalize_title(title);
+    let candidates: Vec<&T> = results.iter().filter(|r| normalize_title(r.title()) == wanted).collect();
+    let contenders: Vec<&T> = match (candidates.as_slice(), year) {
+        // Nothing carries the title as given, so the lookup's ranking stands
+        ([], _) => return Ok(first),
+        ([only], _) => return Ok(only),
+        (_, Some(year)) => candidates.iter().copied().filter(|r| r.year() == Some(year) && r.has_id()).collect(),
+        (_, None) => candidates.clone(),
+    };
+
+    match contenders.as_slice() {
+        [] => {
+            warn!("{} results are titled '{}' but none is from {}, skipping",
+                  candidates.len(), title, year.unwrap_or_default());
+            Err(SkipReason::Ambiguous)
+        }
+        [only] => Ok(only),
+        [first_contender, ..] => match policy {
+            AmbiguousMatch::Skip => {
+                match year {
+                    Some(year) => warn!("{} results titled '{}' are from {}, skipping (ambiguousMatch: skip)",
+                                        contenders.len(), title, year),
+                    None => warn!("{} results are titled '{}' and the watchlist item has no year, skipping (ambiguousMatch: skip)",
+                                  contenders.len(), title),
+                }
+                Err(SkipReason::Ambiguous)
+            }
+            AmbiguousMatch::First => Ok(first_contender),
+            AmbiguousMatch::MostPopular => Ok(contenders
+                .iter()
+                .copied()
+                .max_by(|a, b| a.popularity().total_cmp(&b.popularity()))
+                .unwrap_or(first)),
+        },
+    }
+}
+
+/// Lowercase alphanumerics only, so punctuation and spacing differences
+/// between Plex and TMDB titles don't matter
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Sonarr/Radarr tag labels only allow lowercase letters, digits and dashes
+pub fn tag_label(label: &str) -> String {
+    label
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Leading articles dropped from sort titles
+const SORT_ARTICLES: &[&str] = &["the ", "a ", "an "];
+
+/// Lowercased title without a leading article, e.g. "The Matrix" -> "matrix"
+pub fn normalized_sort_title(title: &str) -> String {
+    let lowered = title.trim().to_lowercase();
+    SORT_ARTICLES
+        .iter()
+        .find_map(|article| lowered.strip_prefix(article))
+        .map(|rest| rest.trim_start().to_string())
+        .unwrap_or(lowered)
+}
+
+/// `title` without `pattern` at its end or start (ASCII case-insensitive)
+/// and the separator left behind, e.g. "Alien - Director's Cut" with
+/// "Director's Cut" -> "Alien". `None` when the pattern isn't there or
+/// nothing would be left.
+pub fn strip_title_pattern(title: &str, pattern: &str) -> Option<String> {
+    let title = title.trim();
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let suffix_start = title.len().checked_sub(pattern.len())?;
+    let stripped = if title.get(suffix_start..).is_some_and(|end| end.eq_ignore_ascii_case(pattern)) {
+        &title[..suffix_start]
+    } else if title.get(..pattern.len()).is_some_and(|start| start.eq_ignore_ascii_case(pattern)) {
+        &title[pattern.len()..]
+    } else {
+        return None;
+    };
+
+    let stripped = stripped.trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | ':' | ','));
+    (!stripped.is_empty()).then(|| stripped.to_string())
+}
+
+/// Treat a Radarr/Sonarr lookup result as adult when it flags itself
+/// explicitly or carries an adult certification; results without either are
+/// assumed safe
+pub fn is_adult(extra_fields: &Value) -> bool {
+    if extra_fields.get("adult").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return true;
+    }
+
+    extra_fields
+        .get("certification")
+        .and_then(|v| v.as_str())
+        .map(|c| matches!(c.trim().to_uppercase().as_str(), "X" | "XXX"))
+        .unwrap_or(false)
+}
+
+/// First of a lookup result's genres, which is what `{genre}` expands to
+pub fn first_genre(extra_fields: &Value) -> Option<&str> {
+    extra_fields.get("genres")?.as_array()?.first()?.as_str()
+}
+
+/// Expand a `rootFolderTemplate` for one item, refusing paths outside the
+/// `service`'s own root folders unless `allow_any` is set
+pub fn templated_root_folder(service: &str, template: &str, values: &TemplateValues, root_folders: &[RootFolder], allow_any: bool) -> Result<String> {
+    let path = template::expand_root_folder(template, values);
+    if !allow_any && !template::is_within_root_folders(&path, root_folders.iter().map(|f| f.path.as_str())) {
+        anyhow::bail!("Root folder '{}' from rootFolderTemplate is not under any {} root folder \
+                       (set allowAnyRootFolder to use it anyway)", path, service);
+    }
+    Ok(path)
+}
+
+/// The `service`'s first root folder, or `default_root` (the `default_key`
+/// setting) when it reports none. With neither there is no sensible place
+/// to add `title`, so rather than guess a path this fails.
+pub fn fallback_root_folder(service: &str, root_folders: &[RootFolder], default_root: Option<&String>, default_key: &str, title: &str) -> Result<String> {
+    match (root_folders.first(), default_root) {
+        (Some(folder), _) => Ok(folder.path.clone()),
+        (None, Some(folder)) => Ok(folder.clone()),
+        (None, None) => anyhow::bail!("{} has no root folders and neither rootFolder nor {} is set, \
+                                       not guessing where to put '{}'", service, default_key, title),
+    }
+}
+
+/// Body for adding to Sonarr/Radarr: just the modelled `body` in minimal mode,
+/// or the whole `lookup` object with `body`'s fields layered on top in full
+/// mode. User-supplied `extra` fields win over everything we computed.
+pub fn build_payload<T: Serialize>(body: &T, lookup: &Value, mode: PayloadMode, extra: Option<&Value>) -> Result<Value> {
+    let payload = serde_json::to_value(body)?;
+
+    let mut payload = match mode {
+        PayloadMode::Minimal => payload,
+        PayloadMode::Full => {
+            let mut merged = lookup.clone();
+            if let (Some(merged_fields), Value::Object(fields)) = (merged.as_object_mut(), payload) {
+                merged_fields.extend(fields);
+            }
+            merged
+        }
+    };
+
+    if let Some(extra) = extra {
+        merge_json(&mut payload, extra);
+    }
+    Ok(payload)
+}
+
+/// Recursively merge `overlay` into `target`: nested objects are merged key by
+/// key, anything else in `overlay` replaces the value in `target`
+fn merge_json(target: &mut Value, overlay: &Value) {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_json(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (target, overlay) => *target = overlay.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, Arr, Logs};
+    use serde_json::json;
+
+    struct Candidate {
+        title: &'static str,
+        year: i32,
+        id: Option<i32>,
+        popularity: f64,
+    }
+
+    impl LookupCandidate for Candidate {
+        fn title(&self) -> &str {
+            self.title
+        }
+
+        fn year(&self) -> Option<i32> {
+            Some(self.year)
+        }
+
+        fn has_id(&self) -> bool {
+            self.id.is_some()
+        }
+
+        fn popularity(&self) -> f64 {
+            self.popularity
+        }
+    }
+
+    /// Two "Dune" results from 2021 the year can't separate, behind a
+    /// differently titled first result
+    fn year_tie() -> Vec<Candidate> {
+        vec![
+            Candidate { title: "Dune: Part Two", year: 2024, id: Some(1), popularity: 90.0 },
+            Candidate { title: "Dune", year: 2021, id: Some(2), popularity: 10.0 },
+            Candidate { title: "DUNE", year: 2021, id: Some(3), popularity: 50.0 },
+            Candidate { title: "Dune", year: 1984, id: Some(4), popularity: 99.0 },
+        ]
+    }
+
+    fn picked_id(results: &[Candidate], year: Option<i32>, policy: AmbiguousMatch) -> Result<Option<i32>, SkipReason> {
+        pick_lookup_result(results, "Dune", year, policy).map(|c| c.id)
+    }
+
+    #[test]
+    fn a_year_tie_follows_the_ambiguous_match_policy() {
+        let results = year_tie();
+
+        assert_eq!(picked_id(&results, Some(2021), AmbiguousMatch::Skip), Err(SkipReason::Ambiguous));
+        assert_eq!(picked_id(&results, Some(2021), AmbiguousMatch::First), Ok(Some(2)));
+        assert_eq!(picked_id(&results, Some(2021), AmbiguousMatch::MostPopular), Ok(Some(3)));
+        assert_eq!(picked_id(&results, Some(1984), AmbiguousMatch::Skip), Ok(Some(4)));
+    }
+
+    #[test]
+    fn a_same_year_result_without_an_id_is_out_of_the_running() {
+        let mut results = year_tie();
+        results[2].id = None;
+
+        assert_eq!(picked_id(&results, Some(2021), AmbiguousMatch::Skip), Ok(Some(2)));
+    }
+
+    #[test]
+    fn no_contender_from_the_item_year_is_ambiguous_under_every_policy() {
+        let results = year_tie();
+
+        for policy in [AmbiguousMatch::Skip, AmbiguousMatch::First, AmbiguousMatch::MostPopular] {
+            assert_eq!(picked_id(&results, Some(2000), policy), Err(SkipReason::Ambiguous), "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn unique_or_absent_titles_need_no_policy() {
+        let results = year_tie();
+
+        assert_eq!(pick_lookup_result(&results, "Dune: Part Two", None, AmbiguousMatch::Skip).map(|c| c.id), Ok(Some(1)));
+        // No result carries the title as given, so the lookup's ranking stands
+        assert_eq!(pick_lookup_result(&results, "Dune (1984)", None, AmbiguousMatch::Skip).map(|c| c.id), Ok(Some(1)));
+        assert!(matches!(pick_lookup_result::<Candidate>(&[], "Dune", None, AmbiguousMatch::First), Err(SkipReason::NotFound)));
+    }
+
+    #[test]
+    fn normalized_sort_titles_drop_one_leading_article() {
+        assert_eq!(normalized_sort_title("The Matrix"), "matrix");
+        assert_eq!(normalized_sort_title(" A Quiet Place"), "quiet place");
+        assert_eq!(normalized_sort_title("An Education"), "education");
+        assert_eq!(normalized_sort_title("Theodore Rex"), "theodore rex");
+        assert_eq!(normalized_sort_title("The"), "the");
+    }
+
+    #[test]
+    fn strip_patterns_remove_a_prefix_or_suffix_and_its_separator() {
+        assert_eq!(strip_title_pattern("Alien - Director's Cut", "director's cut").as_deref(), Some("Alien"));
+        assert_eq!(strip_title_pattern("Disney's: Hercules", "Disney's").as_deref(), Some("Hercules"));
+        assert_eq!(strip_title_pattern("Heat", "Director's Cut"), None);
+        assert_eq!(strip_title_pattern("Director's Cut", "Director's Cut"), None);
+        assert_eq!(strip_title_pattern("Heat", " "), None);
+    }
+
+    #[test]
+    fn adult_flag_or_certification_marks_a_result_adult() {
+        assert!(is_adult(&json!({ "adult": true })));
+        assert!(is_adult(&json!({ "certification": " xxx " })));
+        assert!(is_adult(&json!({ "adult": false, "certification": "X" })));
+    }
+
+    #[test]
+    fn results_without_a_flag_are_not_adult() {
+        assert!(!is_adult(&json!({})));
+        assert!(!is_adult(&json!({ "adult": false, "certification": "R" })));
+        assert!(!is_adult(&json!({ "adult": "yes", "certification": 18 })));
+    }
+
+    fn payload_parts() -> (Value, Value) {
+        let body = json!({ "title": "Heat", "tmdbId": 949, "qualityProfileId": 1, "monitored": true });
+        let lookup = json!({ "title": "Heat (1995)", "tmdbId": 949, "images": [], "runtime": 170, "monitored": false });
+        (body, lookup)
+    }
+
+    #[test]
+    fn minimal_payload_sends_only_the_modelled_fields() {
+        let (body, lookup) = payload_parts();
+
+        let payload = build_payload(&body, &lookup, PayloadMode::Minimal, None).unwrap();
+
+        assert_eq!(payload, body);
+        assert!(payload.get("images").is_none());
+        assert!(payload.get("runtime").is_none());
+    }
+
+    #[test]
+    fn full_payload_layers_the_modelled_fields_over_the_lookup() {
+        let (body, lookup) = payload_parts();
+
+        let payload = build_payload(&body, &lookup, PayloadMode::Full, None).unwrap();
+
+        assert_eq!(payload["title"], "Heat");
+        assert_eq!(payload["qualityProfileId"], 1);
+        assert_eq!(payload["monitored"], true);
+        assert_eq!(payload["runtime"], 170);
+        assert_eq!(payload["images"], json!([]));
+    }
+
+    #[test]
+    fn extra_fields_win_and_merge_into_nested_objects() {
+        let (mut body, lookup) = payload_parts();
+        body["addOptions"] = json!({ "searchForMovie": true, "monitor": "movieOnly" });
+        let extra = json!({ "monitored": false, "addOptions": { "monitor": "none" }, "minimumAvailability": "released" });
+
+        let payload = build_payload(&body, &lookup, PayloadMode::Full, Some(&extra)).unwrap();
+
+        assert_eq!(payload["monitored"], false);
+        assert_eq!(payload["addOptions"], json!({ "searchForMovie": true, "monitor": "none" }));
+        assert_eq!(payload["minimumAvailability"], "released");
+        assert_eq!(payload["runtime"], 170);
+    }
+
+    #[tokio::test]
+    async fn tag_ids_are_sorted_deduped_and_unknown_names_warned_about() {
+        let (router, _) = test_support::arr("movie", Arr {
+            tags: json!([{ "id": 3, "label": "watchlistarr" }, { "id": 1, "label": "kids" }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let http = test_support::http_client();
+        let searches = QueuedSearches::default();
+        let api = ArrApi { service: "Radarr", http: &http, base_url: &base_url, api_key: "test-key", api_version: DEFAULT_API_VERSION, searches: &searches };
+        let names: Vec<String> = ["watchlistarr", "kids", "watchlistarr", "missing"].map(String::from).into();
+        let logs = Logs::default();
+        let _guard = logs.capture();
+
+        let tag_ids = api.resolve_tag_ids(&names).await.unwrap();
+
+        assert_eq!(tag_ids, vec![1, 3]);
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("Tag 'missing' does not exist in Radarr, ignoring"), "{:?}", lines);
+    }
+
+    #[test]
+    fn tag_labels_are_lowercased_with_dashes_for_anything_else() {
+        assert_eq!(tag_label(" Sci-Fi & Fantasy "), "sci-fi---fantasy");
+        assert_eq!(tag_label("Kids"), "kids");
+    }
+
+    #[test]
+    fn endpoints_use_the_configured_api_version() {
+        let http = test_support::http_client();
+        let searches = QueuedSearches::default();
+        let api = ArrApi {
+            service: "Lidarr",
+            http: &http,
+            base_url: "http://arr:8686/",
+            api_key: "key",
+            api_version: "v1",
+            searches: &searches,
+        };
+
+        assert_eq!(api.build_url("system/status", &[]), "http://arr:8686/api/v1/system/status?apikey=key");
+        assert_eq!(api.build_url("artist/lookup", &[("term", "abba")]), "http://arr:8686/api/v1/artist/lookup?term=abba&apikey=key");
+    }
+
+    #[test]
+    fn every_endpoint_url_is_normalized_and_encoded() {
+        let http = test_support::http_client();
+        let searches = QueuedSearches::default();
+        let api = ArrApi {
+            service: "Radarr",
+            http: &http,
+            base_url: "https://arr.example.com/radarr//",
+            api_key: "k&y=1",
+            api_version: "/v3/",
+            searches: &searches,
+        };
+        let base = "https://arr.example.com/radarr/api/v3";
+
+        for path in ["qualityprofile", "rootfolder", "system/status", "tag", "command", "movie", "series", "/movie"] {
+            assert_eq!(api.build_url(path, &[]), format!("{}/{}?apikey=k%26y%3D1", base, path.trim_start_matches('/')));
+        }
+        assert_eq!(
+            api.build_url("movie/lookup", &[("term", "Amélie & Nino? 100%")]),
+            format!("{}/movie/lookup?term=Am%C3%A9lie%20%26%20Nino%3F%20100%25&apikey=k%26y%3D1", base)
+        );
+        assert_eq!(api.build_url("series/lookup", &[("term", "tvdb:79126")]), format!("{}/series/lookup?term=tvdb%3A79126&apikey=k%26y%3D1", base));
+    }
+
+    #[test]
+    fn library_ids_match_by_tvdb_then_tmdb_then_imdb() {
+        let mut library = LibraryIds::default();
+        library.insert(Some(1), Some(79126), None, None);
+        library.insert(Some(2), None, Some(949), Some("TT0113277"));
+
+        let matched_by = |m: Option<ExistingMatch>| m.map(|m| (m.matched_by, m.id));
+        assert_eq!(library.len(), 2);
+        assert_eq!(matched_by(library.matching(Some(79126), Some(949), &[])), Some(("TVDB: 79126".to_string(), Some(1))));
+        assert_eq!(matched_by(library.matching(Some(1), Some(949), &[])), Some(("TMDB: 949".to_string(), Some(2))));
+        assert_eq!(
+            matched_by(library.matching(None, Some(1), &["tt1", "tt0113277"])),
+            Some(("IMDb: tt0113277".to_string(), Some(2)))
+        );
+        assert_eq!(library.matching(Some(1), Some(1), &["tt1"]), None);
+    }
+}
@@ -1,21 +1,37 @@
+use crate::http::{RedirectPolicy, RetryBudget, RetryPolicy};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct Configuration {
     pub interval: Option<IntervalConfig>,
     pub sonarr: Option<SonarrConfig>,
     pub radarr: Option<RadarrConfig>,
+    pub overseerr: Option<OverseerrConfig>,
     pub plex: Option<PlexConfig>,
     pub delete: Option<DeleteConfig>,
+    pub sync: Option<SyncConfig>,
+    pub server: Option<ServerConfig>,
+    pub statsd: Option<StatsdConfig>,
+    pub retry: Option<RetryConfig>,
+    pub http: Option<HttpConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct HttpConfig {
+    pub redirects: Option<RedirectPolicy>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct IntervalConfig {
     pub seconds: u64,
+    #[serde(rename = "fullSyncSeconds")]
+    pub full_sync_seconds: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct SonarrConfig {
     #[serde(rename = "baseUrl")]
     pub base_url: String,
@@ -25,14 +41,70 @@ pub struct SonarrConfig {
     pub quality_profile: Option<String>,
     #[serde(rename = "rootFolder")]
     pub root_folder: Option<String>,
+    /// Used when rootFolder is unset and Sonarr reports no root folders
+    #[serde(rename = "defaultShowRoot")]
+    pub default_show_root: Option<String>,
     #[serde(rename = "bypassIgnored")]
     pub bypass_ignored: Option<bool>,
     #[serde(rename = "seasonMonitoring")]
     pub season_monitoring: Option<String>,
+    #[serde(rename = "monitorSeasons")]
+    pub monitor_seasons: Option<Vec<i32>>,
     pub tags: Option<Vec<String>>,
+    #[serde(rename = "allowAdultContent")]
+    pub allow_adult_content: Option<bool>,
+    #[serde(rename = "timeoutSeconds")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(rename = "payloadMode")]
+    pub payload_mode: Option<PayloadMode>,
+    #[serde(rename = "mirrorPlexLabels")]
+    pub mirror_plex_labels: Option<bool>,
+    #[serde(rename = "nonBlockingAdd")]
+    pub non_blocking_add: Option<bool>,
+    /// API version segment in request URLs, defaults to "v3"
+    #[serde(rename = "apiVersion")]
+    pub api_version: Option<String>,
+    /// Raw fields merged into the add request body, overriding computed ones
+    #[serde(rename = "extraAddFields")]
+    pub extra_add_fields: Option<serde_json::Value>,
+    /// Skip shows with fewer regular seasons; unknown counts are kept
+    #[serde(rename = "minSeasons")]
+    pub min_seasons: Option<u64>,
+    /// Skip shows with fewer episodes; unknown counts are kept
+    #[serde(rename = "minEpisodes")]
+    pub min_episodes: Option<u64>,
+    /// Send a lowercased sort title without leading articles instead of
+    /// the lookup's own
+    #[serde(rename = "normalizeSortTitle")]
+    pub normalize_sort_title: Option<bool>,
+    /// Move items that already exist to the configured qualityProfile
+    #[serde(rename = "updateExistingProfile")]
+    pub update_existing_profile: Option<bool>,
+    /// Literal title prefixes/suffixes (e.g. "Director's Cut") removed for a
+    /// second lookup when the title as-is finds nothing
+    #[serde(rename = "stripPatterns")]
+    pub strip_patterns: Option<Vec<String>>,
+    /// Root folder per item from `{title}`, `{year}`, `{genre}` and
+    /// `{first_letter}`, e.g. "/media/tv/{first_letter}"; wins over rootFolder
+    #[serde(rename = "rootFolderTemplate")]
+    pub root_folder_template: Option<String>,
+    /// Accept template paths outside Sonarr's configured root folders
+    #[serde(rename = "allowAnyRootFolder")]
+    pub allow_any_root_folder: Option<bool>,
+    /// Overrides the top-level retry settings for Sonarr requests
+    pub retry: Option<BackendRetryConfig>,
+    /// Look shows without a TVDB id up by their TMDB id before searching by
+    /// title, taking the TVDB id from the match; defaults to true
+    #[serde(rename = "tmdbLookup")]
+    pub tmdb_lookup: Option<bool>,
+    #[serde(rename = "ambiguousMatch")]
+    pub ambiguous_match: Option<AmbiguousMatch>,
+    /// Put episodes in per-season folders; unset uses Sonarr's default
+    #[serde(rename = "seasonFolder")]
+    pub season_folder: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct RadarrConfig {
     #[serde(rename = "baseUrl")]
     pub base_url: String,
@@ -42,19 +114,182 @@ pub struct RadarrConfig {
     pub quality_profile: Option<String>,
     #[serde(rename = "rootFolder")]
     pub root_folder: Option<String>,
+    /// Used when rootFolder is unset and Radarr reports no root folders
+    #[serde(rename = "defaultMovieRoot")]
+    pub default_movie_root: Option<String>,
     #[serde(rename = "bypassIgnored")]
     pub bypass_ignored: Option<bool>,
     pub tags: Option<Vec<String>>,
+    #[serde(rename = "allowAdultContent")]
+    pub allow_adult_content: Option<bool>,
+    #[serde(rename = "timeoutSeconds")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(rename = "payloadMode")]
+    pub payload_mode: Option<PayloadMode>,
+    #[serde(rename = "mirrorPlexLabels")]
+    pub mirror_plex_labels: Option<bool>,
+    #[serde(rename = "nonBlockingAdd")]
+    pub non_blocking_add: Option<bool>,
+    /// API version segment in request URLs, defaults to "v3"
+    #[serde(rename = "apiVersion")]
+    pub api_version: Option<String>,
+    /// Raw fields merged into the add request body, overriding computed ones
+    #[serde(rename = "extraAddFields")]
+    pub extra_add_fields: Option<serde_json::Value>,
+    /// Opt-in: when a watchlisted movie exists but is unmonitored, monitor
+    /// it and search instead of skipping it
+    #[serde(rename = "searchUnmonitoredExisting")]
+    pub search_unmonitored_existing: Option<bool>,
+    #[serde(rename = "ambiguousMatch")]
+    pub ambiguous_match: Option<AmbiguousMatch>,
+    /// Skip movies shorter than this; movies with unknown runtime are kept
+    #[serde(rename = "minRuntimeMinutes")]
+    pub min_runtime_minutes: Option<u64>,
+    /// Send a lowercased sort title without leading articles instead of
+    /// the lookup's own
+    #[serde(rename = "normalizeSortTitle")]
+    pub normalize_sort_title: Option<bool>,
+    /// Move items that already exist to the configured qualityProfile
+    #[serde(rename = "updateExistingProfile")]
+    pub update_existing_profile: Option<bool>,
+    /// Literal title prefixes/suffixes (e.g. "Director's Cut") removed for a
+    /// second lookup when the title as-is finds nothing
+    #[serde(rename = "stripPatterns")]
+    pub strip_patterns: Option<Vec<String>>,
+    /// Root folder per item from `{title}`, `{year}`, `{genre}` and
+    /// `{first_letter}`, e.g. "/media/movies/{first_letter}"; wins over rootFolder
+    #[serde(rename = "rootFolderTemplate")]
+    pub root_folder_template: Option<String>,
+    /// Accept template paths outside Radarr's configured root folders
+    #[serde(rename = "allowAnyRootFolder")]
+    pub allow_any_root_folder: Option<bool>,
+    /// Overrides the top-level retry settings for Radarr requests
+    pub retry: Option<BackendRetryConfig>,
+}
+
+/// What to do when several lookup results carry the item's title (remakes)
+/// and the item's year, if it has one, doesn't narrow them down to one.
+/// Sonarr and Radarr each take their own setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AmbiguousMatch {
+    /// Skip the item rather than risk adding the wrong film
+    #[default]
+    Skip,
+    /// Take the lookup's first candidate, the backend's own ranking
+    First,
+    /// Take the candidate with the highest TMDB popularity (movies) or the
+    /// most rating votes (shows)
+    #[serde(alias = "highest_popularity")]
+    MostPopular,
+}
+
+/// Overseerr or Jellyseerr, used to create requests instead of adding
+/// straight to Sonarr/Radarr
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct OverseerrConfig {
+    #[serde(rename = "baseUrl")]
+    pub base_url: String,
+    #[serde(rename = "apikey")]
+    pub api_key: String,
+    #[serde(rename = "timeoutSeconds")]
+    pub timeout_seconds: Option<u64>,
+    /// Also add to Sonarr/Radarr directly after requesting
+    #[serde(rename = "alsoDirect")]
+    pub also_direct: Option<bool>,
+    /// Language for title searches (e.g. "de"), to match watchlist titles
+    /// shown in a non-English Plex locale
+    pub language: Option<String>,
+}
+
+/// Which fields are sent when adding to Sonarr/Radarr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadMode {
+    /// Only the fields this tool models explicitly
+    #[default]
+    Minimal,
+    /// The full lookup object with the modelled fields layered on top
+    Full,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Order in which a sync works through the watchlist when adding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AddOrder {
+    /// Whatever order Plex returned the items in
+    #[default]
+    WatchlistOrder,
+    NewestFirst,
+    OldestFirst,
+    /// By title, ignoring case
+    Alphabetical,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct PlexConfig {
-    pub token: String,
+    /// Plex account token, used for discover/watchlist and friends endpoints
+    #[serde(rename = "token", alias = "accountToken")]
+    pub account_token: String,
+    /// Token for calls made directly to a Plex Media Server, when it differs
+    /// from the account token
+    #[serde(rename = "serverToken")]
+    pub server_token: Option<String>,
+    /// Plex Media Server address, e.g. "http://192.168.1.10:32400"
+    #[serde(rename = "serverUrl")]
+    pub server_url: Option<String>,
+    /// Leave out watchlist items already in one of serverUrl's movie or
+    /// show libraries
+    #[serde(rename = "skipOwned")]
+    pub skip_owned: Option<bool>,
+    /// Additional, separate Plex accounts whose watchlists are synced too
+    pub accounts: Option<Vec<PlexAccountConfig>>,
     #[serde(rename = "skipfriendsync")]
     pub skip_friend_sync: Option<bool>,
+    #[serde(rename = "watchlistSort")]
+    pub watchlist_sort: Option<String>,
+    #[serde(rename = "maxFriends")]
+    pub max_friends: Option<usize>,
+    /// Friends' watchlists fetched at once (default 4)
+    #[serde(rename = "friendsConcurrency")]
+    pub friends_concurrency: Option<usize>,
+    /// How long the friends list is reused before it is fetched again
+    /// (default 3600, 0 fetches it every sync)
+    #[serde(rename = "friendsCacheSeconds")]
+    pub friends_cache_seconds: Option<u64>,
+    /// Only sync these friends, by username or friend id
+    #[serde(rename = "friendsAllowlist")]
+    pub friends_allowlist: Option<Vec<String>>,
+    /// Never sync these friends, by username or friend id
+    #[serde(rename = "friendsBlocklist")]
+    pub friends_blocklist: Option<Vec<String>>,
+    /// Fetch each item's full metadata for external ids the watchlist
+    /// listing lacks; one extra request per item
+    #[serde(rename = "resolveMetadata")]
+    pub resolve_metadata: Option<bool>,
+    /// Base URL of the Plex metadata API. Never read from the file; tests
+    /// point this at a local server.
+    #[serde(skip)]
+    pub metadata_url: Option<String>,
+    /// Base URL of the Plex community (GraphQL) API, overridden the same way
+    #[serde(skip)]
+    pub community_url: Option<String>,
+    /// Sent as X-Plex-Client-Identifier; generated once and kept in a file
+    /// next to the config when unset
+    #[serde(rename = "clientIdentifier")]
+    pub client_identifier: Option<String>,
+    /// Overrides the top-level retry settings for Plex requests
+    pub retry: Option<BackendRetryConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PlexAccountConfig {
+    /// Name recorded as the owner of this account's watchlist items
+    pub label: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DeleteConfig {
     pub movie: Option<bool>,
     #[serde(rename = "endedShow")]
@@ -66,7 +301,139 @@ pub struct DeleteConfig {
     pub delete_files: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SyncConfig {
+    #[serde(rename = "itemTimeoutSeconds")]
+    pub item_timeout_secs: Option<u64>,
+    #[serde(rename = "slowSyncThresholdSeconds")]
+    pub slow_sync_threshold_secs: Option<u64>,
+    /// Only add items from the owner's own accounts, even on a full sync.
+    /// Unlike `plex.skipfriendsync`, this does not stop friends from being
+    /// fetched elsewhere (e.g. export); `skipfriendsync` wins when both are set.
+    #[serde(rename = "ownerOnly")]
+    pub owner_only: Option<bool>,
+    #[serde(rename = "addOrder")]
+    pub add_order: Option<AddOrder>,
+    /// Re-query Sonarr/Radarr at the end of a sync and report adds that
+    /// did not materialise
+    #[serde(rename = "verifyAdds")]
+    pub verify_adds: Option<bool>,
+    /// Upper bound on the delay between scheduled syncs after repeated
+    /// failures; 0 disables backing off
+    #[serde(rename = "failureBackoffMaxSeconds")]
+    pub failure_backoff_max_secs: Option<u64>,
+    /// How long to wait before looking up an item again after a backend
+    /// could not find it; 0 looks it up every sync
+    #[serde(rename = "notFoundCooldownSeconds")]
+    pub not_found_cooldown_secs: Option<u64>,
+    /// Fraction of failed adds (0-1) above which a sync logs an alert
+    #[serde(rename = "failureAlertThreshold")]
+    pub failure_alert_threshold: Option<f64>,
+    /// At startup, wait up to this long for Sonarr/Radarr to answer before
+    /// the first sync; 0 (default) starts straight away
+    #[serde(rename = "waitForArrSeconds")]
+    pub wait_for_arr_secs: Option<u64>,
+    /// Replace per-item info logs with periodic progress lines
+    #[serde(rename = "progressLogging")]
+    pub progress_logging: Option<ProgressLoggingConfig>,
+    /// Stop starting new items once a sync has run this long, leaving the
+    /// rest for the next one; 0 (default) never stops early
+    #[serde(rename = "cycleTimeBudgetSeconds")]
+    pub cycle_time_budget_secs: Option<u64>,
+    /// Send items of a genre to other backends than their type would;
+    /// the first route naming one of the item's genres wins
+    pub routes: Option<Vec<RouteConfig>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RouteConfig {
+    /// Plex genre, compared case-insensitively
+    pub genre: String,
+    /// Backends to send the item to. Radarr only takes movies and Sonarr
+    /// only shows; either is passed over for the other type.
+    pub targets: Vec<Target>,
+}
+
+/// A backend an item can be sent to
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Overseerr,
+    Radarr,
+    Sonarr,
+}
+
+/// A progress line is logged after `everyItems` items or `everySeconds`
+/// seconds, whichever comes first
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ProgressLoggingConfig {
+    #[serde(rename = "everyItems")]
+    pub every_items: Option<usize>,
+    #[serde(rename = "everySeconds")]
+    pub every_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RetryConfig {
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: Option<u32>,
+    #[serde(rename = "backoffMillis")]
+    pub backoff_millis: Option<u64>,
+    #[serde(rename = "retryBudget")]
+    pub retry_budget: Option<RetryBudgetConfig>,
+}
+
+/// A backend's own retry settings, overriding the top-level `retry` ones.
+/// The retry budget stays shared across all backends.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct BackendRetryConfig {
+    /// 1 disables retries for this backend
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: Option<u32>,
+    #[serde(rename = "backoffMillis")]
+    pub backoff_millis: Option<u64>,
+}
+
+impl BackendRetryConfig {
+    /// `base` with whichever settings this block sets replaced
+    pub fn apply_to(&self, base: RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts.unwrap_or(base.max_attempts).max(1),
+            backoff: self.backoff_millis.map(Duration::from_millis).unwrap_or(base.backoff),
+        }
+    }
+}
+
+/// At most `maxRetries` retries across all requests per `windowSeconds`
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RetryBudgetConfig {
+    #[serde(rename = "maxRetries")]
+    pub max_retries: u32,
+    #[serde(rename = "windowSeconds")]
+    pub window_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ServerConfig {
+    /// Address for the status HTTP server, e.g. "0.0.0.0:8080"
+    pub bind: String,
+}
+
+/// Push the /metrics gauges to a StatsD or DogStatsD agent; needs a build
+/// with the `statsd` feature
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct StatsdConfig {
+    /// Agent address, e.g. "127.0.0.1:8125"
+    pub address: String,
+    /// Metric name prefix, defaults to "watchlistarr"
+    pub prefix: Option<String>,
+    /// Send labels as DogStatsD tags instead of appending them to the name
+    pub tags: Option<bool>,
+    #[serde(rename = "intervalSeconds")]
+    pub interval_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DeleteIntervalConfig {
     pub days: u64,
 }
@@ -78,13 +445,180 @@ impl Configuration {
         Ok(config)
     }
 
-    pub fn refresh_interval(&self) -> Duration {
-        Duration::from_secs(
-            self.interval
-                .as_ref()
-                .map(|i| i.seconds)
-                .unwrap_or(10)
-        )
+    /// Whether any of Sonarr, Radarr or Overseerr is configured. Without one
+    /// only the read-only features (export, status) have anything to do.
+    pub fn has_sync_targets(&self) -> bool {
+        self.sonarr.is_some() || self.radarr.is_some() || self.overseerr.is_some()
+    }
+
+    /// JSON Schema of the config file, derived from these structs so it
+    /// can't drift from what is actually accepted
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Configuration)
+    }
+
+    /// Reject configs that would start the daemon but never sync anything
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let Some(ref plex) = self.plex else {
+            anyhow::bail!("No plex section configured, there is no watchlist to sync");
+        };
+
+        if plex.account_token.trim().is_empty() {
+            anyhow::bail!("plex.token is empty, an account token is required to read watchlists");
+        }
+
+        if plex.skip_owned.unwrap_or(false) && plex.server_url.is_none() {
+            anyhow::bail!("plex.skipOwned needs plex.serverUrl to read the server's libraries");
+        }
+
+        let extra_add_fields = [
+            ("sonarr", self.sonarr.as_ref().and_then(|s| s.extra_add_fields.as_ref())),
+            ("radarr", self.radarr.as_ref().and_then(|r| r.extra_add_fields.as_ref())),
+        ];
+        for (section, fields) in extra_add_fields {
+            if fields.is_some_and(|f| !f.is_object()) {
+                anyhow::bail!("{}.extraAddFields must be a mapping of field names to values", section);
+            }
+        }
+
+        if let Some(threshold) = self.failure_alert_threshold() {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                anyhow::bail!("sync.failureAlertThreshold must be a fraction between 0 and 1, got {}", threshold);
+            }
+        }
+
+        for route in self.routes() {
+            if route.targets.is_empty() {
+                anyhow::bail!("sync.routes entry for genre '{}' has no targets", route.genre);
+            }
+            for target in &route.targets {
+                let (section, configured) = match target {
+                    Target::Overseerr => ("overseerr", self.overseerr.is_some()),
+                    Target::Radarr => ("radarr", self.radarr.is_some()),
+                    Target::Sonarr => ("sonarr", self.sonarr.is_some()),
+                };
+                if !configured {
+                    anyhow::bail!("sync.routes sends genre '{}' to {}, but there is no {} section", route.genre, section, section);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// RSS polling interval, `None` when set to 0 (manual/webhook-only syncing)
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        let seconds = self.interval
+            .as_ref()
+            .map(|i| i.seconds)
+            .unwrap_or(10);
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Full sync interval, `None` when set to 0
+    pub fn full_sync_interval(&self) -> Option<Duration> {
+        let seconds = self.interval
+            .as_ref()
+            .and_then(|i| i.full_sync_seconds)
+            .unwrap_or(19 * 60);
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Syncs taking longer than this are logged as a warning
+    pub fn slow_sync_threshold(&self) -> Option<Duration> {
+        self.sync
+            .as_ref()
+            .and_then(|s| s.slow_sync_threshold_secs)
+            .map(Duration::from_secs)
+    }
+
+    pub fn owner_only(&self) -> bool {
+        self.sync.as_ref().and_then(|s| s.owner_only).unwrap_or(false)
+    }
+
+    /// Cap for failure backoff (default 30 minutes), `None` when disabled
+    pub fn failure_backoff_max(&self) -> Option<Duration> {
+        let seconds = self.sync
+            .as_ref()
+            .and_then(|s| s.failure_backoff_max_secs)
+            .unwrap_or(1800);
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Negative lookup cache lifetime (default 6 hours), `None` when disabled
+    pub fn not_found_cooldown(&self) -> Option<Duration> {
+        let seconds = self.sync
+            .as_ref()
+            .and_then(|s| s.not_found_cooldown_secs)
+            .unwrap_or(6 * 60 * 60);
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Progress cadence as (items, interval), `None` when progress logging is off
+    pub fn progress_logging(&self) -> Option<(usize, Duration)> {
+        let progress = self.sync.as_ref()?.progress_logging.as_ref()?;
+        Some((
+            progress.every_items.unwrap_or(50).max(1),
+            Duration::from_secs(progress.every_seconds.unwrap_or(30)),
+        ))
+    }
+
+    pub fn wait_for_arr(&self) -> Option<Duration> {
+        self.sync
+            .as_ref()
+            .and_then(|s| s.wait_for_arr_secs)
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs)
+    }
+
+    pub fn cycle_time_budget(&self) -> Option<Duration> {
+        self.sync
+            .as_ref()
+            .and_then(|s| s.cycle_time_budget_secs)
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs)
+    }
+
+    pub fn failure_alert_threshold(&self) -> Option<f64> {
+        self.sync.as_ref().and_then(|s| s.failure_alert_threshold)
+    }
+
+    pub fn verify_adds(&self) -> bool {
+        self.sync.as_ref().and_then(|s| s.verify_adds).unwrap_or(false)
+    }
+
+    pub fn add_order(&self) -> AddOrder {
+        self.sync.as_ref().and_then(|s| s.add_order).unwrap_or_default()
+    }
+
+    pub fn routes(&self) -> &[RouteConfig] {
+        self.sync.as_ref().and_then(|s| s.routes.as_deref()).unwrap_or_default()
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        let Some(ref retry) = self.retry else {
+            return defaults;
+        };
+
+        RetryPolicy {
+            max_attempts: retry.max_attempts.unwrap_or(defaults.max_attempts).max(1),
+            backoff: retry.backoff_millis.map(Duration::from_millis).unwrap_or(defaults.backoff),
+        }
+    }
+
+    /// Global retry budget, defaulting to 60 retries per minute
+    pub fn retry_budget(&self) -> Arc<RetryBudget> {
+        let (max_retries, window_seconds) = self.retry
+            .as_ref()
+            .and_then(|r| r.retry_budget.as_ref())
+            .map(|b| (b.max_retries, b.window_seconds))
+            .unwrap_or((60, 60));
+        Arc::new(RetryBudget::new(max_retries, Duration::from_secs(window_seconds)))
+    }
+
+    pub fn redirect_policy(&self) -> RedirectPolicy {
+        self.http.as_ref().and_then(|h| h.redirects).unwrap_or_default()
     }
 
     pub fn delete_interval(&self) -> Duration {
@@ -96,4 +630,125 @@ impl Configuration {
                 .unwrap_or(7 * 24 * 60 * 60)
         )
     }
-}
\ No newline at end of file
+
+    /// Upper bound on a single item's lookup + duplicate check + add
+    pub fn item_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.sync
+                .as_ref()
+                .and_then(|s| s.item_timeout_secs)
+                .unwrap_or(300)
+        )
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn default_config_has_no_sections_and_default_intervals() {
+        let config = Configuration::default();
+
+        assert!(config.plex.is_none() && config.sonarr.is_none() && config.radarr.is_none());
+        assert_eq!(config.refresh_interval(), Some(Duration::from_secs(10)));
+        assert_eq!(config.full_sync_interval(), Some(Duration::from_secs(19 * 60)));
+    }
+
+    #[test]
+    fn empty_config_is_rejected() {
+        let config: Configuration = serde_yaml::from_str("{}").unwrap();
+
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("No plex section configured"), "{}", err);
+    }
+
+    #[test]
+    fn plex_without_a_backend_is_valid_but_has_no_sync_targets() {
+        let config = Configuration { plex: Some(test_support::plex_config("http://plex.test", "http://community.test")), ..Configuration::default() };
+
+        config.validate().unwrap();
+        assert!(!config.has_sync_targets());
+    }
+
+    #[test]
+    fn overseerr_alone_is_a_valid_backend() {
+        let config = Configuration {
+            plex: Some(test_support::plex_config("http://plex.test", "http://community.test")),
+            overseerr: Some(serde_yaml::from_str("baseUrl: http://overseerr.test\napikey: test-key").unwrap()),
+            ..Configuration::default()
+        };
+
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn plex_with_one_backend_is_valid() {
+        let config = Configuration {
+            plex: Some(test_support::plex_config("http://plex.test", "http://community.test")),
+            radarr: Some(test_support::radarr_config("http://radarr.test", "")),
+            ..Configuration::default()
+        };
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn zero_intervals_disable_polling() {
+        let config: Configuration = serde_yaml::from_str("interval:\n  seconds: 0\n  fullSyncSeconds: 0\n").unwrap();
+
+        assert_eq!(config.refresh_interval(), None);
+        assert_eq!(config.full_sync_interval(), None);
+    }
+
+    #[test]
+    fn empty_account_token_is_rejected() {
+        let mut plex = test_support::plex_config("http://plex.test", "http://community.test");
+        plex.account_token = " ".to_string();
+        let config = Configuration {
+            plex: Some(plex),
+            radarr: Some(test_support::radarr_config("http://radarr.test", "")),
+            ..Configuration::default()
+        };
+
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("plex.token is empty"), "{}", err);
+    }
+
+    #[test]
+    fn extra_add_fields_must_be_a_mapping() {
+        let config = Configuration {
+            plex: Some(test_support::plex_config("http://plex.test", "http://community.test")),
+            radarr: Some(test_support::radarr_config("http://radarr.test", "extraAddFields: [monitored]\n")),
+            ..Configuration::default()
+        };
+
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("radarr.extraAddFields must be a mapping"), "{}", err);
+    }
+
+    #[test]
+    fn example_config_matches_the_generated_schema() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config-example.yaml");
+        let example: serde_json::Value = serde_yaml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let schema = serde_json::to_value(Configuration::json_schema()).unwrap();
+
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let errors: Vec<String> = validator
+            .iter_errors(&example)
+            .map(|e| format!("{}: {}", e.instance_path, e))
+            .collect();
+        assert!(errors.is_empty(), "config-example.yaml does not match the schema:\n{}", errors.join("\n"));
+
+        serde_json::from_value::<Configuration>(example).unwrap();
+    }
+
+    #[test]
+    fn account_token_is_read_from_token_or_account_token() {
+        let plex: PlexConfig = serde_yaml::from_str("token: a\nserverToken: s").unwrap();
+        assert_eq!((plex.account_token.as_str(), plex.server_token.as_deref()), ("a", Some("s")));
+
+        let plex: PlexConfig = serde_yaml::from_str("accountToken: a").unwrap();
+        assert_eq!((plex.account_token.as_str(), plex.server_token), ("a", None));
+    }
+}
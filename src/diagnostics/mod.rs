@@ -0,0 +1,214 @@
+use crate::config::Configuration;
+use crate::http::HttpClient;
+use crate::plex::PlexClient;
+use crate::radarr::RadarrClient;
+use crate::sonarr::SonarrClient;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use tracing::{info, warn};
+
+const WATCHLIST_SAMPLE_SIZE: usize = 10;
+const REDACTED: &str = "[REDACTED]";
+
+/// Keys whose values are always replaced, matched case-insensitively as substrings
+const SECRET_KEY_MARKERS: [&str; 4] = ["token", "apikey", "password", "secret"];
+
+#[derive(Debug, Serialize)]
+struct DiagnosticsBundle {
+    generated_at: DateTime<Utc>,
+    version: &'static str,
+    config: Value,
+    services: Vec<ServiceDiagnostics>,
+    watchlist_sample: Vec<String>,
+    watchlist_total: Option<usize>,
+    error_count: usize,
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ServiceDiagnostics {
+    service: &'static str,
+    reachable: bool,
+    version: Option<String>,
+}
+
+/// Collect a local, secret-free snapshot of the setup for attaching to bug reports.
+/// Nothing is sent anywhere; the bundle is only written to `out`.
+pub async fn write_bundle(config: &Configuration, http_client: &HttpClient, out: &Path) -> Result<()> {
+    let mut errors = Vec::new();
+    let mut services = Vec::new();
+
+    if let Some(ref radarr_config) = config.radarr {
+        let client = RadarrClient::new(http_client.clone(), radarr_config.clone());
+        let status = client.get_system_status().await;
+        services.push(ServiceDiagnostics {
+            service: "radarr",
+            reachable: status.is_ok(),
+            version: status.as_ref().ok().map(|s| s.version.clone()),
+        });
+        if let Err(e) = status {
+            errors.push(format!("radarr system/status: {}", e));
+        }
+    }
+
+    if let Some(ref sonarr_config) = config.sonarr {
+        let client = SonarrClient::new(http_client.clone(), sonarr_config.clone());
+        let status = client.get_system_status().await;
+        services.push(ServiceDiagnostics {
+            service: "sonarr",
+            reachable: status.is_ok(),
+            version: status.as_ref().ok().map(|s| s.version.clone()),
+        });
+        if let Err(e) = status {
+            errors.push(format!("sonarr system/status: {}", e));
+        }
+    }
+
+    let mut watchlist_sample = Vec::new();
+    let mut watchlist_total = None;
+    if let Some(ref plex_config) = config.plex {
+        let plex_client = PlexClient::new(http_client.clone(), plex_config.clone());
+        match plex_client.get_watchlist().await {
+            Ok(items) => {
+                watchlist_total = Some(items.len());
+                watchlist_sample = items
+                    .iter()
+                    .take(WATCHLIST_SAMPLE_SIZE)
+                    .map(|i| i.item.title.clone())
+                    .collect();
+            }
+            Err(e) => errors.push(format!("plex watchlist: {}", e)),
+        }
+    }
+
+    let mut config_value = serde_json::to_value(config)?;
+    redact_keys(&mut config_value);
+
+    let bundle = DiagnosticsBundle {
+        generated_at: Utc::now(),
+        version: env!("CARGO_PKG_VERSION"),
+        config: config_value,
+        services,
+        watchlist_sample,
+        watchlist_total,
+        error_count: errors.len(),
+        errors,
+    };
+
+    // Error messages can echo request URLs, so scrub every known secret value
+    // from the rendered output as well as redacting the config keys
+    let rendered = scrub_secrets(&serde_json::to_string_pretty(&bundle)?, &secret_values(config));
+
+    if bundle.error_count > 0 {
+        warn!("Diagnostics collected with {} errors", bundle.error_count);
+    }
+
+    std::fs::write(out, rendered)?;
+    info!("Diagnostics bundle written to {}", out.display());
+    Ok(())
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEY_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+fn redact_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_secret_key(key) && !v.is_null() {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact_keys(v);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_keys),
+        _ => {}
+    }
+}
+
+fn secret_values(config: &Configuration) -> Vec<String> {
+    let mut secrets = Vec::new();
+    if let Some(ref plex) = config.plex {
+        secrets.push(plex.account_token.clone());
+        secrets.extend(plex.server_token.clone());
+        secrets.extend(plex.accounts.iter().flatten().map(|a| a.token.clone()));
+    }
+    if let Some(ref sonarr) = config.sonarr {
+        secrets.push(sonarr.api_key.clone());
+        secrets.extend(url_password(&sonarr.base_url));
+    }
+    if let Some(ref radarr) = config.radarr {
+        secrets.push(radarr.api_key.clone());
+        secrets.extend(url_password(&radarr.base_url));
+    }
+    if let Some(ref overseerr) = config.overseerr {
+        secrets.push(overseerr.api_key.clone());
+        secrets.extend(url_password(&overseerr.base_url));
+    }
+    secrets.retain(|s| !s.is_empty());
+    secrets
+}
+
+/// Basic-auth credentials embedded in a base URL are secrets too
+fn url_password(base_url: &str) -> Option<String> {
+    url::Url::parse(base_url)
+        .ok()
+        .and_then(|u| u.password().map(|p| p.to_string()))
+}
+
+fn scrub_secrets(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .fold(text.to_string(), |acc, secret| acc.replace(secret.as_str(), REDACTED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[tokio::test]
+    async fn bundle_contains_no_secret_values() {
+        let unreachable = test_support::unreachable_url().await;
+        let sonarr_url = unreachable.replace("http://", "http://admin:url-password@");
+        let mut config: Configuration = serde_yaml::from_str(&format!(
+            "radarr:\n  baseUrl: {}\n  apikey: radarr-key\nsonarr:\n  baseUrl: {}\n  apikey: sonarr-key\n",
+            unreachable, sonarr_url
+        ))
+        .unwrap();
+        let mut plex = test_support::plex_config(&unreachable, &unreachable);
+        plex.account_token = "plex-token".to_string();
+        plex.server_token = Some("server-token".to_string());
+        config.plex = Some(plex);
+        let out = test_support::temp_path("diagnostics.json");
+
+        write_bundle(&config, &test_support::http_client(), &out).await.unwrap();
+        let bundle = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        for secret in ["radarr-key", "sonarr-key", "url-password", "plex-token", "server-token"] {
+            assert!(!bundle.contains(secret), "{} leaked into the bundle", secret);
+        }
+        let bundle: Value = serde_json::from_str(&bundle).unwrap();
+        assert_eq!(bundle["config"]["radarr"]["apikey"], REDACTED);
+        assert_eq!(bundle["config"]["plex"]["token"], REDACTED);
+        assert_eq!(bundle["error_count"], 3);
+    }
+
+    #[test]
+    fn secret_keys_are_matched_case_insensitively_as_substrings() {
+        let mut value = serde_json::json!({ "serverToken": "a", "apiKey": "b", "nested": [{ "clientSecret": "c" }], "label": "d" });
+        redact_keys(&mut value);
+
+        assert_eq!(
+            value,
+            serde_json::json!({ "serverToken": REDACTED, "apiKey": REDACTED, "nested": [{ "clientSecret": REDACTED }], "label": "d" })
+        );
+    }
+}
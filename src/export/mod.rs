@@ -0,0 +1,144 @@
+use crate::models::{ItemType, WatchlistItem};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::Path;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportRow<'a> {
+    title: &'a str,
+    year: Option<i32>,
+    #[serde(rename = "type")]
+    item_type: &'a ItemType,
+    imdb_id: Option<&'a str>,
+    tmdb_id: Option<i32>,
+    tvdb_id: Option<i32>,
+    added_at: String,
+    user: &'a str,
+}
+
+const CSV_HEADER: &str = "title,year,type,imdb_id,tmdb_id,tvdb_id,added_at,user";
+
+impl<'a> From<&'a WatchlistItem> for ExportRow<'a> {
+    fn from(watchlist_item: &'a WatchlistItem) -> Self {
+        let item = &watchlist_item.item;
+        Self {
+            title: &item.title,
+            year: item.year,
+            item_type: &item.item_type,
+            imdb_id: item.imdb_id.as_deref(),
+            tmdb_id: item.tmdb_id,
+            tvdb_id: item.tvdb_id,
+            added_at: watchlist_item.added_at.to_rfc3339(),
+            user: &watchlist_item.user_id,
+        }
+    }
+}
+
+pub fn write_watchlist(items: &[WatchlistItem], format: ExportFormat, path: &Path) -> Result<()> {
+    let rows: Vec<ExportRow> = items.iter().map(ExportRow::from).collect();
+
+    let content = match format {
+        ExportFormat::Csv => render_csv(&rows),
+        ExportFormat::Json => serde_json::to_string_pretty(&rows)?,
+    };
+
+    std::fs::write(path, content)?;
+    info!("Exported {} watchlist items to {}", rows.len(), path.display());
+    Ok(())
+}
+
+fn render_csv(rows: &[ExportRow]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+
+    for row in rows {
+        let item_type = match row.item_type {
+            ItemType::Movie => "movie",
+            ItemType::Show => "show",
+        };
+        let fields = [
+            csv_escape(row.title),
+            optional(row.year),
+            item_type.to_string(),
+            csv_escape(row.imdb_id.unwrap_or_default()),
+            optional(row.tmdb_id),
+            optional(row.tvdb_id),
+            row.added_at.clone(),
+            csv_escape(row.user),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn optional(value: Option<i32>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use chrono::{TimeZone, Utc};
+
+    fn watchlist_item(title: &str, user_id: &str) -> WatchlistItem {
+        let mut item = test_support::item(ItemType::Movie, title, 1995);
+        item.imdb_id = Some("tt0113277".to_string());
+        item.tmdb_id = Some(949);
+        WatchlistItem {
+            item,
+            added_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            user_id: user_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn csv_export_writes_the_header_and_a_row_per_item() {
+        let path = test_support::temp_path("export.csv");
+
+        write_watchlist(&[watchlist_item("Heat", "self")], ExportFormat::Csv, &path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            written,
+            "title,year,type,imdb_id,tmdb_id,tvdb_id,added_at,user\n\
+             Heat,1995,movie,tt0113277,949,,2024-05-01T12:00:00+00:00,self\n"
+        );
+    }
+
+    #[test]
+    fn empty_watchlist_exports_only_the_header() {
+        assert_eq!(render_csv(&[]), format!("{}\n", CSV_HEADER));
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_commas_quotes_and_newlines() {
+        let items = [watchlist_item("Crouching Tiger, \"Hidden\" Dragon", "line\nbreak")];
+        let rows: Vec<ExportRow> = items.iter().map(ExportRow::from).collect();
+
+        let row = render_csv(&rows).lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            row,
+            "\"Crouching Tiger, \"\"Hidden\"\" Dragon\",1995,movie,tt0113277,949,,2024-05-01T12:00:00+00:00,\"line\nbreak\""
+        );
+    }
+}
@@ -1,33 +1,378 @@
 use anyhow::Result;
-use reqwest::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{redirect, Client, Method, RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
-use std::time::Duration;
-use tracing::{debug, error, instrument};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, error, instrument, warn};
+
+/// Non-success HTTP status, kept typed so callers can react to e.g. 401 vs 5xx
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP {method} failed: {status}")]
+pub struct HttpStatusError {
+    pub method: Method,
+    pub status: StatusCode,
+    /// Response body, for the backend's own explanation (e.g. validation errors)
+    pub body: String,
+}
+
+impl HttpStatusError {
+    async fn from_response(method: Method, response: Response) -> Self {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Self { method, status, body }
+    }
+
+    /// The response body of the HTTP status error anywhere in `error`'s chain
+    pub fn body_of(error: &anyhow::Error) -> Option<&str> {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<HttpStatusError>())
+            .map(|e| e.body.as_str())
+    }
+
+    /// Whether `error` (anywhere in its chain) is an HTTP status error with `status`
+    pub fn is_status(error: &anyhow::Error, status: StatusCode) -> bool {
+        error
+            .chain()
+            .filter_map(|e| e.downcast_ref::<HttpStatusError>())
+            .any(|e| e.status == status)
+    }
+}
+
+/// A JSON API answered with a login page, typically an SSO proxy such as
+/// Authelia or Authentik intercepting requests that carry no session
+#[derive(Debug, thiserror::Error)]
+#[error("{url} returned a login page instead of JSON, the backend appears to be behind an auth portal; \
+configure credentials or exempt the API from the portal")]
+pub struct AuthPortalError {
+    /// Origin and path only, the query string may carry an API key
+    pub url: String,
+}
+
+/// Request counts and time spent waiting on responses, grouped by sync
+/// phase and host, for `sync --profile`
+#[derive(Debug, Default)]
+pub struct RequestProfile {
+    /// Keyed by (phase, host)
+    entries: Mutex<BTreeMap<(&'static str, String), PhaseStats>>,
+}
+
+#[derive(Debug, Default)]
+struct PhaseStats {
+    requests: u32,
+    elapsed: Duration,
+}
+
+impl RequestProfile {
+    fn record(&self, method: &Method, url: &str, elapsed: Duration) {
+        let parsed = Url::parse(url).ok();
+        let host = parsed
+            .as_ref()
+            .and_then(|u| u.host_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let path = parsed.as_ref().map(|u| u.path()).unwrap_or_default();
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.entry((request_phase(method, &host, path), host)).or_default();
+        entry.requests += 1;
+        entry.elapsed += elapsed;
+    }
+
+    /// A table of requests and time per phase and host
+    pub fn report(&self) -> String {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let host_width = entries.keys().map(|(_, host)| host.len()).max().unwrap_or(0).max("Host".len());
+
+        let mut table = String::new();
+        let _ = writeln!(table, "{:<16} {:<host_width$} {:>8} {:>10}", "Phase", "Host", "Requests", "Time");
+        for ((phase, host), stats) in entries.iter() {
+            let _ = writeln!(table, "{:<16} {:<host_width$} {:>8} {:>9.2}s",
+                             phase, host, stats.requests, stats.elapsed.as_secs_f64());
+        }
+        table
+    }
+}
+
+/// Which part of a sync a request belongs to, judged from its URL
+fn request_phase(method: &Method, host: &str, path: &str) -> &'static str {
+    let last_segment = path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    if host.ends_with("plex.tv") || path.contains("/watchlist") {
+        "Plex watchlist"
+    } else if last_segment == "lookup" || last_segment == "search" {
+        "Lookups"
+    } else if *method == Method::GET && matches!(last_segment, "movie" | "series") {
+        "Library fetches"
+    } else if *method == Method::POST && matches!(last_segment, "movie" | "series" | "request") {
+        "Adds"
+    } else {
+        "Other"
+    }
+}
+
+/// A write request refused because the client is in read-only mode
+#[derive(Debug, thiserror::Error)]
+#[error("Refusing HTTP {method} to {url}: running in read-only mode")]
+pub struct ReadOnlyError {
+    pub method: Method,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further retry up to
+    /// five minutes
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Longest wait between retries; the doubling stops growing here
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+impl RetryPolicy {
+    /// Wait before retry number `retry`, counting from 1. Saturates at
+    /// MAX_BACKOFF instead of overflowing when maxAttempts is large.
+    pub fn backoff_delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.checked_mul(factor).map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+    }
+}
+
+/// Token bucket shared by every clone of a client, so a widespread outage
+/// degrades into fast failures instead of multiplying load with retries
+#[derive(Debug)]
+pub struct RetryBudget {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RetryBudget {
+    pub fn new(max_retries: u32, window: Duration) -> Self {
+        let capacity = max_retries as f64;
+        Self {
+            capacity,
+            refill_per_sec: capacity / window.as_secs_f64().max(1.0),
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Take one retry token, returning false when the budget is exhausted
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (ref mut tokens, ref mut last_refill) = *state;
+
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*last_refill).as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        *last_refill = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Which redirects the client follows. API keys travel in headers and query
+/// strings, so following a redirect to another host would hand them over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectPolicy {
+    /// Follow redirects on the same host, including an http to https upgrade
+    #[default]
+    SameOrigin,
+    /// Never follow, treat the redirect as the response
+    None,
+    /// Follow anything (reqwest's behaviour); may leak API keys
+    Any,
+}
+
+const MAX_REDIRECTS: usize = 10;
+/// Longest Retry-After wait honoured; anything longer is cut down to this
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+impl RedirectPolicy {
+    fn to_reqwest(self) -> redirect::Policy {
+        match self {
+            RedirectPolicy::None => redirect::Policy::none(),
+            RedirectPolicy::Any => redirect::Policy::limited(MAX_REDIRECTS),
+            RedirectPolicy::SameOrigin => redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() > MAX_REDIRECTS {
+                    return attempt.error("too many redirects");
+                }
+                let Some(origin) = attempt.previous().first() else {
+                    return attempt.follow();
+                };
+                if is_same_origin(origin, attempt.url()) {
+                    attempt.follow()
+                } else {
+                    let message = format!(
+                        "refusing to follow redirect from {} to another origin ({}), point the base URL at it directly",
+                        origin.host_str().unwrap_or_default(),
+                        attempt.url().host_str().unwrap_or_default()
+                    );
+                    attempt.error(message)
+                }
+            }),
+        }
+    }
+}
+
+/// Same host, and same scheme and port or a plain http to https upgrade
+fn is_same_origin(from: &Url, to: &Url) -> bool {
+    if from.host_str() != to.host_str() {
+        return false;
+    }
+    let upgrade = from.scheme() == "http" && to.scheme() == "https";
+    upgrade || (from.scheme() == to.scheme() && from.port_or_known_default() == to.port_or_known_default())
+}
+
+tokio::task_local! {
+    /// Correlation id of the logical operation in progress
+    static REQUEST_ID: String;
+}
+
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Run `f` with every request it makes carrying `request_id` as X-Request-Id
+pub async fn with_request_id<F: Future>(request_id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(request_id, f).await
+}
+
+/// Spawn `f` carrying the current request id, so requests it makes after
+/// the caller has moved on still match up with the item they belong to
+pub fn spawn_with_request_id<F>(f: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match REQUEST_ID.try_with(|id| id.clone()) {
+        Ok(request_id) => tokio::spawn(REQUEST_ID.scope(request_id, f)),
+        Err(_) => tokio::spawn(f),
+    }
+}
 
 #[derive(Clone)]
 pub struct HttpClient {
     client: Client,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    retry_budget: Option<Arc<RetryBudget>>,
+    headers: HeaderMap,
+    read_only: bool,
+    profile: Option<Arc<RequestProfile>>,
 }
 
 impl HttpClient {
-    pub fn new() -> Self {
+    pub fn new(redirects: RedirectPolicy) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
+            .redirect(redirects.to_reqwest())
             .user_agent("watchlistarr-rust/0.1.0")
             .build()
             .expect("Failed to create HTTP client");
         
-        Self { client }
+        Self {
+            client,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            retry_budget: None,
+            headers: HeaderMap::new(),
+            read_only: false,
+            profile: None,
+        }
+    }
+
+    /// Share the connection pool but apply a different per-request timeout,
+    /// for backends that are slower than the global default
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Retry idempotent requests per `policy`, drawing every retry from `budget`
+    pub fn with_retry(&self, policy: RetryPolicy, budget: Option<Arc<RetryBudget>>) -> Self {
+        Self {
+            retry: policy,
+            retry_budget: budget,
+            ..self.clone()
+        }
+    }
+
+    /// Count and time every request in `profile`
+    pub fn with_profile(&self, profile: Arc<RequestProfile>) -> Self {
+        Self {
+            profile: Some(profile),
+            ..self.clone()
+        }
+    }
+
+    /// Replace the retry policy, keeping the shared retry budget
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        Self {
+            retry: policy,
+            ..self.clone()
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// Send `name: value` with every request, for APIs that authenticate by
+    /// header rather than query string
+    pub fn with_header(&self, name: &'static str, value: &str) -> Result<Self> {
+        let mut headers = self.headers.clone();
+        let mut value = HeaderValue::from_str(value)?;
+        value.set_sensitive(true);
+        headers.insert(HeaderName::from_static(name), value);
+        Ok(Self {
+            headers,
+            ..self.clone()
+        })
+    }
+
+    /// Refuse every POST/PUT/DELETE, so nothing can change any backend
+    /// whatever code path asks for it. Only `post_json_read`, for POSTs that
+    /// are really queries, still goes out.
+    pub fn with_read_only(&self, read_only: bool) -> Self {
+        Self {
+            read_only,
+            ..self.clone()
+        }
     }
 
     #[instrument(skip(self), fields(url = %url))]
     pub async fn get(&self, url: &str) -> Result<Response> {
         debug!("Making GET request");
-        let response = self.client.get(url).send().await?;
+        let response = self.send_with_retry(Method::GET, url, None).await?;
         
         if !response.status().is_success() {
             error!("HTTP request failed with status: {}", response.status());
-            return Err(anyhow::anyhow!("HTTP request failed: {}", response.status()));
+            return Err(HttpStatusError::from_response(Method::GET, response).await.into());
         }
         
         Ok(response)
@@ -36,42 +381,403 @@ impl HttpClient {
     #[instrument(skip(self), fields(url = %url))]
     pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
         let response = self.get(url).await?;
-        let json = response.json::<T>().await?;
-        Ok(json)
+        read_json(response).await
     }
 
     #[instrument(skip(self, body), fields(url = %url))]
     pub async fn post_json<T: DeserializeOwned, B: serde::Serialize>(&self, url: &str, body: &B) -> Result<T> {
         debug!("Making POST request");
-        let response = self.client
-            .post(url)
-            .json(body)
-            .send()
-            .await?;
+        self.ensure_writable(Method::POST, url)?;
+        let request = self.request(Method::POST, url).json(body);
+        let response = self.send(request, &Method::POST, url).await?;
         
         if !response.status().is_success() {
             error!("HTTP POST failed with status: {}", response.status());
-            return Err(anyhow::anyhow!("HTTP POST failed: {}", response.status()));
+            return Err(HttpStatusError::from_response(Method::POST, response).await.into());
         }
         
-        let json = response.json::<T>().await?;
-        Ok(json)
+        read_json(response).await
     }
 
+    /// POST that only reads, like a GraphQL query. Allowed in read-only mode
+    /// and retried like a GET.
+    #[instrument(skip(self, body), fields(url = %url))]
+    pub async fn post_json_read<T: DeserializeOwned, B: serde::Serialize>(&self, url: &str, body: &B) -> Result<T> {
+        debug!("Making read-only POST request");
+        let body = serde_json::to_value(body)?;
+        let response = self.send_with_retry(Method::POST, url, Some(&body)).await?;
+
+        if !response.status().is_success() {
+            error!("HTTP POST failed with status: {}", response.status());
+            return Err(HttpStatusError::from_response(Method::POST, response).await.into());
+        }
+
+        read_json(response).await
+    }
+
+    #[instrument(skip(self, body), fields(url = %url))]
+    pub async fn put_json<T: DeserializeOwned, B: serde::Serialize>(&self, url: &str, body: &B) -> Result<T> {
+        debug!("Making PUT request");
+        self.ensure_writable(Method::PUT, url)?;
+        let request = self.request(Method::PUT, url).json(body);
+        let response = self.send(request, &Method::PUT, url).await?;
+        
+        if !response.status().is_success() {
+            error!("HTTP PUT failed with status: {}", response.status());
+            return Err(HttpStatusError::from_response(Method::PUT, response).await.into());
+        }
+        
+        read_json(response).await
+    }
+
+    #[allow(dead_code)]
     #[instrument(skip(self), fields(url = %url))]
     pub async fn delete(&self, url: &str) -> Result<()> {
         debug!("Making DELETE request");
-        let response = self.client.delete(url).send().await?;
+        self.ensure_writable(Method::DELETE, url)?;
+        let response = self.send(self.request(Method::DELETE, url), &Method::DELETE, url).await?;
         
         if !response.status().is_success() {
             error!("HTTP DELETE failed with status: {}", response.status());
-            return Err(anyhow::anyhow!("HTTP DELETE failed: {}", response.status()));
+            return Err(HttpStatusError::from_response(Method::DELETE, response).await.into());
         }
         
         Ok(())
     }
 
-    pub fn request(&self, method: reqwest::Method, url: &str) -> RequestBuilder {
-        self.client.request(method, url)
+    async fn send_with_retry(&self, method: Method, url: &str, body: Option<&serde_json::Value>) -> reqwest::Result<Response> {
+        let mut attempt = 1;
+
+        loop {
+            let request = match body {
+                Some(body) => self.request(method.clone(), url).json(body),
+                None => self.request(method.clone(), url),
+            };
+            let result = self.send(request, &method, url).await;
+
+            let retryable = match result {
+                Ok(ref response) => is_retryable_status(response.status()),
+                Err(ref e) => e.is_timeout() || e.is_connect(),
+            };
+            if !retryable || attempt >= self.retry.max_attempts {
+                return result;
+            }
+
+            // A backend that says when to come back (e.g. Sonarr/Radarr
+            // migrating their database on startup) is busy, not down, so
+            // the wait doesn't draw on the retry budget
+            let requested = result
+                .as_ref()
+                .ok()
+                .and_then(|response| Some((response.status(), retry_after(response)?)));
+            let delay = match requested {
+                Some((status, delay)) => {
+                    warn!("{} attempt {} of {} got {}, retrying in {:?} as asked by Retry-After",
+                          method, attempt, self.retry.max_attempts, status, delay);
+                    delay
+                }
+                None => {
+                    if let Some(ref budget) = self.retry_budget {
+                        if !budget.try_acquire() {
+                            warn!("Retry budget exhausted, failing {} without retrying", method);
+                            return result;
+                        }
+                    }
+
+                    let delay = self.retry.backoff_delay(attempt);
+                    warn!("{} attempt {} of {} failed, retrying in {:?}", method, attempt, self.retry.max_attempts, delay);
+                    delay
+                }
+            };
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, method: &Method, url: &str) -> reqwest::Result<Response> {
+        let started = Instant::now();
+        let result = request.send().await;
+        if let Some(ref profile) = self.profile {
+            profile.record(method, url, started.elapsed());
+        }
+        result
+    }
+
+    fn ensure_writable(&self, method: Method, url: &str) -> Result<()> {
+        if self.read_only {
+            warn!("Read-only mode, not sending {} {}", method, url);
+            return Err(ReadOnlyError { method, url: url.to_string() }.into());
+        }
+        Ok(())
+    }
+
+    /// Private so every write goes through a method that checks read-only mode
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let mut request = self.client.request(method, url).headers(self.headers.clone());
+        if let Ok(request_id) = REQUEST_ID.try_with(|id| id.clone()) {
+            request = request.header("x-request-id", request_id);
+        }
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+}
+
+/// Deserialize a JSON body, explaining HTML responses instead of failing
+/// with a bare "expected value at line 1"
+async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|content_type| content_type.trim_start().starts_with("text/html"));
+    if !is_html {
+        return Ok(response.json::<T>().await?);
+    }
+
+    let url = format!("{}{}", response.url().origin().ascii_serialization(), response.url().path());
+    let body = response.text().await.unwrap_or_default();
+    if looks_like_login_page(&body) {
+        return Err(AuthPortalError { url }.into());
+    }
+    anyhow::bail!("{} returned an HTML page instead of JSON, check the configured base URL", url)
+}
+
+fn looks_like_login_page(html: &str) -> bool {
+    let html = html.to_lowercase();
+    html.contains("type=\"password\"")
+        || html.contains("type='password'")
+        || ["authelia", "authentik", "oauth2-proxy", "sign in", "log in", "login"]
+            .iter()
+            .any(|marker| html.contains(marker))
+}
+
+/// Delay from a 429/503's Retry-After header, in seconds or as an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    if !matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+        return None;
+    }
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO)
+        }
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use axum::http::StatusCode as Status;
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Stand-in answering GET / with each status of `script` in turn, with
+    /// its Retry-After header if any, then `{}` once the script runs out
+    async fn scripted_server(script: Vec<(Status, Option<&'static str>)>) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let router = Router::new().route("/", get(move || {
+            let hit = counter.fetch_add(1, Ordering::SeqCst);
+            let scripted = script.get(hit).copied();
+            async move {
+                match scripted {
+                    Some((status, Some(retry_after))) => (status, [("retry-after", retry_after)]).into_response(),
+                    Some((status, None)) => status.into_response(),
+                    None => axum::Json(serde_json::json!({})).into_response(),
+                }
+            }
+        }));
+        (test_support::serve(router).await, hits)
+    }
+
+    /// Stand-in recording the X-Api-Key of each request to /landing, which
+    /// /same redirects to with a 308, as /away does to `elsewhere`
+    async fn redirecting_server(elsewhere: &str) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let keys: Arc<Mutex<Vec<Option<String>>>> = Arc::default();
+        let recorded = Arc::clone(&keys);
+        let away = format!("{}/landing", elsewhere);
+        let router = Router::new()
+            .route("/same", get(|| async { (Status::PERMANENT_REDIRECT, [("location", "/landing")]) }))
+            .route("/away", get(move || async move { (Status::PERMANENT_REDIRECT, [("location", away)]) }))
+            .route("/landing", get(move |headers: axum::http::HeaderMap| async move {
+                let key = headers.get("x-api-key").and_then(|v| v.to_str().ok()).map(str::to_string);
+                recorded.lock().unwrap().push(key);
+                axum::Json(serde_json::json!({}))
+            }));
+        (test_support::serve(router).await, keys)
+    }
+
+    #[tokio::test]
+    async fn api_key_follows_same_origin_redirects_only() {
+        let (other_url, other_keys) = redirecting_server("http://unused").await;
+        let (url, keys) = redirecting_server(&other_url).await;
+        let http = test_support::http_client().with_header("x-api-key", "secret").unwrap();
+
+        assert!(http.get(&format!("{}/same", url)).await.unwrap().status().is_success());
+        assert_eq!(*keys.lock().unwrap(), [Some("secret".to_string())]);
+
+        assert!(http.get(&format!("{}/away", url)).await.is_err());
+        assert!(other_keys.lock().unwrap().is_empty(), "the key was sent to another origin");
+    }
+
+    #[test]
+    fn same_origin_allows_an_https_upgrade_but_not_another_host_or_port() {
+        let url = |s: &str| Url::parse(s).unwrap();
+
+        assert!(is_same_origin(&url("http://arr.local/api"), &url("https://arr.local/api/")));
+        assert!(!is_same_origin(&url("https://arr.local/"), &url("http://arr.local/")));
+        assert!(!is_same_origin(&url("http://arr.local:7878/"), &url("http://arr.local:8989/")));
+        assert!(!is_same_origin(&url("http://arr.local/"), &url("http://evil.example/")));
+    }
+
+    #[test]
+    fn backoff_doubles_per_retry_and_saturates() {
+        let policy = RetryPolicy { max_attempts: 100, backoff: Duration::from_millis(500) };
+
+        let delays: Vec<_> = (1..=4).map(|retry| policy.backoff_delay(retry)).collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000].map(Duration::from_millis));
+
+        assert_eq!(policy.backoff_delay(33), MAX_BACKOFF);
+        assert_eq!(policy.backoff_delay(u32::MAX), MAX_BACKOFF);
+
+        let huge = RetryPolicy { max_attempts: 3, backoff: Duration::MAX };
+        assert_eq!(huge.backoff_delay(2), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_until_they_clear() {
+        let (url, hits) = scripted_server(vec![(Status::BAD_GATEWAY, None), (Status::SERVICE_UNAVAILABLE, None)]).await;
+        let http = test_support::http_client().with_retry(RetryPolicy { max_attempts: 3, backoff: Duration::ZERO }, None);
+
+        assert!(http.get(&url).await.unwrap().status().is_success());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn exhausted_retry_budget_fails_without_retrying() {
+        let (url, hits) = scripted_server(vec![(Status::INTERNAL_SERVER_ERROR, None); 10]).await;
+        let policy = RetryPolicy { max_attempts: 10, backoff: Duration::ZERO };
+        let http = test_support::http_client().with_retry(policy, Some(Arc::new(RetryBudget::new(1, Duration::from_secs(3600)))));
+
+        assert!(http.get(&url).await.is_err());
+        // The first attempt, then the budget's one retry
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // The budget is shared, so the next request doesn't retry at all
+        assert!(http.get(&url).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    /// Stand-in answering `{}` to POST / and counting the requests it gets
+    async fn counting_server() -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let router = Router::new().route("/", post(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { axum::Json(serde_json::json!({})) }
+        }));
+        (test_support::serve(router).await, hits)
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honoured_without_drawing_on_the_budget() {
+        let (url, hits) = scripted_server(vec![
+            (Status::SERVICE_UNAVAILABLE, Some("0")),
+            (Status::TOO_MANY_REQUESTS, Some("0")),
+        ])
+        .await;
+        let policy = RetryPolicy { max_attempts: 3, backoff: Duration::ZERO };
+        // An empty budget would refuse any ordinary retry
+        let http = test_support::http_client().with_retry(policy, Some(Arc::new(RetryBudget::new(0, Duration::from_secs(3600)))));
+
+        let response = http.get(&url).await.unwrap();
+
+        assert!(response.status().is_success());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn a_503_is_waited_out_for_as_long_as_retry_after_asks() {
+        let (url, hits) = scripted_server(vec![(Status::SERVICE_UNAVAILABLE, Some("1"))]).await;
+        let policy = RetryPolicy { max_attempts: 2, backoff: Duration::ZERO };
+        let http = test_support::http_client().with_retry(policy, None);
+        let started = Instant::now();
+
+        let response = http.get(&url).await.unwrap();
+
+        assert!(response.status().is_success());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn retry_after_does_not_extend_max_attempts() {
+        let (url, hits) = scripted_server(vec![(Status::SERVICE_UNAVAILABLE, Some("0")); 5]).await;
+        let policy = RetryPolicy { max_attempts: 2, backoff: Duration::ZERO };
+        let http = test_support::http_client().with_retry(policy, None);
+
+        assert!(http.get(&url).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn read_only_refuses_post_without_sending_it() {
+        let (url, hits) = counting_server().await;
+        let http = test_support::http_client().with_read_only(true);
+
+        let error = http.post_json::<serde_json::Value, _>(&url, &serde_json::json!({})).await.unwrap_err();
+
+        assert!(error.downcast_ref::<ReadOnlyError>().is_some());
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn read_only_still_sends_query_posts() {
+        let (url, hits) = counting_server().await;
+        let http = test_support::http_client().with_read_only(true);
+
+        let _: serde_json::Value = http.post_json_read(&url, &serde_json::json!({})).await.unwrap();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    /// Stand-in answering GET / with a 200 `text/html` page
+    async fn html_server(html: &'static str) -> String {
+        let router = Router::new().route("/", get(move || async move { axum::response::Html(html) }));
+        test_support::serve(router).await
+    }
+
+    #[tokio::test]
+    async fn login_pages_are_reported_as_an_auth_portal() {
+        let url = html_server(r#"<html><title>Login - Authelia</title><form><input type="password" name="password"></form></html>"#).await;
+
+        let error = test_support::http_client().get_json::<serde_json::Value>(&format!("{}/?apikey=secret", url)).await.unwrap_err();
+
+        assert!(error.downcast_ref::<AuthPortalError>().is_some(), "{}", error);
+        let message = error.to_string();
+        assert!(message.contains("behind an auth portal; configure credentials"), "{}", message);
+        assert!(!message.contains("secret"), "the query string leaked: {}", message);
+    }
+
+    #[tokio::test]
+    async fn other_html_pages_point_at_the_base_url() {
+        let url = html_server("<html><body>Welcome to nginx!</body></html>").await;
+
+        let error = test_support::http_client().get_json::<serde_json::Value>(&url).await.unwrap_err();
+
+        assert!(error.downcast_ref::<AuthPortalError>().is_none());
+        assert!(error.to_string().contains("check the configured base URL"), "{}", error);
+    }
+}
@@ -1,22 +1,42 @@
+mod arr;
 mod config;
+mod diagnostics;
+mod export;
 mod http;
+mod metrics;
 mod models;
+mod overseerr;
 mod plex;
 mod radarr;
+mod server;
 mod sonarr;
+mod status;
+mod systemd;
+mod template;
+#[cfg(test)]
+mod test_support;
 
-use anyhow::Result;
-use clap::Parser;
-use config::Configuration;
-use http::HttpClient;
-use models::ItemType;
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use config::{AddOrder, Configuration, RouteConfig, Target};
+use export::ExportFormat;
+use http::{HttpClient, HttpStatusError, RequestProfile};
+use models::{AddOutcome, Item, ItemType, SkipReason, WatchlistItem};
+use overseerr::OverseerrClient;
 use plex::PlexClient;
+use reqwest::StatusCode;
 use radarr::RadarrClient;
 use sonarr::SonarrClient;
+use status::{SkippedItem, SyncReport, SyncStatus};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::{interval, sleep};
-use tracing::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, interval_at, sleep, timeout, MissedTickBehavior};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -25,36 +45,172 @@ struct Cli {
     #[arg(short, long, default_value = "config.yaml")]
     config: String,
 
-    /// Log level
-    #[arg(short, long, default_value = "info")]
-    log_level: String,
+    /// Log level or filter directive, overrides -v
+    #[arg(short, long)]
+    log_level: Option<String>,
+
+    /// More verbose logging: -v for debug, -vv for trace
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Log output format
+    #[arg(long, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Disable colored log output (also disabled when stdout is not a terminal or NO_COLOR is set)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Run one full sync before starting the scheduled syncs, then keep running
+    #[arg(long)]
+    initial_sync: bool,
+
+    /// Never send POST/PUT/DELETE to any backend; writes fail with an error
+    #[arg(long)]
+    read_only: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the selected sync once and exit instead of running as a daemon
+    Sync {
+        /// Which sync to run
+        #[arg(long, value_enum, default_value = "all")]
+        mode: SyncMode,
+
+        /// Only sync items for this service, leaving the other untouched
+        #[arg(long, value_enum)]
+        service: Option<Service>,
+
+        /// Print requests and time per sync phase and host afterwards
+        #[arg(long)]
+        profile: bool,
+    },
+    /// Write the combined (owner + friends) watchlist to a file and exit
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
+        /// Destination file
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Write a local, secret-redacted diagnostics bundle for bug reports and exit
+    Diagnostics {
+        /// Destination file
+        #[arg(long, default_value = "watchlistarr-diagnostics.json")]
+        out: PathBuf,
+    },
+    /// Print the JSON Schema for the configuration file and exit
+    Schema,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum LogFormat {
+    /// Compact human-readable lines, colored on a terminal
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Service {
+    /// Movies only
+    Radarr,
+    /// Shows only
+    Sonarr,
+}
+
+impl Service {
+    fn item_type(self) -> ItemType {
+        match self {
+            Service::Radarr => ItemType::Movie,
+            Service::Sonarr => ItemType::Show,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum SyncMode {
+    Full,
+    Rss,
+    Delete,
+    /// Full sync followed by delete sync
+    All,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(&cli.log_level)
-        .init();
+    // Printed before logging starts so stdout holds only the schema
+    if let Some(Command::Schema) = cli.command {
+        println!("{}", serde_json::to_string_pretty(&Configuration::json_schema())?);
+        return Ok(());
+    }
 
-    info!("Starting Watchlistarr Rust v0.1.0");
+    // Loaded before logging starts, as progress logging changes the filter
+    let mut config = Configuration::from_file(&cli.config)?;
+
+    init_tracing(&cli, &config);
 
-    // Load configuration
-    let config = Arc::new(Configuration::from_file(&cli.config)?);
+    info!("Starting Watchlistarr Rust v0.1.0");
     info!("Configuration loaded from: {}", cli.config);
 
+    // Reuse one identifier across runs so Plex sees a single device
+    if let Some(ref mut plex_config) = config.plex {
+        if plex_config.client_identifier.is_none() {
+            let path = Path::new(&cli.config).with_file_name(PLEX_CLIENT_IDENTIFIER_FILE);
+            plex_config.client_identifier = Some(plex::load_client_identifier(&path));
+        }
+    }
+    let config = Arc::new(config);
+
     // Initialize HTTP client
-    let http_client = HttpClient::new();
-
-    // Start sync tasks
-    let sync_tasks = vec![
-        tokio::spawn(ping_token_sync(Arc::clone(&config), http_client.clone())),
-        tokio::spawn(plex_rss_sync(Arc::clone(&config), http_client.clone())),
-        tokio::spawn(plex_full_sync(Arc::clone(&config), http_client.clone())),
-        tokio::spawn(plex_delete_sync(Arc::clone(&config), http_client.clone())),
-    ];
+    let http_client = HttpClient::new(config.redirect_policy())
+        .with_retry(config.retry_policy(), Some(config.retry_budget()))
+        .with_read_only(cli.read_only);
+    if cli.read_only {
+        warn!("Read-only mode: no changes will be made to any backend");
+    }
+
+    match cli.command {
+        Some(Command::Sync { mode, service, profile }) => {
+            config.validate()?;
+            warn_if_no_sync_targets(&config);
+            if !profile {
+                return run_once(&config, &http_client, mode, service.map(Service::item_type)).await;
+            }
+
+            let request_profile = Arc::new(RequestProfile::default());
+            let http_client = http_client.with_profile(Arc::clone(&request_profile));
+            let started = Instant::now();
+            let result = run_once(&config, &http_client, mode, service.map(Service::item_type)).await;
+            println!("\n{}Total: {:.2}s", request_profile.report(), started.elapsed().as_secs_f64());
+            return result;
+        }
+        Some(Command::Export { format, out }) => {
+            return run_export(&config, &http_client, format, &out).await;
+        }
+        Some(Command::Diagnostics { out }) => {
+            return diagnostics::write_bundle(&config, &http_client, &out).await;
+        }
+        Some(Command::Schema) => unreachable!("handled before loading the configuration"),
+        None => config.validate()?,
+    }
+
+    warn_if_no_sync_targets(&config);
+
+    if let Some(timeout) = config.wait_for_arr() {
+        wait_for_arr(&config, &http_client, timeout).await;
+    }
+
+    let status = SyncStatus::new();
+    let sync_tasks = start_syncs(&config, &http_client, &status, cli.initial_sync).await;
 
     // Wait for all tasks (they run forever)
     for task in sync_tasks {
@@ -66,51 +222,282 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn ping_token_sync(config: Arc<Configuration>, http_client: HttpClient) -> Result<()> {
-    let mut interval = interval(Duration::from_secs(24 * 60 * 60)); // 24 hours
-    
+/// Spawn the scheduled syncs, the status server, the StatsD sink and the
+/// systemd notifier.
+/// With `initial_sync`, a full sync runs first and is recorded like a
+/// scheduled one, so a failure counts toward the backoff.
+async fn start_syncs(config: &Arc<Configuration>, http_client: &HttpClient, status: &SyncStatus, initial_sync: bool) -> Vec<JoinHandle<Result<()>>> {
+    if initial_sync {
+        info!("Running initial full sync before starting scheduled syncs");
+        let period = config.full_sync_interval().or_else(|| config.refresh_interval()).unwrap_or(Duration::MAX);
+        run_scheduled_sync(config, http_client, status, true, period).await;
+    }
+
+    let mut sync_tasks = vec![
+        tokio::spawn(ping_token_sync(Arc::clone(config), http_client.clone(), status.clone())),
+        tokio::spawn(plex_rss_sync(Arc::clone(config), http_client.clone(), status.clone())),
+        tokio::spawn(plex_full_sync(Arc::clone(config), http_client.clone(), status.clone(), initial_sync)),
+        tokio::spawn(plex_delete_sync(Arc::clone(config), http_client.clone(), status.clone())),
+    ];
+
+    if let Some(ref server_config) = config.server {
+        sync_tasks.push(tokio::spawn(server::serve(server_config.bind.clone(), status.clone())));
+    }
+
+    if let Some(ref statsd_config) = config.statsd {
+        #[cfg(feature = "statsd")]
+        sync_tasks.push(tokio::spawn(metrics::push_statsd(statsd_config.clone(), status.clone())));
+        #[cfg(not(feature = "statsd"))]
+        warn!("Ignoring statsd ({}), this build does not include the statsd feature", statsd_config.address);
+    }
+
+    // With no scheduled syncs there is no first sync to wait for
+    let scheduled = config.refresh_interval().is_some() || config.full_sync_interval().is_some();
+    sync_tasks.push(tokio::spawn(systemd::supervise(systemd::Systemd, status.clone(), scheduled)));
+
+    sync_tasks
+}
+
+fn warn_if_no_sync_targets(config: &Configuration) {
+    if !config.has_sync_targets() {
+        warn!("None of sonarr, radarr or overseerr is configured, syncs will be skipped");
+    }
+}
+
+/// Sidecar file, next to the config file, holding the generated Plex client identifier
+const PLEX_CLIENT_IDENTIFIER_FILE: &str = ".plex-client-identifier";
+
+/// How often to poll a backend that is not answering yet at startup
+const ARR_READY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Hold off the first sync until each configured arr answers or `timeout`
+/// passes; a backend that never comes up is left to the per-sync pre-flight
+async fn wait_for_arr(config: &Configuration, http_client: &HttpClient, timeout: Duration) {
+    let radarr = async {
+        if let Some(ref radarr_config) = config.radarr {
+            let client = RadarrClient::new(http_client.clone(), radarr_config.clone());
+            wait_until_ready("Radarr", timeout, ARR_READY_POLL_INTERVAL, || client.get_system_status()).await;
+        }
+    };
+    let sonarr = async {
+        if let Some(ref sonarr_config) = config.sonarr {
+            let client = SonarrClient::new(http_client.clone(), sonarr_config.clone());
+            wait_until_ready("Sonarr", timeout, ARR_READY_POLL_INTERVAL, || client.get_system_status()).await;
+        }
+    };
+
+    tokio::join!(radarr, sonarr);
+}
+
+/// Run `check` every `poll` until it succeeds or `timeout` would be passed
+async fn wait_until_ready<F, Fut, T>(service: &str, timeout: Duration, poll: Duration, check: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut attempt = 1;
+
     loop {
-        interval.tick().await;
-        
-        if let Some(ref plex_config) = config.plex {
-            info!("Running token ping sync");
-            
-            let plex_client = PlexClient::new(http_client.clone(), plex_config.clone());
-            
-            match plex_client.get_watchlist().await {
-                Ok(_) => debug!("Token ping successful"),
-                Err(e) => warn!("Token ping failed: {}", e),
+        match check().await {
+            Ok(_) if attempt == 1 => return,
+            Ok(_) => {
+                info!("{} is ready after {} attempts", service, attempt);
+                return;
+            }
+            Err(e) if Instant::now() + poll > deadline => {
+                warn!("{} still not answering after {}s, starting without waiting further: {}", service, timeout.as_secs(), e);
+                return;
+            }
+            Err(e) => {
+                info!("Waiting for {} to start (attempt {}): {}", service, attempt, e);
+            }
+        }
+
+        sleep(poll).await;
+        attempt += 1;
+    }
+}
+
+fn init_tracing(cli: &Cli, config: &Configuration) {
+    let builder = tracing_subscriber::fmt().with_env_filter(log_filter(cli, config));
+
+    match cli.log_format {
+        LogFormat::Json => builder.json().init(),
+        LogFormat::Text => builder.compact().with_ansi(use_color(cli, std::io::stdout().is_terminal())).init(),
+    }
+}
+
+/// Colored text logs, unless `--no-color`, `NO_COLOR` or output that isn't a terminal
+fn use_color(cli: &Cli, is_terminal: bool) -> bool {
+    !cli.no_color && std::env::var_os("NO_COLOR").is_none() && is_terminal
+}
+
+/// `--log-level` when given, else info raised by each `-v`
+fn log_filter(cli: &Cli, config: &Configuration) -> String {
+    if let Some(ref log_level) = cli.log_level {
+        return log_level.clone();
+    }
+    match cli.verbose {
+        // The clients log every item at info; progress logging leaves them
+        // to warnings and reports progress from the sync loop instead
+        0 if config.progress_logging().is_some() => {
+            "info,watchlistarr::plex=warn,watchlistarr::radarr=warn,watchlistarr::sonarr=warn,watchlistarr::overseerr=warn".to_string()
+        }
+        0 => "info".to_string(),
+        1 => "debug".to_string(),
+        _ => "trace".to_string(),
+    }
+}
+
+/// Normal token ping cadence
+const TOKEN_PING_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Tighter cadence while the token is being rejected
+const TOKEN_RECHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+async fn ping_token_sync(config: Arc<Configuration>, http_client: HttpClient, status: SyncStatus) -> Result<()> {
+    let Some(ref plex_config) = config.plex else {
+        return Ok(());
+    };
+
+    let plex_client = PlexClient::new(http_client.clone(), plex_config.clone());
+
+    loop {
+        let next_ping = ping_token(&plex_client, &status).await;
+        sleep(next_ping).await;
+    }
+}
+
+/// Ping the Plex token once, recording whether it was accepted, and return
+/// how long to wait before the next ping
+async fn ping_token(plex_client: &PlexClient, status: &SyncStatus) -> Duration {
+    info!("Running token ping sync");
+
+    match plex_client.get_watchlist().await {
+        Ok(_) => {
+            if status.plex_token_valid() == Some(false) {
+                info!("Plex token accepted again, restoring 24h ping cadence");
             }
+            status.set_plex_token_valid(true);
+            debug!("Token ping successful");
+            TOKEN_PING_INTERVAL
+        }
+        Err(e) if HttpStatusError::is_status(&e, StatusCode::UNAUTHORIZED) => {
+            error!("Plex token was rejected, re-checking every {}s until it works: {}", TOKEN_RECHECK_INTERVAL.as_secs(), e);
+            status.set_plex_token_valid(false);
+            TOKEN_RECHECK_INTERVAL
+        }
+        Err(e) => {
+            warn!("Token ping failed: {}", e);
+            TOKEN_PING_INTERVAL
         }
     }
 }
 
-async fn plex_rss_sync(config: Arc<Configuration>, http_client: HttpClient) -> Result<()> {
-    let refresh_interval = config.refresh_interval();
+/// Consecutive skipped ticks before suggesting a longer interval
+const SKIPPED_TICK_WARNING_THRESHOLD: u32 = 3;
+
+async fn plex_rss_sync(config: Arc<Configuration>, http_client: HttpClient, status: SyncStatus) -> Result<()> {
+    let Some(refresh_interval) = config.refresh_interval() else {
+        info!("RSS sync disabled (interval is 0), only manual syncs will run");
+        return Ok(());
+    };
     let mut interval = interval(refresh_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
     
     loop {
         interval.tick().await;
         
-        if let Err(e) = run_sync(&config, &http_client, false).await {
-            error!("RSS sync failed: {}", e);
-        }
+        run_scheduled_sync(&config, &http_client, &status, false, refresh_interval).await;
     }
 }
 
-async fn plex_full_sync(config: Arc<Configuration>, http_client: HttpClient) -> Result<()> {
-    let mut interval = interval(Duration::from_secs(19 * 60)); // 19 minutes
+/// `initial_sync_done` delays the first tick by a period, as a full sync
+/// has just run at startup
+async fn plex_full_sync(config: Arc<Configuration>, http_client: HttpClient, status: SyncStatus, initial_sync_done: bool) -> Result<()> {
+    let Some(full_sync_interval) = config.full_sync_interval() else {
+        info!("Full sync disabled (interval is 0), only manual syncs will run");
+        return Ok(());
+    };
+    let mut interval = if initial_sync_done {
+        interval_at(tokio::time::Instant::now() + full_sync_interval, full_sync_interval)
+    } else {
+        interval(full_sync_interval)
+    };
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
     
     loop {
         interval.tick().await;
         
-        if let Err(e) = run_sync(&config, &http_client, true).await {
-            error!("Full sync failed: {}", e);
+        run_scheduled_sync(&config, &http_client, &status, true, full_sync_interval).await;
+    }
+}
+
+/// Run one scheduled sync unless another is still in progress, tracking ticks
+/// lost to long-running syncs so an interval that is too short gets flagged
+async fn run_scheduled_sync(config: &Configuration, http_client: &HttpClient, status: &SyncStatus, full_sync: bool, period: Duration) {
+    let sync_kind = if full_sync { "Full" } else { "RSS" };
+
+    if let Some(remaining) = status.backoff_remaining() {
+        debug!("Backing off after repeated failures, skipping this {} sync tick ({}s left)", sync_kind, remaining.as_secs());
+        // Holding off on purpose still counts as the loop being alive
+        status.record_progress();
+        return;
+    }
+
+    let Some(_sync_guard) = status.try_begin_sync() else {
+        debug!("Previous sync still running, skipping this {} sync tick", sync_kind);
+        warn_on_skipped_ticks(status.record_skipped_ticks(1), period);
+        return;
+    };
+
+    let started = Instant::now();
+    let result = run_sync(config, http_client, status, full_sync, None).await;
+
+    // Progress whatever the outcome: failing over a Plex outage is the loop
+    // working, and restarting would only make systemd loop on it
+    status.record_progress();
+
+    match result {
+        Ok(_) => status.record_sync_success(),
+        Err(e) => {
+            error!("{} sync failed: {}", sync_kind, e);
+            let failures = status.record_sync_failure();
+            if let Some(cap) = config.failure_backoff_max() {
+                let delay = failure_backoff(period, failures, cap);
+                if delay > period {
+                    warn!("{} consecutive sync failures, backing off scheduled syncs for {}s", failures, delay.as_secs());
+                }
+                // The next tick is already one period away
+                status.back_off(delay - period);
+            }
         }
     }
+
+    // Ticks that elapsed while this sync ran are dropped by MissedTickBehavior::Skip
+    let missed_ticks = (started.elapsed().as_secs_f64() / period.as_secs_f64()) as u32;
+    if missed_ticks > 0 {
+        warn_on_skipped_ticks(status.record_skipped_ticks(missed_ticks), period);
+    } else {
+        status.reset_skipped_ticks();
+    }
+}
+
+/// Delay before the next scheduled sync after `failures` consecutive
+/// failures: one period after the first, doubling each time, up to `cap`
+fn failure_backoff(period: Duration, failures: u32, cap: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    period.saturating_mul(factor).min(cap).max(period)
+}
+
+fn warn_on_skipped_ticks(consecutive: u32, period: Duration) {
+    if consecutive >= SKIPPED_TICK_WARNING_THRESHOLD {
+        warn!("{} consecutive sync ticks skipped because the previous sync was still running; \
+               consider an interval longer than {}s", consecutive, period.as_secs());
+    }
 }
 
-async fn plex_delete_sync(config: Arc<Configuration>, http_client: HttpClient) -> Result<()> {
+async fn plex_delete_sync(config: Arc<Configuration>, http_client: HttpClient, status: SyncStatus) -> Result<()> {
     let delete_interval = config.delete_interval();
     let mut interval = interval(delete_interval);
     
@@ -123,7 +510,7 @@ async fn plex_delete_sync(config: Arc<Configuration>, http_client: HttpClient) -
                 || delete_config.continuing_show.unwrap_or(false) 
             {
                 info!("Running delete sync");
-                if let Err(e) = run_delete_sync(&config, &http_client).await {
+                if let Err(e) = run_delete_sync(&config, &http_client, &status).await {
                     error!("Delete sync failed: {}", e);
                 }
             }
@@ -131,58 +518,1468 @@ async fn plex_delete_sync(config: Arc<Configuration>, http_client: HttpClient) -
     }
 }
 
-async fn run_sync(config: &Configuration, http_client: &HttpClient, full_sync: bool) -> Result<()> {
+/// Errors are reserved for failures that stop the whole sync (e.g. Plex being
+/// unreachable); per-item problems are counted in the report instead.
+/// `only_type` restricts the sync to one item type, leaving the other
+/// type's backend alone entirely.
+async fn run_sync(
+    config: &Configuration,
+    http_client: &HttpClient,
+    status: &SyncStatus,
+    full_sync: bool,
+    only_type: Option<ItemType>,
+) -> Result<SyncReport> {
     let Some(ref plex_config) = config.plex else {
         warn!("No Plex configuration found, skipping sync");
-        return Ok(());
+        return Ok(SyncReport::default());
     };
 
-    info!("Running {} sync", if full_sync { "full" } else { "RSS" });
+    // Warned about once at startup, no need to repeat it every tick
+    if !config.has_sync_targets() {
+        debug!("No sync targets configured, skipping sync");
+        return Ok(SyncReport::default());
+    }
+
+    let sync_kind = if full_sync { "Full" } else { "RSS" };
+    let started = Instant::now();
+    info!("Running {} sync", sync_kind);
     
-    let plex_client = PlexClient::new(http_client.clone(), plex_config.clone());
+    let plex_client = PlexClient::new(http_client.clone(), plex_config.clone()).with_friends_cache(status.clone());
     
+    // Only ask Plex for the types a configured backend can actually take
+    let type_filter = only_type.clone().or(match (config.radarr.is_some(), config.sonarr.is_some(), config.overseerr.is_some()) {
+        (true, false, false) => Some(ItemType::Movie),
+        (false, true, false) => Some(ItemType::Show),
+        _ => None,
+    });
+
     // Get watchlist items
-    let mut watchlist_items = plex_client.get_watchlist().await?;
+    let mut watchlist_items = plex_client.get_all_accounts_watchlist(type_filter).await?.items;
     
+    // skipfriendsync turns friends off entirely; ownerOnly only keeps them
+    // out of the work list for syncs
     if !plex_config.skip_friend_sync.unwrap_or(false) && full_sync {
-        let friends_items = plex_client.get_friends_watchlists().await?;
-        watchlist_items.extend(friends_items);
+        if config.owner_only() {
+            debug!("Owner-only sync, not adding items from friends' watchlists");
+        } else {
+            let friends_items = plex_client.get_friends_watchlists().await?;
+            watchlist_items.extend(friends_items.items);
+        }
+    }
+
+    // The same title can appear on several accounts' and friends' watchlists
+    let mut watchlist_items = plex::merge_duplicate_items(watchlist_items);
+    if let Some(ref only_type) = only_type {
+        // Friends' watchlists aren't fetched by type
+        watchlist_items.retain(|i| i.item.item_type == *only_type);
+    }
+    plex_client.resolve_metadata(&mut watchlist_items).await;
+
+    if let Some(server_url) = plex_config.server_url.as_ref().filter(|_| plex_config.skip_owned.unwrap_or(false)) {
+        match plex_client.get_library_guids(server_url).await {
+            Ok(library_guids) => {
+                let before = watchlist_items.len();
+                watchlist_items.retain(|i| {
+                    let owned = plex::is_owned(&i.item, &library_guids);
+                    if owned {
+                        debug!("'{}' is already in the Plex library, skipping", i.item.title);
+                    }
+                    !owned
+                });
+                info!("Skipped {} watchlist items already in the Plex library", before - watchlist_items.len());
+            }
+            Err(e) => warn!("Could not read the Plex libraries, not skipping owned items this sync: {}", e),
+        }
     }
 
+    apply_add_order(&mut watchlist_items, config.add_order());
+
     info!("Found {} items in watchlist", watchlist_items.len());
 
+    // Pre-flight each backend once so a dead instance is skipped for the
+    // whole sync instead of failing item by item
+    let radarr_client = match config.radarr {
+        Some(ref radarr_config) if only_type != Some(ItemType::Show) => {
+            let client = RadarrClient::new(http_client.clone(), radarr_config.clone());
+            match client.get_system_status().await {
+                Ok(system_status) => {
+                    debug!("Radarr is reachable (version {})", system_status.version);
+                    status.record_service("radarr", Some(system_status.version));
+                    Some(client)
+                }
+                Err(e) => {
+                    status.record_service("radarr", None);
+                    warn!("Radarr is unreachable, skipping all movies this sync: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let sonarr_client = match config.sonarr {
+        Some(ref sonarr_config) if only_type != Some(ItemType::Movie) => {
+            let client = SonarrClient::new(http_client.clone(), sonarr_config.clone());
+            match client.get_system_status().await {
+                Ok(system_status) => {
+                    debug!("Sonarr is reachable (version {})", system_status.version);
+                    status.record_service("sonarr", Some(system_status.version));
+                    Some(client)
+                }
+                Err(e) => {
+                    status.record_service("sonarr", None);
+                    warn!("Sonarr is unreachable, skipping all shows this sync: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let overseerr_client = match config.overseerr {
+        Some(ref overseerr_config) => {
+            let client = OverseerrClient::new(http_client.clone(), overseerr_config.clone())?;
+            match client.get_status().await {
+                Ok(system_status) => {
+                    debug!("Overseerr is reachable (version {})", system_status.version);
+                    status.record_service("overseerr", Some(system_status.version));
+                    Some(client)
+                }
+                Err(e) => {
+                    status.record_service("overseerr", None);
+                    warn!("Overseerr is unreachable, skipping requests this sync: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Requests replace direct adds unless alsoDirect is set. An unreachable
+    // Overseerr does not fall back to direct adds, that would bypass approval.
+    let direct_adds = config.overseerr.is_none()
+        || overseerr_client.as_ref().is_some_and(|c| c.also_direct());
+
+    let item_timeout = config.item_timeout();
+    let mut skipped = Vec::new();
+    let mut added = 0;
+    let mut failed = 0;
+    let mut failed_by_service: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut added_movies = Vec::new();
+    let mut added_series = Vec::new();
+
+    let not_found_cooldown = config.not_found_cooldown();
+
+    let total = watchlist_items.len();
+    let cycle_time_budget = config.cycle_time_budget();
+    let mut deferred = 0;
+    let mut progress = config.progress_logging().map(|(every_items, every)| ProgressLog::new(every_items, every));
+
     // Process items
-    for watchlist_item in watchlist_items {
+    for (processed, watchlist_item) in watchlist_items.into_iter().enumerate() {
         let item = &watchlist_item.item;
-        
-        match item.item_type {
-            ItemType::Movie => {
-                if let Some(ref radarr_config) = config.radarr {
-                    let radarr_client = RadarrClient::new(http_client.clone(), radarr_config.clone());
-                    if let Err(e) = radarr_client.add_movie(item).await {
-                        error!("Failed to add movie to Radarr: {}", e);
+
+        // Items are in addOrder, so the ones left over are the least urgent
+        if cycle_time_budget.is_some_and(|budget| started.elapsed() >= budget) {
+            deferred = total - processed;
+            warn!("Sync ran past cycleTimeBudgetSeconds, deferring {} remaining items to the next sync", deferred);
+            break;
+        }
+
+        if progress.as_mut().is_some_and(|p| p.due(processed)) {
+            info!("Processed {}/{}, added {}, skipped {}, failed {}", processed, total, added, skipped.len(), failed);
+        }
+
+        if let Some(age) = not_found_cooldown.and_then(|cooldown| status.not_found_within(&item.id, cooldown)) {
+            debug!("Skipping '{}', it was not found {}m ago", item.title, age.as_secs() / 60);
+            skipped.push(SkippedItem::new(&watchlist_item, SkipReason::NotFound));
+            continue;
+        }
+
+        // One id per item, sent as X-Request-Id on every request made for it
+        // so this run can be matched up with the Sonarr/Radarr/Plex logs
+        let request_id = http::new_request_id();
+        let span = info_span!("item", request_id = %request_id, title = %item.title);
+
+        let targets = select_targets(item, config.routes(), config.overseerr.is_some(), direct_adds);
+        for target in &targets {
+            let available = match target {
+                Target::Overseerr => overseerr_client.is_some(),
+                Target::Radarr => radarr_client.is_some(),
+                Target::Sonarr => sonarr_client.is_some(),
+            };
+            if !available {
+                skipped.push(SkippedItem::new(&watchlist_item, SkipReason::BackendUnavailable));
+            }
+        }
+
+        // An item can go to several backends; add to all of them at once
+        let overseerr_add = async {
+            let client = overseerr_client.as_ref().filter(|_| targets.contains(&Target::Overseerr))?;
+            Some(("Overseerr", timeout(item_timeout, client.request(item)).await))
+        };
+        let direct_add = async {
+            if let Some(radarr_client) = radarr_client.as_ref().filter(|_| targets.contains(&Target::Radarr)) {
+                return Some(("Radarr", timeout(item_timeout, radarr_client.add_movie(item)).await));
+            }
+            let sonarr_client = sonarr_client.as_ref().filter(|_| targets.contains(&Target::Sonarr))?;
+            Some(("Sonarr", timeout(item_timeout, sonarr_client.add_series(item)).await))
+        };
+
+        let (overseerr_result, direct_result) = http::with_request_id(request_id, async {
+            tokio::join!(overseerr_add, direct_add)
+        })
+        .instrument(span)
+        .await;
+
+        let results: Vec<_> = overseerr_result.into_iter().chain(direct_result).collect();
+        let mut summary = Vec::with_capacity(results.len());
+
+        for (service, result) in results {
+            match result {
+                Ok(Ok(AddOutcome::Added(id))) => {
+                    added += 1;
+                    summary.push(format!("added to {}", service));
+                    match (id, &item.item_type) {
+                        (Some(id), ItemType::Movie) => added_movies.push((id, item.title.clone())),
+                        (Some(id), ItemType::Show) => added_series.push((id, item.title.clone())),
+                        (None, _) => debug!("{} returned no id for '{}', it cannot be verified", service, item.title),
                     }
                 }
-            }
-            ItemType::Show => {
-                if let Some(ref sonarr_config) = config.sonarr {
-                    let sonarr_client = SonarrClient::new(http_client.clone(), sonarr_config.clone());
-                    if let Err(e) = sonarr_client.add_series(item).await {
-                        error!("Failed to add series to Sonarr: {}", e);
+                Ok(Ok(AddOutcome::Skipped(reason))) => {
+                    debug!("Skipped '{}' for {}: {}", item.title, service, reason);
+                    summary.push(format!("{} in {}", reason, service));
+                    if reason == SkipReason::NotFound && not_found_cooldown.is_some() {
+                        status.record_not_found(&item.id);
                     }
+                    skipped.push(SkippedItem::new(&watchlist_item, reason));
+                }
+                Ok(Err(e)) => {
+                    error!("Failed to add '{}' to {}: {}", item.title, service, e);
+                    summary.push(format!("failed in {}", service));
+                    failed += 1;
+                    *failed_by_service.entry(service).or_default() += 1;
+                }
+                Err(_) => {
+                    error!("Adding '{}' to {} timed out after {}s, abandoning", item.title, service, item_timeout.as_secs());
+                    summary.push(format!("timed out in {}", service));
+                    failed += 1;
+                    *failed_by_service.entry(service).or_default() += 1;
                 }
             }
         }
-        
+
+        if summary.len() > 1 {
+            info!("'{}': {}", item.title, summary.join(", "));
+        }
+
+        // A long sync keeps the watchdog fed as long as items keep finishing
+        status.record_progress();
+
         // Small delay between requests to be respectful
         sleep(Duration::from_millis(100)).await;
     }
 
-    info!("Sync completed");
+    // Searches queued by nonBlockingAdd outlive their add; finish them
+    // before returning so a one-shot sync doesn't exit and drop them
+    if let Some(ref radarr_client) = radarr_client {
+        radarr_client.wait_for_searches().await;
+    }
+    if let Some(ref sonarr_client) = sonarr_client {
+        sonarr_client.wait_for_searches().await;
+    }
+
+    if config.verify_adds() {
+        if let (Some(radarr_client), false) = (&radarr_client, added_movies.is_empty()) {
+            report_missing_adds("Radarr", &added_movies, radarr_client.missing_movie_ids(&ids_of(&added_movies)).await);
+        }
+        if let (Some(sonarr_client), false) = (&sonarr_client, added_series.is_empty()) {
+            report_missing_adds("Sonarr", &added_series, sonarr_client.missing_series_ids(&ids_of(&added_series)).await);
+        }
+    }
+
+    let not_found = skipped.iter().filter(|s| s.reason == SkipReason::NotFound).count();
+    let report = SyncReport {
+        added,
+        skipped: skipped.len() - not_found,
+        not_found,
+        failed,
+        deleted: 0,
+        deferred,
+        duration: started.elapsed(),
+    };
+
+    let elapsed = report.duration;
+    info!("{} sync completed in {:.1}s ({} added, {} skipped, {} not found, {} failed)",
+          sync_kind, elapsed.as_secs_f64(), report.added, report.skipped, report.not_found, report.failed);
+
+    if let Some(threshold) = config.slow_sync_threshold() {
+        if elapsed > threshold {
+            warn!("{} sync took {:.1}s, over the {}s slow sync threshold; consider a longer interval",
+                  sync_kind, elapsed.as_secs_f64(), threshold.as_secs());
+        }
+    }
+
+    if let Some(threshold) = config.failure_alert_threshold() {
+        alert_on_failures(sync_kind, &report, &failed_by_service, threshold);
+    }
+
+    status.record_skipped(skipped);
+    Ok(report)
+}
+
+/// Backends `item` is sent to. The first of `routes` naming one of its
+/// genres decides, leaving out Radarr for shows and Sonarr for movies.
+/// Otherwise Overseerr when configured, plus Radarr for movies or Sonarr
+/// for shows when direct adds are on. The one place to route items
+/// differently.
+fn select_targets(item: &Item, routes: &[RouteConfig], overseerr: bool, direct_adds: bool) -> Vec<Target> {
+    let route = routes
+        .iter()
+        .find(|route| item.genres.iter().any(|genre| genre.eq_ignore_ascii_case(&route.genre)));
+    if let Some(route) = route {
+        debug!("Routing '{}' by genre '{}' to {:?}", item.title, route.genre, route.targets);
+        return route
+            .targets
+            .iter()
+            .copied()
+            .filter(|target| match target {
+                Target::Overseerr => true,
+                Target::Radarr => item.item_type == ItemType::Movie,
+                Target::Sonarr => item.item_type == ItemType::Show,
+            })
+            .collect();
+    }
+
+    let mut targets = Vec::new();
+    if overseerr {
+        targets.push(Target::Overseerr);
+    }
+    if direct_adds {
+        targets.push(match item.item_type {
+            ItemType::Movie => Target::Radarr,
+            ItemType::Show => Target::Sonarr,
+        });
+    }
+    targets
+}
+
+/// Decides when the sync loop logs a progress line
+struct ProgressLog {
+    every_items: usize,
+    every: Duration,
+    last_logged_at: Instant,
+    last_logged_count: usize,
+}
+
+impl ProgressLog {
+    fn new(every_items: usize, every: Duration) -> Self {
+        Self {
+            every_items,
+            every,
+            last_logged_at: Instant::now(),
+            last_logged_count: 0,
+        }
+    }
+
+    /// Whether a line is due with `processed` items done, recording it if so
+    fn due(&mut self, processed: usize) -> bool {
+        let items_since = processed - self.last_logged_count;
+        if items_since == 0 || (items_since < self.every_items && self.last_logged_at.elapsed() < self.every) {
+            return false;
+        }
+        self.last_logged_at = Instant::now();
+        self.last_logged_count = processed;
+        true
+    }
+}
+
+/// Fewest attempted adds in a sync before a failure rate is worth alerting on
+const FAILURE_ALERT_MIN_ATTEMPTS: usize = 5;
+
+/// Log an error when the share of failed adds is over `threshold`, which
+/// usually means a backend is down or misconfigured rather than bad items
+fn alert_on_failures(sync_kind: &str, report: &SyncReport, failed_by_service: &BTreeMap<&str, usize>, threshold: f64) {
+    let attempted = report.added + report.failed;
+    if attempted < FAILURE_ALERT_MIN_ATTEMPTS {
+        return;
+    }
+
+    let rate = report.failed as f64 / attempted as f64;
+    if rate <= threshold {
+        return;
+    }
+
+    let breakdown: Vec<String> = failed_by_service
+        .iter()
+        .map(|(service, count)| format!("{} {}", service, count))
+        .collect();
+    error!("{} sync failed {} of {} adds ({:.0}%, over the {:.0}% alert threshold): {}",
+           sync_kind, report.failed, attempted, rate * 100.0, threshold * 100.0, breakdown.join(", "));
+}
+
+fn ids_of(added: &[(i64, String)]) -> Vec<i64> {
+    added.iter().map(|(id, _)| *id).collect()
+}
+
+/// Log every item that was reported as added but is absent on re-query
+fn report_missing_adds(service: &str, added: &[(i64, String)], missing: Result<Vec<i64>>) {
+    match missing {
+        Ok(missing) if missing.is_empty() => {
+            debug!("Verified all {} items added to {}", added.len(), service);
+        }
+        Ok(missing) => {
+            for (id, title) in added.iter().filter(|(id, _)| missing.contains(id)) {
+                warn!("'{}' was added to {} (id {}) but is missing on re-query", title, service, id);
+            }
+        }
+        Err(e) => warn!("Could not verify items added to {}: {}", service, e),
+    }
+}
+
+/// Reorder the work list so adds (and the searches they trigger) happen in
+/// the configured order. Ties are broken by title and then rating key, so
+/// every order except `WatchlistOrder` is the same whatever order Plex
+/// returned the items in.
+fn apply_add_order(items: &mut [WatchlistItem], order: AddOrder) {
+    let tie_break = |i: &WatchlistItem| (i.item.title.to_lowercase(), i.item.id.clone());
+    match order {
+        AddOrder::WatchlistOrder => {}
+        AddOrder::NewestFirst => items.sort_by_cached_key(|i| (std::cmp::Reverse(i.added_at), tie_break(i))),
+        AddOrder::OldestFirst => items.sort_by_cached_key(|i| (i.added_at, tie_break(i))),
+        AddOrder::Alphabetical => items.sort_by_cached_key(tie_break),
+    }
+}
+
+async fn run_once(config: &Configuration, http_client: &HttpClient, mode: SyncMode, only_type: Option<ItemType>) -> Result<()> {
+    match only_type {
+        Some(ref item_type) => info!("Running one-off {:?} sync ({:?} items only)", mode, item_type),
+        None => info!("Running one-off {:?} sync", mode),
+    }
+    let status = SyncStatus::new();
+
+    if matches!(mode, SyncMode::Full | SyncMode::All) {
+        run_sync(config, http_client, &status, true, only_type.clone()).await?;
+    }
+
+    if mode == SyncMode::Rss {
+        run_sync(config, http_client, &status, false, only_type).await?;
+    }
+
+    if matches!(mode, SyncMode::Delete | SyncMode::All) {
+        run_delete_sync(config, http_client, &status).await?;
+    }
+
     Ok(())
 }
 
-async fn run_delete_sync(_config: &Configuration, _http_client: &HttpClient) -> Result<()> {
+async fn run_delete_sync(config: &Configuration, http_client: &HttpClient, status: &SyncStatus) -> Result<()> {
+    let Some(ref plex_config) = config.plex else {
+        warn!("No Plex configuration found, skipping delete sync");
+        return Ok(());
+    };
+
+    if !config.has_sync_targets() {
+        debug!("No sync targets configured, skipping delete sync");
+        return Ok(());
+    }
+
+    // An empty watchlist from a rejected token looks exactly like a user who
+    // cleared their watchlist; never delete while the token is known bad
+    if status.plex_token_valid() == Some(false) {
+        warn!("Plex token was rejected, refusing to run delete sync");
+        return Ok(());
+    }
+
+    let plex_client = PlexClient::new(http_client.clone(), plex_config.clone()).with_friends_cache(status.clone());
+
+    // Every source has to come back cleanly. A failure anywhere means the
+    // view is partial, and deleting against a partial view removes items
+    // that are still watchlisted.
+    let mut watchlist_items = plex_client
+        .get_all_accounts_watchlist(None)
+        .await
+        .and_then(plex::Watchlists::complete)
+        .context("Refusing to delete anything, the watchlist fetch failed")?;
+    watchlist_items.extend(
+        plex_client
+            .get_friends_watchlists()
+            .await
+            .and_then(plex::Watchlists::complete)
+            .context("Refusing to delete anything, the friends' watchlist fetch failed")?,
+    );
+    let watchlist_items = plex::merge_duplicate_items(watchlist_items);
+
+    info!("Delete sync has a complete watchlist of {} items", watchlist_items.len());
     info!("Delete sync functionality not yet implemented");
     Ok(())
 }
+
+async fn run_export(config: &Configuration, http_client: &HttpClient, format: ExportFormat, out: &Path) -> Result<()> {
+    let Some(ref plex_config) = config.plex else {
+        return Err(anyhow::anyhow!("Export requires a Plex configuration"));
+    };
+
+    let plex_client = PlexClient::new(http_client.clone(), plex_config.clone());
+
+    let mut watchlist_items = plex_client.get_all_accounts_watchlist(None).await?.items;
+    watchlist_items.extend(plex_client.get_friends_watchlists().await?.items);
+    let watchlist_items = plex::merge_duplicate_items(watchlist_items);
+
+    export::write_watchlist(&watchlist_items, format, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Plex serving `movies` as the owner's watchlist, Radarr at `radarr_url`
+    /// and `sync` as the sync section
+    async fn sync_config(movies: &[(&str, &str, i32)], radarr_url: &str, sync: &str) -> Configuration {
+        let metadata_url = test_support::serve(test_support::plex_metadata(test_support::watchlist_xml(movies))).await;
+        let mut config: Configuration = serde_yaml::from_str(&format!(
+            "radarr:\n  baseUrl: {}\n  apikey: test-key\nsync: {}\n",
+            radarr_url, sync
+        ))
+        .unwrap();
+        config.plex = Some(test_support::plex_config(&metadata_url, &test_support::unreachable_url().await));
+        config
+    }
+
+    #[tokio::test]
+    async fn unreachable_radarr_skips_movies_with_a_single_warning() {
+        let movies = [("1", "Heat", 1995), ("2", "Ronin", 1998), ("3", "Collateral", 2004)];
+        let config = sync_config(&movies, &test_support::unreachable_url().await, "{}").await;
+        let status = SyncStatus::new();
+        let logs = test_support::Logs::default();
+
+        let result = {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &status, false, None).await
+        };
+
+        assert!(result.is_ok());
+        let skipped = status.skipped();
+        assert_eq!(skipped.len(), 3);
+        assert!(skipped.iter().all(|s| s.reason == SkipReason::BackendUnavailable));
+        let warnings = logs.lines();
+        assert_eq!(warnings.len(), 1, "expected one warning for the whole sync, got {:?}", warnings);
+        assert!(warnings[0].contains("Radarr is unreachable, skipping all movies this sync"));
+    }
+
+    #[tokio::test]
+    async fn service_health_follows_radarr_going_down() {
+        let (router, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(router).await;
+        let status = SyncStatus::new();
+
+        let config = sync_config(&[], &radarr_url, "{}").await;
+        run_sync(&config, &test_support::http_client(), &status, false, None).await.unwrap();
+        let radarr = &status.services()["radarr"];
+        assert!(radarr.up);
+        assert_eq!(radarr.version.as_deref(), Some("4.0.0"));
+
+        let config = sync_config(&[], &test_support::unreachable_url().await, "{}").await;
+        run_sync(&config, &test_support::http_client(), &status, false, None).await.unwrap();
+        let radarr = &status.services()["radarr"];
+        assert!(!radarr.up);
+        assert_eq!(radarr.version, None);
+    }
+
+    #[tokio::test]
+    async fn profile_reports_requests_per_sync_phase() {
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{}").await;
+        let profile = Arc::new(RequestProfile::default());
+        let http_client = test_support::http_client().with_profile(Arc::clone(&profile));
+
+        run_sync(&config, &http_client, &SyncStatus::new(), false, None).await.unwrap();
+
+        let report = profile.report();
+        let requests = |phase: &str| {
+            let line = report.lines().find(|l| l.starts_with(phase)).unwrap_or_else(|| panic!("no {} in\n{}", phase, report));
+            line.split_whitespace().rev().nth(1).unwrap().to_string()
+        };
+        assert!(report.starts_with("Phase"), "{}", report);
+        assert_eq!(requests("Plex watchlist"), "1");
+        assert_eq!(requests("Lookups"), "1");
+        assert_eq!(requests("Library fetches"), "1");
+        assert_eq!(requests("Adds"), "1");
+    }
+
+    #[tokio::test]
+    async fn skip_owned_leaves_items_in_the_plex_library_out_of_the_adds() {
+        let (router, writes) = test_support::arr("movie", test_support::Arr {
+            term_lookups: std::collections::HashMap::from([
+                ("Heat 1995".to_string(), serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }])),
+                ("Ronin 1998".to_string(), serde_json::json!([{ "title": "Ronin", "originalTitle": "Ronin", "sortTitle": "ronin", "year": 1998, "tmdbId": 8195 }])),
+            ]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let server_url = test_support::serve(test_support::plex_server(vec!["plex://movie/1".to_string()])).await;
+        let mut config = sync_config(&[("1", "Heat", 1995), ("2", "Ronin", 1998)], &radarr_url, "{}").await;
+        let plex = config.plex.as_mut().unwrap();
+        plex.server_url = Some(server_url);
+        plex.skip_owned = Some(true);
+
+        run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1, "{:?}", *writes);
+        assert_eq!(writes[0].body["title"], "Ronin");
+    }
+
+    #[tokio::test]
+    async fn item_timeout_abandons_a_slow_add() {
+        let (router, writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            lookup_delay: Duration::from_secs(30),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ itemTimeoutSeconds: 1 }").await;
+        let logs = test_support::Logs::default();
+        let started = Instant::now();
+
+        let result = {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await
+        };
+
+        assert!(result.is_ok());
+        assert!(started.elapsed() < Duration::from_secs(10), "the sync waited on the lookup");
+        let errors = logs.lines();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("Adding 'Heat' to Radarr timed out after 1s"));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn item_timeout_leaves_adds_that_finish_in_time_alone() {
+        let (router, writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ itemTimeoutSeconds: 1 }").await;
+
+        run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!((writes[0].path.as_str(), &writes[0].body["tmdbId"]), ("/movie", &serde_json::json!(949)));
+    }
+
+    #[tokio::test]
+    async fn each_item_sends_the_request_id_recorded_on_its_span() {
+        let (router, writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{}").await;
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture_at(tracing::Level::INFO);
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+        }
+
+        let request_id = writes.lock().unwrap()[0].request_id.clone().expect("the add had no X-Request-Id");
+        let lines = logs.lines();
+        let added = lines.iter().find(|l| l.contains("Successfully added movie: Heat")).expect("the add was not logged");
+        assert!(added.contains(&format!("item{{request_id={} title=Heat}}", request_id)), "{}", added);
+    }
+
+    /// Config whose Plex stand-ins count owner watchlist fetches and record
+    /// friends queries, with an empty Radarr behind it
+    async fn counting_config() -> (Configuration, Arc<std::sync::atomic::AtomicUsize>, test_support::Queries) {
+        let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&fetches);
+        let xml = test_support::watchlist_xml(&[]);
+        let metadata = axum::Router::new().route(
+            "/library/sections/watchlist/all",
+            axum::routing::get(move || async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                xml
+            }),
+        );
+        let (community, queries) = test_support::plex_community(&[], &[]);
+        let (radarr, _) = test_support::arr("movie", test_support::Arr::default());
+
+        let radarr_url = test_support::serve(radarr).await;
+        let mut config: Configuration =
+            serde_yaml::from_str(&format!("radarr:\n  baseUrl: {}\n  apikey: test-key\n", radarr_url)).unwrap();
+        config.plex = Some(test_support::plex_config(
+            &test_support::serve(metadata).await,
+            &test_support::serve(community).await,
+        ));
+        (config, fetches, queries)
+    }
+
+    #[tokio::test]
+    async fn each_sync_mode_runs_its_own_path() {
+        // The second sync of `All` reuses the friends list the first one cached
+        for (mode, watchlist_fetches, friends_queries) in
+            [(SyncMode::Full, 1, 1), (SyncMode::Rss, 1, 0), (SyncMode::Delete, 1, 1), (SyncMode::All, 2, 1)]
+        {
+            let (config, fetches, queries) = counting_config().await;
+
+            run_once(&config, &test_support::http_client(), mode, None).await.unwrap();
+
+            assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), watchlist_fetches, "{:?}", mode);
+            assert_eq!(queries.lock().unwrap().len(), friends_queries, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn sync_subcommand_defaults_to_all() {
+        let cli = Cli::try_parse_from(["watchlistarr", "sync"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Sync { mode: SyncMode::All, service: None, profile: false })));
+
+        let cli = Cli::try_parse_from(["watchlistarr", "sync", "--mode", "rss", "--service", "radarr", "--profile"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Sync { mode: SyncMode::Rss, service: Some(Service::Radarr), profile: true })));
+    }
+
+    #[tokio::test]
+    async fn zero_interval_disables_the_rss_loop() {
+        let (mut config, fetches, _) = counting_config().await;
+        config.interval = Some(serde_yaml::from_str("seconds: 0").unwrap());
+
+        let finished = timeout(
+            Duration::from_secs(5),
+            plex_rss_sync(Arc::new(config), test_support::http_client(), SyncStatus::new()),
+        )
+        .await;
+
+        assert!(matches!(finished, Ok(Ok(()))), "the RSS loop kept running");
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_rechecked_every_5_minutes_until_it_works() {
+        // The first two pings are rejected, every later one is accepted
+        let pings = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&pings);
+        let xml = test_support::watchlist_xml(&[]);
+        let metadata = axum::Router::new().route(
+            "/library/sections/watchlist/all",
+            axum::routing::get(move || async move {
+                let ping = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                let status = if ping <= 2 { StatusCode::UNAUTHORIZED } else { StatusCode::OK };
+                (status, xml)
+            }),
+        );
+        let metadata_url = test_support::serve(metadata).await;
+        let plex_config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let plex_client = PlexClient::new(test_support::http_client(), plex_config);
+        let status = SyncStatus::new();
+
+        let mut cadence = Vec::new();
+        for _ in 0..4 {
+            cadence.push((ping_token(&plex_client, &status).await, status.plex_token_valid()));
+        }
+
+        assert_eq!(
+            cadence,
+            [
+                (TOKEN_RECHECK_INTERVAL, Some(false)),
+                (TOKEN_RECHECK_INTERVAL, Some(false)),
+                (TOKEN_PING_INTERVAL, Some(true)),
+                (TOKEN_PING_INTERVAL, Some(true)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn syncs_over_the_slow_sync_threshold_log_their_duration() {
+        let (router, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(router).await;
+
+        for (threshold, warnings) in [(0, 1), (3600, 0)] {
+            let sync = format!("{{ slowSyncThresholdSeconds: {} }}", threshold);
+            let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, &sync).await;
+            let logs = test_support::Logs::default();
+            let _guard = logs.capture();
+
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+
+            let slow: Vec<String> = logs.lines().into_iter().filter(|l| l.contains("slow sync threshold")).collect();
+            assert_eq!(slow.len(), warnings, "{:?}", slow);
+            if warnings == 1 {
+                assert!(slow[0].contains("RSS sync took") && slow[0].contains("s, over the 0s slow sync threshold"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_syncs_on_a_short_interval_warn_about_skipped_ticks() {
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookup_delay: Duration::from_millis(400),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{}").await;
+        let http = test_support::http_client();
+        let status = SyncStatus::new();
+        let period = Duration::from_millis(100);
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture();
+            run_scheduled_sync(&config, &http, &status, false, period).await;
+        }
+
+        let warnings: Vec<String> = logs.lines().into_iter().filter(|l| l.contains("sync ticks skipped")).collect();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("consider an interval longer than 0s"));
+    }
+
+    #[tokio::test]
+    async fn ticks_are_skipped_while_another_sync_holds_the_lock() {
+        let config = sync_config(&[("1", "Heat", 1995)], &test_support::unreachable_url().await, "{}").await;
+        let status = SyncStatus::new();
+        let logs = test_support::Logs::default();
+        let _sync = status.try_begin_sync().unwrap();
+
+        {
+            let _guard = logs.capture();
+            for _ in 0..SKIPPED_TICK_WARNING_THRESHOLD {
+                run_scheduled_sync(&config, &test_support::http_client(), &status, true, Duration::from_secs(60)).await;
+            }
+        }
+
+        assert!(status.skipped().is_empty(), "the blocked ticks must not run a sync");
+        let warnings = logs.lines();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("3 consecutive sync ticks skipped"));
+        assert!(warnings[0].contains("consider an interval longer than 60s"));
+    }
+
+    #[tokio::test]
+    async fn owner_only_full_syncs_leave_friends_items_out() {
+        for (owner_only, titles) in [(true, vec!["Heat"]), (false, vec!["Heat", "Movie of f1"])] {
+            let (community, queries) = test_support::plex_community(&[("f1", "friend")], &[]);
+            let (radarr, _) = test_support::arr("movie", test_support::Arr::default());
+            let radarr_url = test_support::serve(radarr).await;
+            let sync = format!("{{ ownerOnly: {} }}", owner_only);
+            let mut config = sync_config(&[("1", "Heat", 1995)], &radarr_url, &sync).await;
+            config.plex.as_mut().unwrap().community_url = Some(test_support::serve(community).await);
+            let status = SyncStatus::new();
+
+            run_sync(&config, &test_support::http_client(), &status, true, None).await.unwrap();
+
+            let skipped: Vec<String> = status.skipped().into_iter().map(|s| s.title).collect();
+            assert_eq!(skipped, titles, "ownerOnly: {}", owner_only);
+            assert_eq!(queries.lock().unwrap().is_empty(), owner_only, "friends are not fetched for owner-only syncs");
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_watchlist_aborts_the_sync_without_adding_anything() {
+        let (radarr, writes) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(radarr).await;
+        let mut config = sync_config(&[], &radarr_url, "{}").await;
+        let truncated = test_support::watchlist_xml(&[("1", "Heat", 1995)]);
+        let metadata_url = test_support::serve(test_support::plex_metadata(truncated[..truncated.len() / 2].to_string())).await;
+        config.plex.as_mut().unwrap().metadata_url = Some(metadata_url);
+        let status = SyncStatus::new();
+
+        let result = run_sync(&config, &test_support::http_client(), &status, false, None).await;
+
+        assert!(result.is_err());
+        assert!(status.skipped().is_empty());
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    /// Config with Plex pointed at the stand-ins and an unreachable Radarr,
+    /// which is enough for delete sync to have something to sync to
+    async fn delete_sync_config(metadata_url: &str, community_url: &str) -> Configuration {
+        let mut config = sync_config(&[], &test_support::unreachable_url().await, "{}").await;
+        config.plex = Some(test_support::plex_config(metadata_url, community_url));
+        config
+    }
+
+    async fn assert_delete_sync_refused(config: &Configuration, status: &SyncStatus, reason: &str) {
+        let error = format!("{:#}", run_delete_sync(config, &test_support::http_client(), status).await.unwrap_err());
+        assert!(error.contains("Refusing to delete anything"), "{}", error);
+        assert!(error.contains(reason), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn delete_sync_refuses_to_run_on_a_rejected_owner_token() {
+        let metadata_url = test_support::serve(test_support::plex_metadata_by_token(&[])).await;
+        let (community, _) = test_support::plex_community(&[], &[]);
+        let config = delete_sync_config(&metadata_url, &test_support::serve(community).await).await;
+
+        assert_delete_sync_refused(&config, &SyncStatus::new(), "the watchlist fetch failed").await;
+
+        // Once the token is known bad the fetch isn't even attempted
+        let status = SyncStatus::new();
+        status.set_plex_token_valid(false);
+        let logs = test_support::Logs::default();
+        {
+            let _guard = logs.capture();
+            run_delete_sync(&config, &test_support::http_client(), &status).await.unwrap();
+        }
+        assert!(logs.lines().iter().any(|l| l.contains("Plex token was rejected, refusing to run delete sync")));
+    }
+
+    #[tokio::test]
+    async fn delete_sync_refuses_to_run_when_an_account_watchlist_fails() {
+        let metadata = test_support::plex_metadata_by_token(&[("test-token", test_support::watchlist_xml(&[("1", "Heat", 1995)]))]);
+        let (community, _) = test_support::plex_community(&[], &[]);
+        let mut config = delete_sync_config(&test_support::serve(metadata).await, &test_support::serve(community).await).await;
+        config.plex.as_mut().unwrap().accounts =
+            Some(vec![config::PlexAccountConfig { label: "kid".to_string(), token: "expired-token".to_string() }]);
+
+        assert_delete_sync_refused(&config, &SyncStatus::new(), "kid").await;
+    }
+
+    #[tokio::test]
+    async fn delete_sync_refuses_to_run_when_a_friend_watchlist_fails() {
+        let metadata_url = test_support::serve(test_support::plex_metadata(test_support::watchlist_xml(&[("1", "Heat", 1995)]))).await;
+        let (community, _) = test_support::plex_community(&[("f1", "alice"), ("f2", "bob")], &["f2"]);
+        let config = delete_sync_config(&metadata_url, &test_support::serve(community).await).await;
+
+        assert_delete_sync_refused(&config, &SyncStatus::new(), "bob").await;
+    }
+
+    #[tokio::test]
+    async fn delete_sync_runs_when_every_watchlist_is_fetched() {
+        let metadata_url = test_support::serve(test_support::plex_metadata(test_support::watchlist_xml(&[]))).await;
+        let (community, _) = test_support::plex_community(&[("f1", "alice"), ("f2", "bob")], &[]);
+        let config = delete_sync_config(&metadata_url, &test_support::serve(community).await).await;
+
+        run_delete_sync(&config, &test_support::http_client(), &SyncStatus::new()).await.unwrap();
+    }
+
+    #[test]
+    fn add_order_sorts_the_work_list() {
+        let now = chrono::Utc::now();
+        let watchlist: Vec<WatchlistItem> = [("ronin", 2), ("Heat", 3), ("Collateral", 1)]
+            .into_iter()
+            .map(|(title, days_ago)| WatchlistItem {
+                item: test_support::item(ItemType::Movie, title, 2000),
+                added_at: now - chrono::Duration::days(days_ago),
+                user_id: "self".to_string(),
+            })
+            .collect();
+
+        for (order, expected) in [
+            (AddOrder::WatchlistOrder, ["ronin", "Heat", "Collateral"]),
+            (AddOrder::NewestFirst, ["Collateral", "ronin", "Heat"]),
+            (AddOrder::OldestFirst, ["Heat", "ronin", "Collateral"]),
+            (AddOrder::Alphabetical, ["Collateral", "Heat", "ronin"]),
+        ] {
+            let mut items = watchlist.clone();
+            apply_add_order(&mut items, order);
+            let titles: Vec<&str> = items.iter().map(|i| i.item.title.as_str()).collect();
+            assert_eq!(titles, expected, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn add_order_ties_sort_the_same_whatever_order_plex_returned() {
+        let added_at = chrono::Utc::now();
+        let watchlist: Vec<WatchlistItem> = [("2", "Heat"), ("1", "heat"), ("3", "Collateral"), ("4", "Ronin")]
+            .into_iter()
+            .map(|(id, title)| {
+                let mut item = test_support::item(ItemType::Movie, title, 2000);
+                item.id = id.to_string();
+                WatchlistItem { item, added_at, user_id: "self".to_string() }
+            })
+            .collect();
+
+        for order in [AddOrder::NewestFirst, AddOrder::OldestFirst, AddOrder::Alphabetical] {
+            for rotation in 0..watchlist.len() {
+                let mut items = watchlist.clone();
+                items.rotate_left(rotation);
+                items.swap(0, 1);
+                apply_add_order(&mut items, order);
+                let ids: Vec<&str> = items.iter().map(|i| i.item.id.as_str()).collect();
+                assert_eq!(ids, ["3", "1", "2", "4"], "{:?} from rotation {}", order, rotation);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_adds_reports_items_missing_on_re_query() {
+        // The stand-in accepts the add but its library never lists it
+        let (radarr, writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(radarr).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ verifyAdds: true }").await;
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+        }
+
+        assert_eq!(writes.lock().unwrap().len(), 1);
+        let warnings = logs.lines();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("'Heat' was added to Radarr (id 1) but is missing on re-query"));
+    }
+
+    #[tokio::test]
+    async fn verify_adds_is_quiet_when_the_added_item_is_listed() {
+        let (radarr, _) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            existing: serde_json::json!([{ "id": 1, "tmdbId": 603 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(radarr).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ verifyAdds: true }").await;
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+        }
+
+        assert!(logs.lines().is_empty(), "{:?}", logs.lines());
+    }
+
+    #[test]
+    fn failure_backoff_doubles_up_to_the_cap() {
+        let period = Duration::from_secs(60);
+        let cap = Duration::from_secs(600);
+
+        let delays: Vec<u64> = (1..=6).map(|failures| failure_backoff(period, failures, cap).as_secs()).collect();
+
+        assert_eq!(delays, [60, 120, 240, 480, 600, 600]);
+        assert_eq!(failure_backoff(period, u32::MAX, cap), cap);
+        assert_eq!(failure_backoff(period, 3, Duration::from_secs(1)), period, "never below one period");
+    }
+
+    #[tokio::test]
+    async fn repeated_sync_failures_lengthen_the_backoff_until_a_success() {
+        let (radarr, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(radarr).await;
+        let mut config = sync_config(&[], &radarr_url, "{ failureBackoffMaxSeconds: 3600 }").await;
+        let working_metadata_url = config.plex.as_ref().unwrap().metadata_url.clone();
+        config.plex.as_mut().unwrap().metadata_url = Some(test_support::unreachable_url().await);
+        let http = test_support::http_client();
+        let status = SyncStatus::new();
+        let period = Duration::from_secs(60);
+
+        let mut backoffs = Vec::new();
+        for _ in 0..3 {
+            run_scheduled_sync(&config, &http, &status, false, period).await;
+            backoffs.push(status.backoff_remaining().unwrap_or_default());
+            status.back_off(Duration::ZERO);
+        }
+
+        assert!(backoffs[0].is_zero(), "the first failure waits one ordinary period");
+        assert!(backoffs[1] > Duration::from_secs(55) && backoffs[1] <= period, "{:?}", backoffs);
+        assert!(backoffs[2] > Duration::from_secs(175) && backoffs[2] <= Duration::from_secs(180), "{:?}", backoffs);
+
+        // While backing off, ticks don't sync at all
+        status.back_off(period);
+        config.plex.as_mut().unwrap().metadata_url = working_metadata_url;
+        run_scheduled_sync(&config, &http, &status, false, period).await;
+        assert!(status.backoff_remaining().is_some());
+
+        status.back_off(Duration::ZERO);
+        run_scheduled_sync(&config, &http, &status, false, period).await;
+        assert_eq!(status.backoff_remaining(), None);
+        assert_eq!(status.record_sync_failure(), 1, "a success resets the failure count");
+    }
+
+    #[tokio::test]
+    async fn initial_sync_finishes_and_is_recorded_before_the_loops_start() {
+        let (router, writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let mut config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{}").await;
+        let (community, _) = test_support::plex_community(&[], &[]);
+        config.plex.as_mut().unwrap().community_url = Some(test_support::serve(community).await);
+        let config = Arc::new(config);
+        let status = SyncStatus::new();
+
+        // Nothing spawned has run yet on this single-threaded runtime, so
+        // anything already done was done by the initial sync
+        let tasks = start_syncs(&config, &test_support::http_client(), &status, true).await;
+        let added = writes.lock().unwrap().len();
+        let failures = status.record_sync_failure();
+        tasks.iter().for_each(JoinHandle::abort);
+
+        assert_eq!(added, 1);
+        assert_eq!(failures, 1, "the initial sync's success was not recorded");
+    }
+
+    #[tokio::test]
+    async fn a_failed_initial_sync_counts_toward_the_backoff() {
+        let mut config = sync_config(&[], &test_support::unreachable_url().await, "{}").await;
+        config.plex.as_mut().unwrap().metadata_url = Some(test_support::unreachable_url().await);
+        let status = SyncStatus::new();
+
+        let tasks = start_syncs(&Arc::new(config), &test_support::http_client(), &status, true).await;
+        let failures = status.record_sync_failure();
+        tasks.iter().for_each(JoinHandle::abort);
+
+        assert_eq!(failures, 2, "the initial sync's failure was not recorded");
+    }
+
+    #[tokio::test]
+    async fn not_found_items_are_not_looked_up_again_until_the_cooldown_passes() {
+        let lookups = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookups: Arc::clone(&lookups),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ notFoundCooldownSeconds: 1 }").await;
+        let http = test_support::http_client();
+        let status = SyncStatus::new();
+        let lookup_count = || lookups.load(std::sync::atomic::Ordering::SeqCst);
+
+        run_sync(&config, &http, &status, false, None).await.unwrap();
+        assert_eq!(lookup_count(), 1);
+
+        run_sync(&config, &http, &status, false, None).await.unwrap();
+        assert_eq!(lookup_count(), 1, "looked up again within the cooldown");
+        assert_eq!(status.skipped()[0].reason, SkipReason::NotFound);
+
+        sleep(Duration::from_millis(1100)).await;
+        run_sync(&config, &http, &status, false, None).await.unwrap();
+        assert_eq!(lookup_count(), 2, "not retried once the cooldown passed");
+    }
+
+    #[tokio::test]
+    async fn sync_report_counts_each_outcome() {
+        let lookup = |title: &str, year: i32, tmdb_id: i32| {
+            serde_json::json!([{ "title": title, "originalTitle": title, "sortTitle": title.to_lowercase(), "year": year, "tmdbId": tmdb_id }])
+        };
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            term_lookups: [("Heat 1995".to_string(), lookup("Heat", 1995, 949)), ("Ronin 1998".to_string(), lookup("Ronin", 1998, 8195))].into(),
+            existing: serde_json::json!([{ "id": 3, "tmdbId": 8195 }]),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let movies = [("1", "Heat", 1995), ("2", "Ronin", 1998), ("3", "Nowhere", 2001)];
+        let config = sync_config(&movies, &radarr_url, "{}").await;
+
+        let report = run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+
+        let counts = (report.added, report.skipped, report.not_found, report.failed, report.deleted);
+        assert_eq!(counts, (1, 1, 1, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn items_past_the_cycle_time_budget_are_deferred() {
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookup_delay: Duration::from_millis(600),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let movies = [("1", "Heat", 1995), ("2", "Ronin", 1998), ("3", "Collateral", 2004), ("4", "Thief", 1981)];
+        let config = sync_config(&movies, &radarr_url, "{ cycleTimeBudgetSeconds: 1 }").await;
+        let logs = test_support::Logs::default();
+
+        let report = {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap()
+        };
+
+        // Two lookups fit in the second, the check before the third finds it spent
+        assert_eq!(report.deferred, 2);
+        assert_eq!(report.added + report.skipped + report.not_found + report.failed, 2);
+        assert!(logs.lines().iter().any(|l| l.contains("deferring 2 remaining items to the next sync")), "{:?}", logs.lines());
+    }
+
+    #[tokio::test]
+    async fn sync_report_counts_failed_adds() {
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookup_delay: Duration::from_secs(30),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{ itemTimeoutSeconds: 1 }").await;
+
+        let report = run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+
+        assert_eq!((report.added, report.skipped, report.not_found, report.failed), (0, 0, 0, 1));
+    }
+
+    #[test]
+    fn each_verbose_flag_lowers_the_log_level_unless_it_is_set() {
+        let level = |args: &[&str]| {
+            let cli = Cli::parse_from([&["watchlistarr"], args].concat());
+            tracing_subscriber::EnvFilter::new(log_filter(&cli, &Configuration::default())).max_level_hint()
+        };
+
+        assert_eq!(level(&[]), Some(tracing::level_filters::LevelFilter::INFO));
+        assert_eq!(level(&["-v"]), Some(tracing::level_filters::LevelFilter::DEBUG));
+        assert_eq!(level(&["-vv"]), Some(tracing::level_filters::LevelFilter::TRACE));
+        assert_eq!(level(&["-vv", "--log-level", "warn"]), Some(tracing::level_filters::LevelFilter::WARN));
+    }
+
+    #[tokio::test]
+    async fn an_item_goes_to_every_backend_at_once_with_one_combined_outcome() {
+        let (router, radarr_writes) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            lookup_delay: Duration::from_secs(1),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+        let (router, overseerr_writes) = test_support::overseerr(serde_json::json!([{ "id": 949, "mediaType": "movie", "releaseDate": "1995-12-15" }]), true);
+        // Requests take as long as the Radarr lookup, so serial adds would take twice as long
+        let router = router.layer(axum::middleware::from_fn(|request: axum::extract::Request, next: axum::middleware::Next| async move {
+            if request.method() == axum::http::Method::POST {
+                sleep(Duration::from_secs(1)).await;
+            }
+            next.run(request).await
+        }));
+        let overseerr_url = test_support::serve(router).await;
+        let mut config = sync_config(&[("1", "Heat", 1995)], &radarr_url, "{}").await;
+        config.overseerr = Some(serde_yaml::from_str(&format!("baseUrl: {}\napikey: test-key\nalsoDirect: true", overseerr_url)).unwrap());
+        let logs = test_support::Logs::default();
+        let started = Instant::now();
+
+        let report = {
+            let _guard = logs.capture_at(tracing::Level::INFO);
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap()
+        };
+
+        assert!(started.elapsed() < Duration::from_millis(1800), "adds ran one after the other: {:?}", started.elapsed());
+        assert_eq!((overseerr_writes.lock().unwrap().len(), radarr_writes.lock().unwrap().len()), (1, 1));
+        assert_eq!((report.added, report.skipped), (1, 1));
+        let lines = logs.lines();
+        assert!(lines.iter().any(|l| l.contains("'Heat': already exists in Overseerr, added to Radarr")), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn a_radarr_only_sync_leaves_shows_and_sonarr_alone() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><MediaContainer size="2">
+            <Video ratingKey="1" type="movie" title="Heat" year="1995" guid="plex://movie/1"/>
+            <Directory ratingKey="2" type="show" title="The Wire" year="2002" guid="plex://show/2"/>
+        </MediaContainer>"#;
+        let (radarr, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(radarr).await;
+        let mut config = sync_config(&[], &radarr_url, "{}").await;
+        config.plex.as_mut().unwrap().metadata_url = Some(test_support::serve(test_support::plex_metadata(xml.to_string())).await);
+        config.sonarr = Some(test_support::sonarr_config(&test_support::unreachable_url().await, ""));
+        let status = SyncStatus::new();
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture();
+            run_sync(&config, &test_support::http_client(), &status, false, Some(ItemType::Movie)).await.unwrap();
+        }
+
+        let skipped: Vec<String> = status.skipped().into_iter().map(|s| s.title).collect();
+        assert_eq!(skipped, ["Heat"]);
+        assert!(!logs.lines().iter().any(|l| l.contains("Sonarr")), "Sonarr was contacted: {:?}", logs.lines());
+    }
+
+    #[tokio::test]
+    async fn a_mostly_failing_sync_raises_an_alert_with_the_breakdown() {
+        let movies = [("1", "Heat", 1995), ("2", "Ronin", 1998), ("3", "Collateral", 2004), ("4", "Thief", 1981), ("5", "Manhunter", 1986)];
+        let lookup: Vec<serde_json::Value> = movies
+            .iter()
+            .map(|(id, title, year)| serde_json::json!({ "title": title, "originalTitle": title, "sortTitle": title.to_lowercase(), "year": year, "tmdbId": id.parse::<i32>().unwrap() }))
+            .collect();
+        // A library that can't be read fails every add
+        let (router, _) = test_support::arr("movie", test_support::Arr {
+            lookup: serde_json::Value::Array(lookup),
+            existing: serde_json::json!("broken"),
+            ..test_support::Arr::default()
+        });
+        let radarr_url = test_support::serve(router).await;
+
+        for (movies, alerted) in [(&movies[..], true), (&movies[..4], false)] {
+            let config = sync_config(movies, &radarr_url, "{ failureAlertThreshold: 0.5 }").await;
+            let logs = test_support::Logs::default();
+
+            let report = {
+                let _guard = logs.capture();
+                run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap()
+            };
+
+            assert_eq!(report.failed, movies.len());
+            let alert = format!("RSS sync failed {0} of {0} adds (100%, over the 50% alert threshold): Radarr {0}", movies.len());
+            assert_eq!(logs.lines().iter().any(|l| l.contains(&alert)), alerted, "{:?}", logs.lines());
+        }
+    }
+
+    #[tokio::test]
+    async fn startup_waits_for_an_arr_that_answers_after_a_few_polls() {
+        let (router, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&radarr_url, ""));
+        let unreachable = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&test_support::unreachable_url().await, ""));
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture_at(tracing::Level::INFO);
+            // Down for the first two polls, then up
+            wait_until_ready("Radarr", Duration::from_secs(5), Duration::from_millis(10), || {
+                let client = if attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 { &unreachable } else { &client };
+                client.get_system_status()
+            })
+            .await;
+        }
+
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let lines = logs.lines();
+        assert_eq!(lines.iter().filter(|l| l.contains("Waiting for Radarr to start")).count(), 2, "{:?}", lines);
+        assert!(lines.last().unwrap().contains("Radarr is ready after 3 attempts"), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn startup_gives_up_on_an_arr_that_never_answers() {
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&test_support::unreachable_url().await, ""));
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture();
+            wait_until_ready("Radarr", Duration::from_millis(100), Duration::from_millis(30), || client.get_system_status()).await;
+        }
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        assert!(lines[0].contains("Radarr still not answering"), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn plex_only_syncs_do_nothing_without_errors() {
+        // Plex is unreachable too, so any processing at all would fail the sync
+        let unreachable = test_support::unreachable_url().await;
+        let config = Configuration { plex: Some(test_support::plex_config(&unreachable, &unreachable)), ..Configuration::default() };
+        let status = SyncStatus::new();
+        let logs = test_support::Logs::default();
+
+        let (report, deleted) = {
+            let _guard = logs.capture();
+            let report = run_sync(&config, &test_support::http_client(), &status, true, None).await.unwrap();
+            (report, run_delete_sync(&config, &test_support::http_client(), &status).await)
+        };
+
+        assert_eq!((report.added, report.skipped, report.not_found, report.failed), (0, 0, 0, 0));
+        assert!(deleted.is_ok());
+        assert!(status.skipped().is_empty());
+        assert!(logs.lines().is_empty(), "{:?}", logs.lines());
+    }
+
+    /// Stand-in answering 502 to its first `failures` requests, then `body`
+    async fn flaky_server(path: &str, failures: usize, body: String) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let router = axum::Router::new().route(path, axum::routing::get(move || {
+            let hit = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let body = body.clone();
+            async move {
+                use axum::response::IntoResponse;
+                if hit < failures { StatusCode::BAD_GATEWAY.into_response() } else { body.into_response() }
+            }
+        }));
+        (test_support::serve(router).await, hits)
+    }
+
+    #[tokio::test]
+    async fn each_backend_retries_by_its_own_policy() {
+        let http = test_support::http_client().with_retry(http::RetryPolicy { max_attempts: 1, backoff: Duration::ZERO }, None);
+        let (plex_url, plex_hits) = flaky_server("/library/sections/watchlist/all", 2, test_support::watchlist_xml(&[("1", "Heat", 1995)])).await;
+        let (radarr_url, radarr_hits) = flaky_server("/api/v3/system/status", 2, r#"{ "version": "5.0" }"#.to_string()).await;
+        let mut plex_config = test_support::plex_config(&plex_url, &test_support::unreachable_url().await);
+        plex_config.retry = serde_yaml::from_str("{ maxAttempts: 3, backoffMillis: 0 }").unwrap();
+        let radarr_config = test_support::radarr_config(&radarr_url, "retry: { maxAttempts: 1 }\n");
+        let radarr_http = http.with_retry(http::RetryPolicy { max_attempts: 5, backoff: Duration::ZERO }, None);
+
+        let watchlist = PlexClient::new(http, plex_config).get_watchlist().await;
+        let radarr = RadarrClient::new(radarr_http, radarr_config).get_system_status().await;
+
+        assert_eq!(watchlist.unwrap().len(), 1);
+        assert_eq!(plex_hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(radarr.is_err());
+        assert_eq!(radarr_hits.load(std::sync::atomic::Ordering::SeqCst), 1, "retries were disabled for Radarr");
+    }
+
+    #[tokio::test]
+    async fn progress_lines_are_logged_every_configured_number_of_items() {
+        let (router, _) = test_support::arr("movie", test_support::Arr::default());
+        let radarr_url = test_support::serve(router).await;
+        let movies = [("1", "Heat", 1995), ("2", "Ronin", 1998), ("3", "Collateral", 2004), ("4", "Thief", 1981), ("5", "Manhunter", 1986)];
+        let config = sync_config(&movies, &radarr_url, "{ progressLogging: { everyItems: 2, everySeconds: 3600 } }").await;
+        let logs = test_support::Logs::default();
+
+        {
+            let _guard = logs.capture_at(tracing::Level::INFO);
+            run_sync(&config, &test_support::http_client(), &SyncStatus::new(), false, None).await.unwrap();
+        }
+
+        let progress: Vec<String> = logs.lines().into_iter().filter(|l| l.contains("Processed ")).collect();
+        assert_eq!(progress.len(), 2, "{:?}", progress);
+        assert!(progress[0].contains("Processed 2/5, added 0, skipped 2, failed 0"), "{:?}", progress);
+        assert!(progress[1].contains("Processed 4/5, added 0, skipped 4, failed 0"), "{:?}", progress);
+    }
+
+    #[test]
+    fn progress_logging_quiets_the_per_item_client_logs() {
+        let cli = Cli::parse_from(["watchlistarr"]);
+        let config: Configuration = serde_yaml::from_str("sync: { progressLogging: {} }").unwrap();
+
+        let filter = log_filter(&cli, &config);
+
+        assert!(filter.starts_with("info,") && filter.contains("watchlistarr::radarr=warn"), "{}", filter);
+        assert_eq!(log_filter(&Cli::parse_from(["watchlistarr", "-v"]), &config), "debug");
+    }
+
+    #[test]
+    fn color_is_only_used_on_a_terminal_without_no_color() {
+        let cli = Cli::parse_from(["watchlistarr"]);
+        assert!(!use_color(&cli, false));
+
+        let cli = Cli::parse_from(["watchlistarr", "--no-color"]);
+        assert!(!use_color(&cli, true));
+    }
+
+    fn routes(yaml: &str) -> Vec<RouteConfig> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn select_targets_sends_movies_to_radarr_and_shows_to_sonarr() {
+        let movie = test_support::item(ItemType::Movie, "Heat", 1995);
+        let show = test_support::item(ItemType::Show, "The Wire", 2002);
+
+        assert_eq!(select_targets(&movie, &[], false, true), vec![Target::Radarr]);
+        assert_eq!(select_targets(&show, &[], false, true), vec![Target::Sonarr]);
+        assert_eq!(select_targets(&movie, &[], true, false), vec![Target::Overseerr]);
+        assert_eq!(select_targets(&show, &[], true, true), vec![Target::Overseerr, Target::Sonarr]);
+    }
+
+    #[test]
+    fn select_targets_routes_by_the_first_matching_genre() {
+        let routes = routes("
+- genre: documentary
+  targets: [overseerr]
+- genre: Drama
+  targets: [radarr, sonarr]
+");
+        let mut documentary = test_support::item(ItemType::Movie, "Free Solo", 2018);
+        documentary.genres = vec!["Drama".to_string(), "Documentary".to_string()];
+        let mut drama = test_support::item(ItemType::Show, "The Wire", 2002);
+        drama.genres = vec!["Drama".to_string()];
+        let comedy = test_support::item(ItemType::Movie, "Airplane!", 1980);
+
+        assert_eq!(select_targets(&documentary, &routes, false, true), vec![Target::Overseerr]);
+        // Radarr can't take a show, so only Sonarr is left of that route
+        assert_eq!(select_targets(&drama, &routes, true, false), vec![Target::Sonarr]);
+        assert_eq!(select_targets(&comedy, &routes, false, true), vec![Target::Radarr]);
+    }
+}
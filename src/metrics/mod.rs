@@ -0,0 +1,158 @@
+//! Gauges shared by the Prometheus `/metrics` endpoint and, with the
+//! `statsd` feature, the StatsD/DogStatsD sink, so both report the same set.
+
+use crate::status::SyncStatus;
+use std::fmt::Write;
+
+pub struct Gauge {
+    /// Name without the `watchlistarr` prefix each format adds its own way
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+pub const PLEX_TOKEN_VALID: Gauge = Gauge {
+    name: "plex_token_valid",
+    help: "Whether Plex accepted the token at the last ping",
+};
+
+pub const SERVICE_UP: Gauge = Gauge {
+    name: "service_up",
+    help: "Whether the backend answered its last pre-flight check",
+};
+
+const GAUGES: &[&Gauge] = &[&PLEX_TOKEN_VALID, &SERVICE_UP];
+
+/// One reading of a gauge, with at most one label
+pub struct Sample {
+    pub gauge: &'static Gauge,
+    pub label: Option<(&'static str, String)>,
+    pub value: u8,
+}
+
+/// Current value of every gauge that has one
+pub fn samples(status: &SyncStatus) -> Vec<Sample> {
+    let mut samples = Vec::new();
+
+    if let Some(valid) = status.plex_token_valid() {
+        samples.push(Sample { gauge: &PLEX_TOKEN_VALID, label: None, value: u8::from(valid) });
+    }
+    for (service, health) in status.services() {
+        samples.push(Sample { gauge: &SERVICE_UP, label: Some(("service", service)), value: u8::from(health.up) });
+    }
+
+    samples
+}
+
+/// Prometheus text exposition format
+pub fn prometheus(status: &SyncStatus) -> String {
+    let samples = samples(status);
+    let mut body = String::new();
+
+    for gauge in GAUGES {
+        let _ = writeln!(body, "# HELP watchlistarr_{} {}", gauge.name, gauge.help);
+        let _ = writeln!(body, "# TYPE watchlistarr_{} gauge", gauge.name);
+        for sample in samples.iter().filter(|s| std::ptr::eq(s.gauge, *gauge)) {
+            match sample.label {
+                Some((key, ref value)) => {
+                    let _ = writeln!(body, "watchlistarr_{}{{{}=\"{}\"}} {}", gauge.name, key, value, sample.value);
+                }
+                None => {
+                    let _ = writeln!(body, "watchlistarr_{} {}", gauge.name, sample.value);
+                }
+            }
+        }
+    }
+
+    body
+}
+
+#[cfg(feature = "statsd")]
+pub use statsd::push_statsd;
+
+#[cfg(feature = "statsd")]
+mod statsd {
+    use super::{samples, Sample};
+    use crate::config::StatsdConfig;
+    use crate::status::SyncStatus;
+    use anyhow::Result;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+    use tokio::time::sleep;
+    use tracing::{debug, info, warn};
+
+    /// Send every gauge over UDP each `intervalSeconds`. Labels become
+    /// DogStatsD tags when `tags` is set, otherwise a name suffix.
+    pub async fn push_statsd(config: StatsdConfig, status: SyncStatus) -> Result<()> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(&config.address).await?;
+        info!("Sending StatsD metrics to {}", config.address);
+
+        let prefix = config.prefix.as_deref().unwrap_or("watchlistarr");
+        let tags = config.tags.unwrap_or(false);
+        let interval = Duration::from_secs(config.interval_seconds.unwrap_or(10).max(1));
+
+        loop {
+            let lines: Vec<String> = samples(&status).into_iter().map(|sample| line(prefix, tags, sample)).collect();
+
+            if !lines.is_empty() {
+                match socket.send(lines.join("\n").as_bytes()).await {
+                    Ok(_) => debug!("Sent {} StatsD gauges", lines.len()),
+                    Err(e) => warn!("Failed to send StatsD metrics to {}: {}", config.address, e),
+                }
+            }
+
+            sleep(interval).await;
+        }
+    }
+
+    /// One gauge in StatsD's `name:value|g` format
+    fn line(prefix: &str, tags: bool, sample: Sample) -> String {
+        match sample.label {
+            Some((key, value)) if tags => format!("{}.{}:{}|g|#{}:{}", prefix, sample.gauge.name, sample.value, key, value),
+            Some((_, value)) => format!("{}.{}.{}:{}|g", prefix, sample.gauge.name, value, sample.value),
+            None => format!("{}.{}:{}|g", prefix, sample.gauge.name, sample.value),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::metrics::{PLEX_TOKEN_VALID, SERVICE_UP};
+
+        #[test]
+        fn labels_become_a_name_suffix_or_dogstatsd_tags() {
+            let up = || Sample { gauge: &SERVICE_UP, label: Some(("service", "radarr".to_string())), value: 1 };
+
+            assert_eq!(line("watchlistarr", false, up()), "watchlistarr.service_up.radarr:1|g");
+            assert_eq!(line("watchlistarr", true, up()), "watchlistarr.service_up:1|g|#service:radarr");
+            assert_eq!(
+                line("home", true, Sample { gauge: &PLEX_TOKEN_VALID, label: None, value: 0 }),
+                "home.plex_token_valid:0|g"
+            );
+        }
+
+        #[tokio::test]
+        async fn gauges_arrive_as_one_newline_separated_datagram() {
+            let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let config = StatsdConfig {
+                address: agent.local_addr().unwrap().to_string(),
+                prefix: None,
+                tags: Some(true),
+                interval_seconds: None,
+            };
+            let status = SyncStatus::new();
+            status.set_plex_token_valid(true);
+            status.record_service("radarr", Some("5.0.0".to_string()));
+
+            let sender = tokio::spawn(push_statsd(config, status));
+            let mut buffer = [0u8; 1024];
+            let received = tokio::time::timeout(Duration::from_secs(5), agent.recv(&mut buffer)).await.unwrap().unwrap();
+            sender.abort();
+
+            assert_eq!(
+                std::str::from_utf8(&buffer[..received]).unwrap(),
+                "watchlistarr.plex_token_valid:1|g\nwatchlistarr.service_up:1|g|#service:radarr"
+            );
+        }
+    }
+}
@@ -1,6 +1,12 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use chrono::{DateTime, Utc};
 
+/// Deserialize an optional TMDB/TVDB id, treating 0 and negative values
+/// (which some Sonarr/Radarr responses use for "unknown") as absent
+pub fn positive_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+    Ok(Option::<i32>::deserialize(deserializer)?.filter(|&id| id > 0))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub id: String,
@@ -11,6 +17,15 @@ pub struct Item {
     pub imdb_id: Option<String>,
     pub tmdb_id: Option<i32>,
     pub tvdb_id: Option<i32>,
+    /// Plex labels and collections the item belongs to
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Title in the original language, when Plex has one
+    #[serde(default)]
+    pub original_title: Option<String>,
+    /// Plex genres, which `sync.routes` can route on
+    #[serde(default)]
+    pub genres: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -43,4 +58,45 @@ pub struct RootFolder {
 pub struct Tag {
     pub id: i32,
     pub label: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemStatus {
+    #[serde(rename = "appName")]
+    pub app_name: Option<String>,
+    pub version: String,
+}
+
+/// What happened to a single watchlist item when it was sent to a backend
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddOutcome {
+    /// Added, with the backend's id for the new entry when it returned one
+    Added(Option<i64>),
+    Skipped(SkipReason),
+}
+
+/// Why an item was not added, kept so users can see it via the status endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    WrongType,
+    AlreadyExists,
+    NotFound,
+    BackendUnavailable,
+    /// Several lookup results matched and none could be picked safely
+    Ambiguous,
+    Filtered(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::WrongType => write!(f, "wrong item type for backend"),
+            SkipReason::AlreadyExists => write!(f, "already exists"),
+            SkipReason::NotFound => write!(f, "not found in lookup"),
+            SkipReason::BackendUnavailable => write!(f, "backend unavailable"),
+            SkipReason::Ambiguous => write!(f, "ambiguous lookup"),
+            SkipReason::Filtered(reason) => write!(f, "filtered: {}", reason),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::config::OverseerrConfig;
+use crate::http::{HttpClient, HttpStatusError};
+use crate::models::{AddOutcome, Item, ItemType, SkipReason};
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, instrument, warn};
+
+/// Creates requests in Overseerr/Jellyseerr (same API) so its approval
+/// workflow decides what reaches Sonarr/Radarr
+pub struct OverseerrClient {
+    http: HttpClient,
+    config: OverseerrConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct MediaRequest {
+    #[serde(rename = "mediaType")]
+    media_type: &'static str,
+    #[serde(rename = "mediaId")]
+    media_id: i32,
+    /// Only sent for TV, where Overseerr requires a season selection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seasons: Option<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverseerrStatus {
+    pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    id: i32,
+    #[serde(rename = "mediaType")]
+    media_type: String,
+    #[serde(rename = "releaseDate")]
+    release_date: Option<String>,
+    #[serde(rename = "firstAirDate")]
+    first_air_date: Option<String>,
+}
+
+impl OverseerrClient {
+    pub fn new(http: HttpClient, config: OverseerrConfig) -> Result<Self> {
+        let http = match config.timeout_seconds {
+            Some(secs) => http.with_timeout(Duration::from_secs(secs)),
+            None => http,
+        };
+        let http = http.with_header("x-api-key", &config.api_key)?;
+        Ok(Self { http, config })
+    }
+
+    fn build_url(&self, path: &str, query: &[(&str, &str)]) -> String {
+        let mut url = format!("{}/api/v1/{}",
+                              self.config.base_url.trim_end_matches('/'),
+                              path.trim_start_matches('/'));
+
+        let mut separator = '?';
+        for (key, value) in query {
+            url.push(separator);
+            url.push_str(&urlencoding::encode(key));
+            url.push('=');
+            url.push_str(&urlencoding::encode(value));
+            separator = '&';
+        }
+        url
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_status(&self) -> Result<OverseerrStatus> {
+        let url = self.build_url("status", &[]);
+
+        self.http.get_json(&url).await
+    }
+
+    /// Whether items should still be added to Sonarr/Radarr directly as well
+    pub fn also_direct(&self) -> bool {
+        self.config.also_direct.unwrap_or(false)
+    }
+
+    /// Request `item`, treating "already requested/available" as a skip
+    #[instrument(skip(self, item))]
+    pub async fn request(&self, item: &Item) -> Result<AddOutcome> {
+        let Some(tmdb_id) = self.resolve_tmdb_id(item).await? else {
+            warn!("Could not find a TMDB id for '{}' in Overseerr", item.title);
+            return Ok(AddOutcome::Skipped(SkipReason::NotFound));
+        };
+
+        let request = MediaRequest {
+            media_type: media_type(&item.item_type),
+            media_id: tmdb_id,
+            seasons: (item.item_type == ItemType::Show).then_some("all"),
+        };
+
+        let url = self.build_url("request", &[]);
+        match self.http.post_json::<serde_json::Value, _>(&url, &request).await {
+            Ok(_) => {
+                info!("Requested '{}' (TMDB: {}) in Overseerr", item.title, tmdb_id);
+                Ok(AddOutcome::Added(None))
+            }
+            Err(e) if HttpStatusError::is_status(&e, StatusCode::CONFLICT) => {
+                info!("'{}' (TMDB: {}) is already requested in Overseerr, skipping", item.title, tmdb_id);
+                Ok(AddOutcome::Skipped(SkipReason::AlreadyExists))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Use the item's TMDB id when Plex gave us one, otherwise search by
+    /// title and prefer a result from the same year
+    async fn resolve_tmdb_id(&self, item: &Item) -> Result<Option<i32>> {
+        if let Some(tmdb_id) = item.tmdb_id {
+            return Ok(Some(tmdb_id));
+        }
+
+        let mut query = vec![("query", item.title.as_str()), ("page", "1")];
+        if let Some(ref language) = self.config.language {
+            query.push(("language", language));
+        }
+        let url = self.build_url("search", &query);
+        let response: SearchResponse = self.http.get_json(&url).await?;
+
+        let wanted_type = media_type(&item.item_type);
+        let candidates: Vec<&SearchResult> = response
+            .results
+            .iter()
+            .filter(|r| r.media_type == wanted_type)
+            .collect();
+
+        let year_matches = |r: &&SearchResult| match item.year {
+            Some(year) => r
+                .release_date
+                .as_deref()
+                .or(r.first_air_date.as_deref())
+                .is_some_and(|date| date.starts_with(&year.to_string())),
+            None => false,
+        };
+
+        Ok(candidates
+            .iter()
+            .copied()
+            .find(year_matches)
+            .or(candidates.first().copied())
+            .map(|r| r.id))
+    }
+}
+
+fn media_type(item_type: &ItemType) -> &'static str {
+    match item_type {
+        ItemType::Movie => "movie",
+        ItemType::Show => "tv",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    async fn client_for(results: serde_json::Value, requested: bool) -> (OverseerrClient, test_support::Writes) {
+        let (router, writes) = test_support::overseerr(results, requested);
+        let base_url = test_support::serve(router).await;
+        let config: OverseerrConfig = serde_yaml::from_str(&format!("baseUrl: {}/\napikey: test-key\n", base_url)).unwrap();
+        (OverseerrClient::new(test_support::http_client(), config).unwrap(), writes)
+    }
+
+    #[tokio::test]
+    async fn movies_are_requested_by_their_tmdb_id() {
+        let (client, writes) = client_for(json!([]), false).await;
+        let mut item = test_support::item(ItemType::Movie, "Heat", 1995);
+        item.tmdb_id = Some(949);
+
+        let outcome = client.request(&item).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Added(None));
+        assert_eq!(writes.lock().unwrap()[0].body, json!({ "mediaType": "movie", "mediaId": 949 }));
+    }
+
+    #[tokio::test]
+    async fn searches_carry_the_configured_language() {
+        let queries: std::sync::Arc<std::sync::Mutex<Vec<String>>> = Default::default();
+        let recorded = std::sync::Arc::clone(&queries);
+        let router = axum::Router::new().route(
+            "/api/v1/search",
+            axum::routing::get(move |axum::extract::RawQuery(query): axum::extract::RawQuery| async move {
+                recorded.lock().unwrap().push(query.unwrap_or_default());
+                axum::Json(json!({ "results": [] }))
+            }),
+        );
+        let base_url = test_support::serve(router).await;
+        let config: OverseerrConfig = serde_yaml::from_str(&format!("baseUrl: {}\napikey: test-key\nlanguage: de\n", base_url)).unwrap();
+        let client = OverseerrClient::new(test_support::http_client(), config).unwrap();
+
+        client.request(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(*queries.lock().unwrap(), ["query=Heat&page=1&language=de"]);
+    }
+
+    #[tokio::test]
+    async fn shows_are_searched_for_and_request_every_season() {
+        let (client, writes) = client_for(
+            json!([
+                { "id": 1, "mediaType": "movie", "releaseDate": "2002-06-02" },
+                { "id": 2, "mediaType": "tv", "firstAirDate": "2011-01-01" },
+                { "id": 1438, "mediaType": "tv", "firstAirDate": "2002-06-02" }
+            ]),
+            false,
+        )
+        .await;
+
+        client.request(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+
+        assert_eq!(writes.lock().unwrap()[0].body, json!({ "mediaType": "tv", "mediaId": 1438, "seasons": "all" }));
+    }
+
+    #[tokio::test]
+    async fn already_requested_items_are_skipped_as_existing() {
+        let (client, _) = client_for(json!([{ "id": 949, "mediaType": "movie", "releaseDate": "1995-12-15" }]), true).await;
+
+        let outcome = client.request(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn items_without_a_search_result_are_not_found() {
+        let (client, writes) = client_for(json!([{ "id": 1438, "mediaType": "tv" }]), false).await;
+
+        let outcome = client.request(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::NotFound));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+}
@@ -1,34 +1,251 @@
 use crate::config::PlexConfig;
 use crate::http::HttpClient;
 use crate::models::{Item, ItemType, WatchlistItem};
-use anyhow::Result;
+use crate::status::SyncStatus;
+use anyhow::{bail, Context, Result};
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::sleep;
 use tracing::{debug, error, info, instrument, warn};
 
+const PLEX_METADATA_API: &str = "https://metadata.provider.plex.tv";
+const PLEX_COMMUNITY_API: &str = "https://community.plex.tv/api";
+const FRIEND_WATCHLIST_PAGE_SIZE: u32 = 100;
+const LIBRARY_PAGE_SIZE: usize = 500;
+/// Pause between per-item metadata requests, which can number in the hundreds
+const METADATA_REQUEST_INTERVAL: Duration = Duration::from_millis(250);
+/// Friends' watchlists fetched at once unless `friendsConcurrency` says
+/// otherwise. Plex rate limits this on servers with many shared users; a
+/// 429 is waited out through its Retry-After.
+const DEFAULT_FRIENDS_CONCURRENCY: usize = 4;
+
+const DEFAULT_FRIENDS_CACHE: Duration = Duration::from_secs(60 * 60);
+
 pub struct PlexClient {
     http: HttpClient,
     config: PlexConfig,
+    /// Per-item metadata by rating key, so each item is fetched at most once
+    metadata: Mutex<HashMap<String, ItemMetadata>>,
+    /// Holds the friends list between syncs, when set
+    friends_cache: Option<SyncStatus>,
+}
+
+/// External ids from an item's full metadata `<Guid>` children, and its
+/// `<Genre>` tags
+#[derive(Debug, Clone, Default)]
+pub struct ItemMetadata {
+    pub imdb_id: Option<String>,
+    pub tmdb_id: Option<i32>,
+    pub tvdb_id: Option<i32>,
+    pub genres: Vec<String>,
+}
+
+/// Watchlist items gathered from several accounts or friends, plus the ones
+/// whose watchlist could not be fetched and so are missing from `items`
+#[derive(Debug, Default)]
+pub struct Watchlists {
+    pub items: Vec<WatchlistItem>,
+    pub failed: Vec<String>,
+}
+
+impl Watchlists {
+    /// The items, or an error when any watchlist is missing
+    pub fn complete(self) -> Result<Vec<WatchlistItem>> {
+        if !self.failed.is_empty() {
+            bail!("could not fetch the watchlist of {}", self.failed.join(", "));
+        }
+        Ok(self.items)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlexFriend {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GraphQlRequest<'a> {
+    query: &'a str,
+    variables: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AllFriendsData {
+    #[serde(rename = "allFriendsV2")]
+    all_friends: Vec<FriendEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendEntry {
+    user: PlexFriend,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchlistHubData {
+    user: WatchlistHubUser,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchlistHubUser {
+    watchlist: WatchlistPage,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchlistPage {
+    nodes: Vec<WatchlistNode>,
+    #[serde(rename = "pageInfo")]
+    page_info: PageInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchlistNode {
+    id: String,
+    title: String,
+    #[serde(rename = "type")]
+    item_type: String,
+    year: Option<i32>,
 }
 
+#[derive(Debug, Deserialize)]
+struct PageInfo {
+    #[serde(rename = "hasNextPage")]
+    has_next_page: bool,
+    #[serde(rename = "endCursor")]
+    end_cursor: Option<String>,
+}
+
+const ALL_FRIENDS_QUERY: &str = "query GetAllFriends {
+  allFriendsV2 {
+    user {
+      id
+      username
+    }
+  }
+}";
+
+const FRIEND_WATCHLIST_QUERY: &str = "query GetWatchlistHub($uuid: ID = \"\", $first: PaginationInt!, $after: String) {
+  user(id: $uuid) {
+    watchlist(first: $first, after: $after) {
+      nodes {
+        id
+        title
+        type
+        year
+      }
+      pageInfo {
+        hasNextPage
+        endCursor
+      }
+    }
+  }
+}";
+
 impl PlexClient {
     pub fn new(http: HttpClient, config: PlexConfig) -> Self {
-        Self { http, config }
+        let http = match config.retry {
+            Some(ref retry) => http.with_retry_policy(retry.apply_to(http.retry_policy())),
+            None => http,
+        };
+        let http = match config.client_identifier {
+            Some(ref identifier) => http.with_header("x-plex-client-identifier", identifier).unwrap_or_else(|e| {
+                warn!("Ignoring invalid Plex client identifier '{}': {}", identifier, e);
+                http
+            }),
+            None => http,
+        };
+        Self {
+            http,
+            config,
+            metadata: Mutex::new(HashMap::new()),
+            friends_cache: None,
+        }
+    }
+
+    /// Reuse the friends list kept in `status` for `friendsCacheSeconds`
+    /// instead of fetching it every sync
+    pub fn with_friends_cache(mut self, status: SyncStatus) -> Self {
+        self.friends_cache = Some(status);
+        self
+    }
+
+    fn metadata_api(&self) -> &str {
+        self.config.metadata_url.as_deref().unwrap_or(PLEX_METADATA_API)
+    }
+
+    fn community_api(&self) -> &str {
+        self.config.community_url.as_deref().unwrap_or(PLEX_COMMUNITY_API)
     }
 
     #[instrument(skip(self))]
     pub async fn get_watchlist(&self) -> Result<Vec<WatchlistItem>> {
+        self.get_watchlist_of_type(None).await
+    }
+
+    /// Fetch the owner's watchlist plus those of any additional configured
+    /// accounts, with each account's items attributed to its label. Only the
+    /// owner's watchlist is required; accounts that fail are listed in `failed`.
+    #[instrument(skip(self))]
+    pub async fn get_all_accounts_watchlist(&self, item_type: Option<ItemType>) -> Result<Watchlists> {
+        let mut watchlists = Watchlists {
+            items: self.get_watchlist_of_type(item_type.clone()).await?,
+            failed: Vec::new(),
+        };
+
+        for account in self.config.accounts.iter().flatten() {
+            let account_config = PlexConfig {
+                account_token: account.token.clone(),
+                ..self.config.clone()
+            };
+            let account_client = PlexClient::new(self.http.clone(), account_config);
+
+            // One expired token shouldn't hold up every other account's sync
+            let account_items = match account_client.get_watchlist_of_type(item_type.clone()).await {
+                Ok(account_items) => account_items,
+                Err(e) => {
+                    warn!("Failed to fetch watchlist for account {}, skipping it this sync: {}", account.label, e);
+                    watchlists.failed.push(account.label.clone());
+                    continue;
+                }
+            };
+            debug!("Retrieved {} watchlist items for account {}", account_items.len(), account.label);
+
+            watchlists.items.extend(account_items.into_iter().map(|mut watchlist_item| {
+                watchlist_item.user_id = account.label.clone();
+                watchlist_item
+            }));
+        }
+
+        Ok(watchlists)
+    }
+
+    /// Fetch the watchlist, asking Plex to only return `item_type` when set
+    #[instrument(skip(self))]
+    pub async fn get_watchlist_of_type(&self, item_type: Option<ItemType>) -> Result<Vec<WatchlistItem>> {
         info!("Fetching Plex watchlist");
         
-        let url = format!(
-            "https://metadata.provider.plex.tv/library/sections/watchlist/all?X-Plex-Token={}",
-            self.config.token
-        );
+        let url = self.watchlist_url(item_type.as_ref());
 
         match self.http.get(&url).await {
             Ok(response) => {
-                let xml_text = response.text().await?;
+                // Honour the Content-Type charset (some proxies transcode), defaulting to UTF-8
+                let xml_text = response.text_with_charset("utf-8").await?;
                 debug!("Received XML response: {} chars", xml_text.len());
                 
-                let items = self.parse_xml_watchlist(&xml_text)?;
+                let items = self.parse_xml_watchlist(&xml_text).map_err(|e| {
+                    error!("Skipping this sync, could not parse Plex watchlist: {:#}", e);
+                    e
+                })?;
                 
                 info!("Retrieved {} watchlist items", items.len());
                 Ok(items)
@@ -40,33 +257,78 @@ impl PlexClient {
         }
     }
 
+    fn watchlist_url(&self, item_type: Option<&ItemType>) -> String {
+        let mut url = format!(
+            "{}/library/sections/watchlist/all?X-Plex-Token={}",
+            self.metadata_api(),
+            self.config.account_token
+        );
+
+        // Plex metadata type codes: 1 = movie, 2 = show
+        match item_type {
+            Some(ItemType::Movie) => url.push_str("&type=1"),
+            Some(ItemType::Show) => url.push_str("&type=2"),
+            None => {}
+        }
+
+        if let Some(ref sort) = self.config.watchlist_sort {
+            url.push_str(&format!("&sort={}", urlencoding::encode(sort)));
+        }
+
+        url
+    }
+
     fn parse_xml_watchlist(&self, xml: &str) -> Result<Vec<WatchlistItem>> {
         let mut items = Vec::new();
+
+        // A leading byte-order mark is not part of the document
+        let xml = xml.strip_prefix('\u{feff}').unwrap_or(xml);
+
+        // The extractors below scan for substrings and will happily return a
+        // partial list from a truncated body. Refuse anything that is not a
+        // complete document so a bad response aborts the sync instead of
+        // looking like a shrunken watchlist.
+        check_well_formed(xml).context("Plex returned a malformed watchlist response")?;
+
+        // A well-formed body that is not a watchlist (an error or login page
+        // from a proxy) must not be read as an empty watchlist either
+        if !xml.contains("<MediaContainer") {
+            bail!("Plex response is not a watchlist (no MediaContainer element)");
+        }
         
         info!("Starting XML parsing for {} character XML", xml.len());
+
+        // Overlapping pages can list an item twice; only the first counts
+        let mut seen_rating_keys = HashSet::new();
         
-        // Find all Video elements (movies) - they contain type="movie"
+        // Find all Video elements (movies) - type="movie", or inferred from the GUID
         let mut start_pos = 0;
-        while let Some(video_start) = xml[start_pos..].find("<Video ") {
+        while let Some(video_start) = find_opening_tag(&xml[start_pos..], "Video") {
             let actual_start = start_pos + video_start;
-            if let Some(end_pos) = xml[actual_start..].find(">") {
+            if let Some(end_pos) = tag_end(&xml[actual_start..]) {
                 let element = &xml[actual_start..actual_start + end_pos + 1];
                 
                 // Check if this is a movie with the required attributes
-                if element.contains("type=\"movie\"") && element.contains("title=") && element.contains("ratingKey=") {
+                if let Some(item_type) = self.extract_item_type(element) {
                     if let (Some(title), Some(rating_key)) = (self.extract_title(element), self.extract_rating_key(element)) {
                         let year = self.extract_year(element);
                         let guid = self.extract_guid(element);
+                        let labels = self.extract_child_tags(&xml[actual_start..], element, "Video", &["Label", "Collection"]);
+                        let genres = self.extract_child_tags(&xml[actual_start..], element, "Video", &["Genre"]);
+                        let original_title = attribute_value(element, "originalTitle").filter(|t| !t.trim().is_empty());
                         
                         let item = Item {
                             id: rating_key,
                             title: title.clone(),
                             year,
-                            item_type: ItemType::Movie,
+                            item_type,
                             guid,
                             imdb_id: None,
                             tmdb_id: None,
                             tvdb_id: None,
+                            labels,
+                            original_title,
+                            genres,
                         };
                         
                         let watchlist_item = WatchlistItem {
@@ -75,11 +337,17 @@ impl PlexClient {
                             user_id: "self".to_string(),
                         };
                         
-                        info!("Found movie: {} ({}) [Rating Key: {}]", 
-                              title, 
-                              year.map_or("Unknown".to_string(), |y| y.to_string()),
-                              &watchlist_item.item.id);
-                        items.push(watchlist_item);
+                        if !seen_rating_keys.insert(watchlist_item.item.id.clone()) {
+                            debug!("Ignoring repeated entry '{}' [Rating Key: {}] in the same response",
+                                   title, &watchlist_item.item.id);
+                        } else {
+                            info!("Found {}: {} ({}) [Rating Key: {}]", 
+                                  type_name(&watchlist_item.item.item_type),
+                                  title, 
+                                  year.map_or("Unknown".to_string(), |y| y.to_string()),
+                                  &watchlist_item.item.id);
+                            items.push(watchlist_item);
+                        }
                     }
                 }
                 start_pos = actual_start + end_pos + 1;
@@ -88,28 +356,34 @@ impl PlexClient {
             }
         }
         
-        // Find all Directory elements (shows) - they contain type="show"
+        // Find all Directory elements (shows) - type="show", or inferred from the GUID
         let mut start_pos = 0;
-        while let Some(dir_start) = xml[start_pos..].find("<Directory ") {
+        while let Some(dir_start) = find_opening_tag(&xml[start_pos..], "Directory") {
             let actual_start = start_pos + dir_start;
-            if let Some(end_pos) = xml[actual_start..].find(">") {
+            if let Some(end_pos) = tag_end(&xml[actual_start..]) {
                 let element = &xml[actual_start..actual_start + end_pos + 1];
                 
                 // Check if this is a show with the required attributes
-                if element.contains("type=\"show\"") && element.contains("title=") && element.contains("ratingKey=") {
+                if let Some(item_type) = self.extract_item_type(element) {
                     if let (Some(title), Some(rating_key)) = (self.extract_title(element), self.extract_rating_key(element)) {
                         let year = self.extract_year(element);
                         let guid = self.extract_guid(element);
+                        let labels = self.extract_child_tags(&xml[actual_start..], element, "Directory", &["Label", "Collection"]);
+                        let genres = self.extract_child_tags(&xml[actual_start..], element, "Directory", &["Genre"]);
+                        let original_title = attribute_value(element, "originalTitle").filter(|t| !t.trim().is_empty());
                         
                         let item = Item {
                             id: rating_key,
                             title: title.clone(),
                             year,
-                            item_type: ItemType::Show,
+                            item_type,
                             guid,
                             imdb_id: None,
                             tmdb_id: None,
                             tvdb_id: None,
+                            labels,
+                            original_title,
+                            genres,
                         };
                         
                         let watchlist_item = WatchlistItem {
@@ -118,11 +392,17 @@ impl PlexClient {
                             user_id: "self".to_string(),
                         };
                         
-                        info!("Found show: {} ({}) [Rating Key: {}]", 
-                              title, 
-                              year.map_or("Unknown".to_string(), |y| y.to_string()),
-                              &watchlist_item.item.id);
-                        items.push(watchlist_item);
+                        if !seen_rating_keys.insert(watchlist_item.item.id.clone()) {
+                            debug!("Ignoring repeated entry '{}' [Rating Key: {}] in the same response",
+                                   title, &watchlist_item.item.id);
+                        } else {
+                            info!("Found {}: {} ({}) [Rating Key: {}]", 
+                                  type_name(&watchlist_item.item.item_type),
+                                  title, 
+                                  year.map_or("Unknown".to_string(), |y| y.to_string()),
+                                  &watchlist_item.item.id);
+                            items.push(watchlist_item);
+                        }
                     }
                 }
                 start_pos = actual_start + end_pos + 1;
@@ -131,59 +411,1226 @@ impl PlexClient {
             }
         }
         
+        let items = merge_duplicate_items(items);
+
         info!("XML parsing completed: found {} total items", items.len());
         Ok(items)
     }
     
-    fn extract_title(&self, line: &str) -> Option<String> {
-        if let Some(start) = line.find("title=\"") {
-            let start = start + 7; // Skip 'title="'
-            if let Some(end) = line[start..].find('"') {
-                return Some(line[start..start + end].to_string());
+    /// The element's `type`, or when that is missing or not movie/show, a
+    /// type inferred from the GUID scheme
+    fn extract_item_type(&self, line: &str) -> Option<ItemType> {
+        match attribute_value(line, "type").as_deref() {
+            Some("movie") => Some(ItemType::Movie),
+            Some("show") => Some(ItemType::Show),
+            declared => {
+                let guid = self.extract_guid(line)?;
+                let inferred = infer_type_from_guid(&guid)?;
+                debug!("Inferred {} from GUID {} (type attribute: {:?})", type_name(&inferred), guid, declared);
+                Some(inferred)
             }
         }
-        None
+    }
+
+    fn extract_title(&self, line: &str) -> Option<String> {
+        attribute_value(line, "title")
     }
     
     fn extract_rating_key(&self, line: &str) -> Option<String> {
-        if let Some(start) = line.find("ratingKey=\"") {
-            let start = start + 11; // Skip 'ratingKey="'
-            if let Some(end) = line[start..].find('"') {
-                return Some(line[start..start + end].to_string());
-            }
-        }
-        None
+        attribute_value(line, "ratingKey")
     }
     
     fn extract_year(&self, line: &str) -> Option<i32> {
-        if let Some(start) = line.find("year=\"") {
-            let start = start + 6; // Skip 'year="'
-            if let Some(end) = line[start..].find('"') {
-                return line[start..start + end].parse().ok();
-            }
-        }
-        None
+        attribute_value(line, "year")?.trim().parse().ok()
     }
     
     fn extract_guid(&self, line: &str) -> Option<String> {
-        if let Some(start) = line.find("guid=\"") {
-            let start = start + 6; // Skip 'guid="'
-            if let Some(end) = line[start..].find('"') {
-                return Some(line[start..start + end].to_string());
+        attribute_value(line, "guid")
+    }
+
+    /// Collect the `tag` of each `children` element (e.g. `<Label tag="..."/>`)
+    /// of an item element. `from` starts at the element's opening tag.
+    fn extract_child_tags(&self, from: &str, opening_tag: &str, element_name: &str, children: &[&str]) -> Vec<String> {
+        if opening_tag.ends_with("/>") {
+            return Vec::new();
+        }
+
+        let closing_tag = format!("</{}>", element_name);
+        let body = match from.find(&closing_tag) {
+            Some(end) => &from[opening_tag.len()..end],
+            None => return Vec::new(),
+        };
+
+        let mut tags = Vec::new();
+        for child in children {
+            let mut pos = 0;
+            while let Some(child_start) = find_opening_tag(&body[pos..], child) {
+                let child_start = pos + child_start;
+                let Some(child_end) = tag_end(&body[child_start..]) else {
+                    break;
+                };
+                let child_element = &body[child_start..child_start + child_end + 1];
+                if let Some(tag) = self.extract_tag(child_element) {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                pos = child_start + child_end + 1;
             }
         }
-        None
+        tags
     }
 
+    fn extract_tag(&self, line: &str) -> Option<String> {
+        attribute_value(line, "tag")
+    }
+
+    /// External ids and genres for `rating_key` from its full metadata,
+    /// which carries the guids the watchlist listing leaves out
     #[instrument(skip(self))]
-    pub async fn get_friends_watchlists(&self) -> Result<Vec<WatchlistItem>> {
+    pub async fn get_item_metadata(&self, rating_key: &str) -> Result<ItemMetadata> {
+        if let Some(ids) = self.cached_metadata(rating_key) {
+            return Ok(ids);
+        }
+
+        let url = format!(
+            "{}/library/metadata/{}?X-Plex-Token={}",
+            self.metadata_api(),
+            urlencoding::encode(rating_key),
+            self.config.account_token
+        );
+        let xml = self.http.get(&url).await?.text_with_charset("utf-8").await?;
+        check_well_formed(&xml).context("Plex returned malformed item metadata")?;
+
+        let ids = parse_item_metadata(&xml);
+        self.metadata
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(rating_key.to_string(), ids.clone());
+        Ok(ids)
+    }
+
+    fn cached_metadata(&self, rating_key: &str) -> Option<ItemMetadata> {
+        self.metadata
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(rating_key)
+            .cloned()
+    }
+
+    /// Fill in external ids for items that have none, when `resolveMetadata`
+    /// is on. A failed lookup leaves the item as it was.
+    pub async fn resolve_metadata(&self, items: &mut [WatchlistItem]) {
+        if !self.config.resolve_metadata.unwrap_or(false) {
+            return;
+        }
+
+        let mut fetched = 0;
+        for watchlist_item in items.iter_mut() {
+            let item = &mut watchlist_item.item;
+            if item.imdb_id.is_some() || item.tmdb_id.is_some() || item.tvdb_id.is_some() {
+                continue;
+            }
+
+            let cached = self.cached_metadata(&item.id).is_some();
+            if !cached && fetched > 0 {
+                sleep(METADATA_REQUEST_INTERVAL).await;
+            }
+            match self.get_item_metadata(&item.id).await {
+                Ok(ids) => {
+                    debug!("Resolved ids for '{}': {:?}", item.title, ids);
+                    item.imdb_id = ids.imdb_id;
+                    item.tmdb_id = ids.tmdb_id;
+                    item.tvdb_id = ids.tvdb_id;
+                    if item.genres.is_empty() {
+                        item.genres = ids.genres;
+                    }
+                }
+                Err(e) => warn!("Could not fetch Plex metadata for '{}', continuing without ids: {}", item.title, e),
+            }
+            if !cached {
+                fetched += 1;
+            }
+        }
+
+        if fetched > 0 {
+            info!("Fetched Plex metadata for {} items", fetched);
+        }
+    }
+
+    /// Guids of every item in `pms_url`'s movie and show libraries: the
+    /// `plex://` guid plus any `imdb://`, `tmdb://` and `tvdb://` ids
+    #[instrument(skip(self))]
+    pub async fn get_library_guids(&self, pms_url: &str) -> Result<HashSet<String>> {
+        let pms_url = pms_url.trim_end_matches('/');
+        let token = self.config.server_token.as_deref().unwrap_or(&self.config.account_token);
+
+        let sections_url = format!("{}/library/sections?X-Plex-Token={}", pms_url, token);
+        let xml = self.http.get(&sections_url).await?.text_with_charset("utf-8").await?;
+        check_well_formed(&xml).context("Plex Media Server returned a malformed library list")?;
+
+        let sections: Vec<String> = opening_tags(&xml, "Directory")
+            .filter(|section| matches!(attribute_value(section, "type").as_deref(), Some("movie" | "show")))
+            .filter_map(|section| attribute_value(section, "key"))
+            .collect();
+
+        let mut guids = HashSet::new();
+        for section in &sections {
+            let mut start = 0;
+            loop {
+                let url = format!(
+                    "{}/library/sections/{}/all?includeGuids=1&X-Plex-Container-Start={}&X-Plex-Container-Size={}&X-Plex-Token={}",
+                    pms_url, urlencoding::encode(section), start, LIBRARY_PAGE_SIZE, token
+                );
+                let xml = self.http.get(&url).await?.text_with_charset("utf-8").await?;
+                check_well_formed(&xml).context("Plex Media Server returned a malformed library page")?;
+
+                let mut page_items = 0;
+                for element in opening_tags(&xml, "Video").chain(opening_tags(&xml, "Directory")) {
+                    page_items += 1;
+                    guids.extend(attribute_value(element, "guid"));
+                }
+                guids.extend(opening_tags(&xml, "Guid").filter_map(|element| attribute_value(element, "id")));
+
+                if page_items < LIBRARY_PAGE_SIZE {
+                    break;
+                }
+                start += LIBRARY_PAGE_SIZE;
+            }
+        }
+
+        info!("Read {} guids from {} Plex libraries", guids.len(), sections.len());
+        Ok(guids)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_friends_watchlists(&self) -> Result<Watchlists> {
         if self.config.skip_friend_sync.unwrap_or(false) {
             debug!("Skipping friends sync as configured");
-            return Ok(Vec::new());
+            return Ok(Watchlists::default());
         }
 
         info!("Fetching friends' watchlists");
-        warn!("Friends watchlist sync not yet implemented");
-        Ok(Vec::new())
+
+        let mut friends = self.filter_friends(self.get_friends_cached().await?);
+
+        // Deterministic order so a cap always selects the same friends
+        friends.sort_by(|a, b| a.id.cmp(&b.id));
+
+        if let Some(max_friends) = self.config.max_friends {
+            if friends.len() > max_friends {
+                warn!("Found {} friends, only syncing the first {} (maxFriends)", friends.len(), max_friends);
+                friends.truncate(max_friends);
+            }
+        }
+
+        let concurrency = self.config.friends_concurrency.unwrap_or(DEFAULT_FRIENDS_CONCURRENCY).max(1);
+        let fetches: Vec<_> = friends
+            .iter()
+            .map(|friend| async move { (friend, self.get_friend_watchlist(friend).await) })
+            .collect();
+        let mut fetches = stream::iter(fetches).buffered(concurrency);
+
+        let mut watchlists = Watchlists::default();
+        while let Some((friend, result)) = fetches.next().await {
+            match result {
+                Ok(friend_items) => {
+                    debug!("Retrieved {} watchlist items for friend {}", friend_items.len(), friend.username);
+                    watchlists.items.extend(friend_items);
+                }
+                Err(e) => {
+                    warn!("Failed to fetch watchlist for friend {}: {}", friend.username, e);
+                    watchlists.failed.push(friend.username.clone());
+                }
+            }
+        }
+
+        info!("Retrieved {} items from {} friends' watchlists", watchlists.items.len(), friends.len());
+        Ok(watchlists)
+    }
+
+    /// Apply `friendsAllowlist` and `friendsBlocklist`; the blocklist wins
+    /// for a friend on both
+    fn filter_friends(&self, mut friends: Vec<PlexFriend>) -> Vec<PlexFriend> {
+        let listed = |list: &[String], friend: &PlexFriend| {
+            list.iter().any(|entry| entry == &friend.id || entry.eq_ignore_ascii_case(&friend.username))
+        };
+
+        if let Some(ref allowlist) = self.config.friends_allowlist {
+            for entry in allowlist {
+                if !friends.iter().any(|f| listed(std::slice::from_ref(entry), f)) {
+                    warn!("Friend '{}' from friendsAllowlist is not among your Plex friends", entry);
+                }
+            }
+            friends.retain(|f| listed(allowlist, f));
+        }
+        if let Some(ref blocklist) = self.config.friends_blocklist {
+            friends.retain(|f| !listed(blocklist, f));
+        }
+        friends
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_friends(&self) -> Result<Vec<PlexFriend>> {
+        let data: AllFriendsData = self.graphql(ALL_FRIENDS_QUERY, serde_json::json!({})).await?;
+        Ok(data.all_friends.into_iter().map(|f| f.user).collect())
+    }
+
+    /// The friends list from the cache while it is fresh, otherwise from Plex
+    async fn get_friends_cached(&self) -> Result<Vec<PlexFriend>> {
+        let ttl = self.config.friends_cache_seconds.map_or(DEFAULT_FRIENDS_CACHE, Duration::from_secs);
+        let Some(cache) = self.friends_cache.as_ref().filter(|_| !ttl.is_zero()) else {
+            return self.get_friends().await;
+        };
+
+        if let Some(friends) = cache.friends_within(ttl) {
+            debug!("Using the cached list of {} friends", friends.len());
+            return Ok(friends);
+        }
+        let friends = self.get_friends().await?;
+        cache.record_friends(friends.clone());
+        Ok(friends)
+    }
+
+    #[instrument(skip(self, friend), fields(friend = %friend.username))]
+    async fn get_friend_watchlist(&self, friend: &PlexFriend) -> Result<Vec<WatchlistItem>> {
+        let mut items = Vec::new();
+        let mut after: Option<String> = None;
+
+        loop {
+            let variables = serde_json::json!({
+                "uuid": friend.id,
+                "first": FRIEND_WATCHLIST_PAGE_SIZE,
+                "after": after,
+            });
+            let data: WatchlistHubData = self.graphql(FRIEND_WATCHLIST_QUERY, variables).await?;
+            let page = data.user.watchlist;
+
+            items.extend(page.nodes.into_iter().filter_map(|node| node_to_watchlist_item(node, &friend.username)));
+
+            match page.page_info.end_cursor {
+                Some(cursor) if page.page_info.has_next_page => after = Some(cursor),
+                _ => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn graphql<T: serde::de::DeserializeOwned>(&self, query: &str, variables: serde_json::Value) -> Result<T> {
+        let url = format!("{}?X-Plex-Token={}", self.community_api(), self.config.account_token);
+        let request = GraphQlRequest { query, variables };
+
+        let response: GraphQlResponse<T> = self.http.post_json_read(&url, &request).await?;
+
+        if let Some(errors) = response.errors.filter(|e| !e.is_empty()) {
+            return Err(anyhow::anyhow!("Plex GraphQL request failed: {:?}", errors));
+        }
+
+        response
+            .data
+            .ok_or_else(|| anyhow::anyhow!("Plex GraphQL response contained no data"))
+    }
+}
+
+/// Only schemes that identify the type unambiguously: TVDB ids in Plex GUIDs
+/// are series ids, and Plex/TMDB GUIDs carry the type in their path
+fn infer_type_from_guid(guid: &str) -> Option<ItemType> {
+    let guid = guid.to_ascii_lowercase();
+    if guid.starts_with("tvdb://") || guid.starts_with("plex://show/") || guid.starts_with("tmdb://tv/") {
+        Some(ItemType::Show)
+    } else if guid.starts_with("plex://movie/") || guid.starts_with("tmdb://movie/") {
+        Some(ItemType::Movie)
+    } else {
+        None
+    }
+}
+
+fn type_name(item_type: &ItemType) -> &'static str {
+    match item_type {
+        ItemType::Movie => "movie",
+        ItemType::Show => "show",
+    }
+}
+
+/// Walk the whole document once, failing on mismatched or unclosed tags,
+/// a missing root element or trailing garbage.
+fn check_well_formed(xml: &str) -> Result<()> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    let mut reader = Reader::from_str(xml);
+    let mut depth = 0usize;
+    let mut seen_root = false;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| anyhow::anyhow!("XML error at byte {}: {}", reader.error_position(), e))?;
+        match event {
+            Event::Start(_) => {
+                if depth == 0 && seen_root {
+                    bail!("more than one root element at byte {}", reader.buffer_position());
+                }
+                depth += 1;
+                seen_root = true;
+            }
+            Event::End(_) => depth -= 1,
+            Event::Empty(_) => {
+                if depth == 0 && seen_root {
+                    bail!("more than one root element at byte {}", reader.buffer_position());
+                }
+                seen_root = true;
+            }
+            Event::Text(text) if depth == 0 && !text.iter().all(u8::is_ascii_whitespace) => {
+                bail!("unexpected text outside the root element at byte {}", reader.buffer_position());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !seen_root {
+        bail!("response contains no XML element");
+    }
+    if depth != 0 {
+        bail!("response ended with {} unclosed element(s); it was probably truncated", depth);
+    }
+    Ok(())
+}
+
+/// Read attribute `name` from a single opening tag. Only whole attribute
+/// names match (so `title` never picks up `originalTitle`), either quote
+/// style is accepted and entities in the value are decoded. Returns `None`
+/// for a missing or unterminated attribute.
+fn attribute_value(element: &str, name: &str) -> Option<String> {
+    let mut pos = 0;
+    while let Some(found) = element[pos..].find(name) {
+        let start = pos + found;
+        pos = start + name.len();
+
+        let at_boundary = element[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_whitespace);
+        if !at_boundary {
+            continue;
+        }
+
+        let rest = element[pos..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let quote = match rest.chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => continue,
+        };
+        let value = &rest[1..];
+        let end = value.find(quote)?;
+        return Some(decode_entities(&value[..end]));
+    }
+    None
+}
+
+/// Decode the predefined XML entities and numeric character references.
+/// Anything unrecognised is kept as-is rather than dropped.
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let replacement = rest.find(';').and_then(|semi| {
+            let entity = &rest[1..semi];
+            let ch = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => {
+                    let code = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                        u32::from_str_radix(hex, 16).ok()
+                    } else if let Some(dec) = entity.strip_prefix('#') {
+                        dec.parse().ok()
+                    } else {
+                        None
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            ch.map(|c| (c, semi + 1))
+        });
+
+        match replacement {
+            Some((c, consumed)) => {
+                decoded.push(c);
+                rest = &rest[consumed..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Offset of the `>` closing the tag that `from` starts with. A `>` inside
+/// a quoted attribute value is legal XML and doesn't end the tag.
+fn tag_end(from: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in from.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Offset of the first `<name ...>` opening tag in `xml`. Attributes may
+/// start on the next line, so any whitespace after the name counts.
+fn find_opening_tag(xml: &str, name: &str) -> Option<usize> {
+    let needle = format!("<{}", name);
+    let mut pos = 0;
+    while let Some(found) = xml[pos..].find(&needle) {
+        let start = pos + found;
+        pos = start + needle.len();
+        if xml[pos..].starts_with(char::is_whitespace) {
+            return Some(start);
+        }
+    }
+    None
+}
+
+/// The client identifier stored at `path`, generating and saving one on
+/// first use. If it can't be saved, this run's identifier is still used.
+pub fn load_client_identifier(path: &Path) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(identifier) if !identifier.trim().is_empty() => return identifier.trim().to_string(),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Could not read Plex client identifier from {}: {}", path.display(), e),
+    }
+
+    let identifier = uuid::Uuid::new_v4().to_string();
+    match std::fs::write(path, &identifier) {
+        Ok(()) => info!("Generated Plex client identifier, saved to {}", path.display()),
+        Err(e) => warn!("Could not save Plex client identifier to {}, a new one will be generated next run: {}",
+                        path.display(), e),
+    }
+    identifier
+}
+
+/// Opening tags of `<name ...>` elements, in document order
+fn opening_tags<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let start = pos + find_opening_tag(&xml[pos..], name)?;
+        let end = tag_end(&xml[start..])?;
+        pos = start + end + 1;
+        Some(&xml[start..pos])
+    })
+}
+
+/// Whether `item` matches one of the library `guids`. TMDB ids aren't
+/// compared, TMDB numbers movies and shows separately.
+pub fn is_owned(item: &Item, guids: &HashSet<String>) -> bool {
+    item.guid.as_ref().is_some_and(|guid| guids.contains(guid))
+        || item.imdb_id.as_ref().is_some_and(|id| guids.contains(&format!("imdb://{}", id)))
+        || item.tvdb_id.is_some_and(|id| guids.contains(&format!("tvdb://{}", id)))
+}
+
+/// Read `imdb://`, `tmdb://` and `tvdb://` ids from `<Guid id="..."/>`
+/// elements, and genres from `<Genre tag="..."/>`
+fn parse_item_metadata(xml: &str) -> ItemMetadata {
+    let mut ids = ItemMetadata::default();
+
+    for element in opening_tags(xml, "Guid") {
+        let Some(id) = attribute_value(element, "id") else {
+            continue;
+        };
+        if let Some(imdb_id) = id.strip_prefix("imdb://") {
+            ids.imdb_id.get_or_insert_with(|| imdb_id.to_string());
+        } else if let Some(tmdb_id) = id.strip_prefix("tmdb://") {
+            ids.tmdb_id = ids.tmdb_id.or(tmdb_id.parse().ok().filter(|&id: &i32| id > 0));
+        } else if let Some(tvdb_id) = id.strip_prefix("tvdb://") {
+            ids.tvdb_id = ids.tvdb_id.or(tvdb_id.parse().ok().filter(|&id: &i32| id > 0));
+        }
+    }
+
+    for genre in opening_tags(xml, "Genre").filter_map(|element| attribute_value(element, "tag")) {
+        if !ids.genres.contains(&genre) {
+            ids.genres.push(genre);
+        }
+    }
+
+    ids
+}
+
+fn node_to_watchlist_item(node: WatchlistNode, username: &str) -> Option<WatchlistItem> {
+    let (item_type, guid_kind) = match node.item_type.to_uppercase().as_str() {
+        "MOVIE" => (ItemType::Movie, "movie"),
+        "SHOW" => (ItemType::Show, "show"),
+        other => {
+            debug!("Ignoring friend watchlist entry '{}' of type {}", node.title, other);
+            return None;
+        }
+    };
+
+    Some(WatchlistItem {
+        item: Item {
+            guid: Some(format!("plex://{}/{}", guid_kind, node.id)),
+            id: node.id,
+            title: node.title,
+            year: node.year,
+            item_type,
+            imdb_id: None,
+            tmdb_id: None,
+            tvdb_id: None,
+            labels: Vec::new(),
+            original_title: None,
+            genres: Vec::new(),
+        },
+        added_at: chrono::Utc::now(),
+        user_id: username.to_string(),
+    })
+}
+
+/// Keys that identify the same underlying title regardless of Plex edition/version
+fn identity_keys(item: &Item) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let Some(ref guid) = item.guid {
+        keys.push(format!("guid:{}", guid));
+    }
+    if let Some(ref imdb_id) = item.imdb_id {
+        keys.push(format!("imdb:{}", imdb_id));
+    }
+    if let Some(tmdb_id) = item.tmdb_id {
+        keys.push(format!("tmdb:{:?}:{}", item.item_type, tmdb_id));
+    }
+    if let Some(tvdb_id) = item.tvdb_id {
+        keys.push(format!("tvdb:{}", tvdb_id));
+    }
+    keys
+}
+
+/// Collapse entries that share a guid or external id (e.g. two editions of the
+/// same film with distinct rating keys, or the same title on several accounts'
+/// watchlists), filling gaps from the later duplicates
+pub fn merge_duplicate_items(items: Vec<WatchlistItem>) -> Vec<WatchlistItem> {
+    let mut merged: Vec<WatchlistItem> = Vec::with_capacity(items.len());
+    let mut index_by_key: HashMap<String, usize> = HashMap::new();
+
+    for watchlist_item in items {
+        let keys = identity_keys(&watchlist_item.item);
+        let existing = keys.iter().find_map(|k| index_by_key.get(k).copied());
+
+        let index = match existing {
+            Some(index) => {
+                let target = &mut merged[index].item;
+                let source = watchlist_item.item;
+                debug!("Merging duplicate watchlist entry '{}' [Rating Key: {}] into [Rating Key: {}]",
+                       source.title, source.id, target.id);
+                target.year = target.year.or(source.year);
+                target.guid = target.guid.take().or(source.guid);
+                target.imdb_id = target.imdb_id.take().or(source.imdb_id);
+                target.tmdb_id = target.tmdb_id.or(source.tmdb_id);
+                target.tvdb_id = target.tvdb_id.or(source.tvdb_id);
+                target.original_title = target.original_title.take().or(source.original_title);
+                if target.genres.is_empty() {
+                    target.genres = source.genres;
+                }
+                index
+            }
+            None => {
+                merged.push(watchlist_item);
+                merged.len() - 1
+            }
+        };
+
+        for key in identity_keys(&merged[index].item) {
+            index_by_key.entry(key).or_insert(index);
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PlexAccountConfig;
+    use crate::test_support;
+    use proptest::prelude::*;
+    use std::path::PathBuf;
+    use std::sync::{Arc, LazyLock};
+
+    /// Escape `value` for an attribute quoted with `quote`, as an XML writer would
+    fn escape(value: &str, quote: char) -> String {
+        let mut escaped = String::new();
+        for c in value.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                c if c == quote => escaped.push_str(if c == '"' { "&quot;" } else { "&apos;" }),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    /// Built once; a reqwest client is too slow to build for every proptest case
+    fn client() -> &'static PlexClient {
+        static CLIENT: LazyLock<PlexClient> = LazyLock::new(|| {
+            PlexClient::new(test_support::http_client(), test_support::plex_config("http://127.0.0.1:1", "http://127.0.0.1:1"))
+        });
+        &CLIENT
+    }
+
+    proptest! {
+        #[test]
+        fn attribute_value_round_trips_any_escaped_value(
+            value in any::<String>(),
+            quote in prop::sample::select(vec!['"', '\'']),
+            other in "[a-zA-Z]{0,12}",
+        ) {
+            let element = format!(r#"<Video originalTitle="{}" title={q}{}{q} ratingKey="1"/>"#,
+                                  escape(&other, '"'), escape(&value, quote), q = quote);
+
+            prop_assert_eq!(attribute_value(&element, "title"), Some(value));
+            prop_assert_eq!(attribute_value(&element, "originalTitle"), Some(other));
+            prop_assert_eq!(attribute_value(&element, "guid"), None);
+        }
+
+        #[test]
+        fn attribute_value_never_panics(element in any::<String>(), name in "[a-zA-Z]{1,12}") {
+            let _ = attribute_value(&element, &name);
+            let _ = tag_end(&element);
+        }
+
+        #[test]
+        fn decode_entities_never_panics_and_keeps_plain_text(value in any::<String>()) {
+            let decoded = decode_entities(&value);
+            if !value.contains('&') {
+                prop_assert_eq!(decoded, value);
+            }
+        }
+
+        #[test]
+        fn extractors_read_back_generated_attributes(
+            title in any::<String>(),
+            rating_key in "[0-9a-f]{1,24}",
+            year in any::<i32>(),
+            guid in "plex://(movie|show)/[0-9a-f]{1,24}",
+        ) {
+            let client = client();
+            let element = format!(r#"<Video ratingKey="{}" title="{}" year="{}" guid="{}">"#,
+                                  rating_key, escape(&title, '"'), year, guid);
+
+            prop_assert_eq!(client.extract_title(&element), Some(title));
+            prop_assert_eq!(client.extract_rating_key(&element), Some(rating_key));
+            prop_assert_eq!(client.extract_year(&element), Some(year));
+            prop_assert_eq!(client.extract_guid(&element), Some(guid));
+            prop_assert_eq!(tag_end(&element), Some(element.len() - 1));
+        }
+
+        #[test]
+        fn extractors_return_none_for_missing_or_unterminated_attributes(value in "[^\"]*") {
+            let client = client();
+            let unterminated = format!(r#"<Video title="{}"#, value);
+            let missing = format!(r#"<Video subtitle="{}" ratingKeys="1" years="2000">"#, escape(&value, '"'));
+
+            prop_assert_eq!(client.extract_title(&unterminated), None);
+            prop_assert_eq!(client.extract_title(&missing), None);
+            prop_assert_eq!(client.extract_rating_key(&missing), None);
+            prop_assert_eq!(client.extract_year(&missing), None);
+        }
+    }
+
+    /// Each `tests/fixtures/plex/<name>.xml` is a watchlist response that once
+    /// tripped the parser, and `<name>.json` the items it has to come out as
+    #[test]
+    fn watchlist_fixtures_parse_to_their_expected_items() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/plex");
+        let client = client();
+        let mut checked = 0;
+
+        for entry in std::fs::read_dir(&fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "xml") {
+                continue;
+            }
+            let xml = std::fs::read_to_string(&path).unwrap();
+            let expected: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+
+            let items: Vec<serde_json::Value> = client
+                .parse_xml_watchlist(&xml)
+                .unwrap()
+                .into_iter()
+                .map(|w| {
+                    let mut item = serde_json::json!({
+                        "id": w.item.id,
+                        "title": w.item.title,
+                        "year": w.item.year,
+                        "type": w.item.item_type,
+                    });
+                    if !w.item.labels.is_empty() {
+                        item["labels"] = serde_json::json!(w.item.labels);
+                    }
+                    if !w.item.genres.is_empty() {
+                        item["genres"] = serde_json::json!(w.item.genres);
+                    }
+                    item
+                })
+                .collect();
+
+            assert_eq!(serde_json::Value::Array(items), expected, "{}", path.display());
+            checked += 1;
+        }
+
+        assert!(checked > 0, "no fixtures in {}", fixtures.display());
+    }
+
+    fn watchlist_item(item: Item) -> WatchlistItem {
+        WatchlistItem { item, added_at: chrono::Utc::now(), user_id: "self".to_string() }
+    }
+
+    #[tokio::test]
+    async fn two_editions_of_a_movie_are_merged_into_one_item() {
+        let xml = r#"<MediaContainer size="2">
+            <Video ratingKey="101" type="movie" title="Blade Runner" guid="plex://movie/5d7768" />
+            <Video ratingKey="102" type="movie" title="Blade Runner" year="1982" guid="plex://movie/5d7768" />
+        </MediaContainer>"#;
+        let metadata_url = test_support::serve(test_support::plex_metadata(xml.to_string())).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let items = client.get_watchlist().await.unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].item.id, "101");
+        assert_eq!(items[0].item.year, Some(1982));
+    }
+
+    #[test]
+    fn duplicates_sharing_only_an_external_id_keep_the_richest_metadata() {
+        let mut theatrical = test_support::item(ItemType::Movie, "Aliens", 1986);
+        theatrical.imdb_id = Some("tt0090605".to_string());
+        let mut special = test_support::item(ItemType::Movie, "Aliens", 1986);
+        special.id = "special".to_string();
+        special.imdb_id = Some("tt0090605".to_string());
+        special.tmdb_id = Some(679);
+        let other = test_support::item(ItemType::Movie, "Alien", 1979);
+
+        let merged = merge_duplicate_items(vec![
+            watchlist_item(theatrical),
+            watchlist_item(special),
+            watchlist_item(other),
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].item.tmdb_id, Some(679));
+        assert_eq!(merged[1].item.title, "Alien");
+    }
+
+    #[test]
+    fn movies_only_watchlist_requests_type_1_and_the_configured_sort() {
+        let mut config = test_support::plex_config("http://plex.test", "http://community.test");
+        config.watchlist_sort = Some("watchlistedAt:desc".to_string());
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        assert_eq!(
+            client.watchlist_url(Some(&ItemType::Movie)),
+            "http://plex.test/library/sections/watchlist/all?X-Plex-Token=test-token&type=1&sort=watchlistedAt%3Adesc"
+        );
+    }
+
+    #[test]
+    fn unfiltered_watchlist_requests_no_type() {
+        let client = PlexClient::new(test_support::http_client(), test_support::plex_config("http://plex.test", "http://community.test"));
+
+        assert_eq!(
+            client.watchlist_url(None),
+            "http://plex.test/library/sections/watchlist/all?X-Plex-Token=test-token"
+        );
+    }
+
+    #[tokio::test]
+    async fn max_friends_caps_the_friend_watchlist_fetches() {
+        let (router, queries) = test_support::plex_community(&[("c", "carol"), ("a", "alice"), ("b", "bob")], &[]);
+        let community_url = test_support::serve(router).await;
+        let mut config = test_support::plex_config(&test_support::unreachable_url().await, &community_url);
+        config.max_friends = Some(2);
+        let client = PlexClient::new(test_support::http_client(), config);
+        let logs = test_support::Logs::default();
+        let _guard = logs.capture();
+
+        let items = client.get_friends_watchlists().await.unwrap().items;
+
+        assert_eq!(*queries.lock().unwrap(), ["friends", "a", "b"]);
+        let users: Vec<&str> = items.iter().map(|i| i.user_id.as_str()).collect();
+        assert_eq!(users, ["alice", "bob"]);
+        assert!(logs.lines()[0].contains("Found 3 friends, only syncing the first 2 (maxFriends)"));
+    }
+
+    #[tokio::test]
+    async fn friends_allowlist_keeps_only_listed_friends_and_warns_about_missing_ones() {
+        let (router, queries) = test_support::plex_community(&[("a", "alice"), ("b", "bob"), ("c", "carol")], &[]);
+        let community_url = test_support::serve(router).await;
+        let mut config = test_support::plex_config(&test_support::unreachable_url().await, &community_url);
+        config.friends_allowlist = Some(vec!["Alice".to_string(), "c".to_string(), "dave".to_string()]);
+        let client = PlexClient::new(test_support::http_client(), config);
+        let logs = test_support::Logs::default();
+        let _guard = logs.capture();
+
+        client.get_friends_watchlists().await.unwrap();
+
+        assert_eq!(*queries.lock().unwrap(), ["friends", "a", "c"]);
+        let warnings = logs.lines();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("Friend 'dave' from friendsAllowlist is not among your Plex friends"));
+    }
+
+    #[tokio::test]
+    async fn friends_blocklist_drops_listed_friends_even_when_allowlisted() {
+        let (router, queries) = test_support::plex_community(&[("a", "alice"), ("b", "bob"), ("c", "carol")], &[]);
+        let community_url = test_support::serve(router).await;
+        let mut config = test_support::plex_config(&test_support::unreachable_url().await, &community_url);
+        config.friends_allowlist = Some(vec!["alice".to_string(), "bob".to_string()]);
+        config.friends_blocklist = Some(vec!["b".to_string(), "carol".to_string()]);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        client.get_friends_watchlists().await.unwrap();
+
+        assert_eq!(*queries.lock().unwrap(), ["friends", "a"]);
+    }
+
+    #[tokio::test]
+    async fn friends_query_goes_through_in_read_only_mode() {
+        let (community, _) = test_support::plex_community(&[("f1", "alice")], &[]);
+        let community_url = test_support::serve(community).await;
+        let http = test_support::http_client().with_read_only(true);
+        let client = PlexClient::new(http, test_support::plex_config(&test_support::unreachable_url().await, &community_url));
+
+        let items = client.get_friends_watchlists().await.unwrap().items;
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].user_id, "alice");
+    }
+
+    #[tokio::test]
+    async fn bom_prefixed_xml_parses() {
+        let xml = format!("\u{feff}{}", test_support::watchlist_xml(&[("1", "Heat", 1995)]));
+        let metadata_url = test_support::serve(test_support::plex_metadata(xml)).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let items = client.get_watchlist().await.unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].item.title, "Heat");
+    }
+
+    #[tokio::test]
+    async fn original_titles_are_parsed_from_the_watchlist() {
+        let xml = r#"<MediaContainer size="2">
+            <Video ratingKey="1" type="movie" title="Amelie" originalTitle="Le Fabuleux Destin d&apos;Amélie Poulain" year="2001" guid="plex://movie/1" />
+            <Video ratingKey="2" type="movie" title="Heat" originalTitle="" year="1995" guid="plex://movie/2" />
+        </MediaContainer>"#;
+        let metadata_url = test_support::serve(test_support::plex_metadata(xml.to_string())).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let items = client.get_watchlist().await.unwrap();
+
+        assert_eq!(items[0].item.original_title.as_deref(), Some("Le Fabuleux Destin d'Amélie Poulain"));
+        assert_eq!(items[1].item.original_title, None);
+    }
+
+    #[tokio::test]
+    async fn library_guids_are_read_page_by_page_from_video_libraries() {
+        let guids: Vec<String> = (0..LIBRARY_PAGE_SIZE + 1).map(|i| format!("plex://movie/{}", i)).collect();
+        let server_url = test_support::serve(test_support::plex_server(guids)).await;
+        let config = test_support::plex_config(&test_support::unreachable_url().await, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let library = client.get_library_guids(&server_url).await.unwrap();
+
+        assert_eq!(library.len(), LIBRARY_PAGE_SIZE + 1);
+        assert!(library.contains(&format!("plex://movie/{}", LIBRARY_PAGE_SIZE)));
+    }
+
+    #[tokio::test]
+    async fn xml_is_decoded_with_the_content_type_charset() {
+        // "Amélie" in ISO-8859-1, where é is the single byte 0xE9
+        let mut body = br#"<MediaContainer size="1"><Video ratingKey="1" type="movie" title="Am"#.to_vec();
+        body.push(0xE9);
+        body.extend_from_slice(br#"lie" year="2001" guid="plex://movie/1"/></MediaContainer>"#);
+        let router = axum::Router::new().route(
+            "/library/sections/watchlist/all",
+            axum::routing::get(move || async move { ([("content-type", "text/xml; charset=ISO-8859-1")], body) }),
+        );
+        let metadata_url = test_support::serve(router).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let items = client.get_watchlist().await.unwrap();
+
+        assert_eq!(items[0].item.title, "Amélie");
+    }
+
+    #[tokio::test]
+    async fn labels_and_collections_are_parsed_from_child_elements() {
+        let xml = r#"<MediaContainer size="2">
+            <Directory ratingKey="1" type="show" title="Bluey" year="2018" guid="plex://show/1">
+                <Label tag="Kids"/><Collection tag="Ludo Studio"/><Label tag="Kids"/>
+            </Directory>
+            <Video ratingKey="2" type="movie" title="Heat" year="1995" guid="plex://movie/2"/>
+        </MediaContainer>"#;
+        let metadata_url = test_support::serve(test_support::plex_metadata(xml.to_string())).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let items = client.get_watchlist().await.unwrap();
+
+        let labels = |title: &str| items.iter().find(|i| i.item.title == title).unwrap().item.labels.clone();
+        assert_eq!(labels("Bluey"), ["Kids", "Ludo Studio"]);
+        assert!(labels("Heat").is_empty());
+    }
+
+    #[tokio::test]
+    async fn watchlist_uses_the_account_token_not_the_server_token() {
+        let tokens = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&tokens);
+        let xml = test_support::watchlist_xml(&[]);
+        let metadata = axum::Router::new().route(
+            "/library/sections/watchlist/all",
+            axum::routing::get(move |axum::extract::Query(query): axum::extract::Query<HashMap<String, String>>| async move {
+                recorded.lock().unwrap().push(query["X-Plex-Token"].clone());
+                xml
+            }),
+        );
+        let mut config = test_support::plex_config(
+            &test_support::serve(metadata).await,
+            &test_support::unreachable_url().await,
+        );
+        config.account_token = "account-token".to_string();
+        config.server_token = Some("server-token".to_string());
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        client.get_watchlist().await.unwrap();
+
+        assert_eq!(*tokens.lock().unwrap(), ["account-token"]);
+    }
+
+    /// Owner watchlist plus "partner" (shares Heat) and "kid" (token rejected)
+    async fn household_client() -> PlexClient {
+        let metadata = test_support::plex_metadata_by_token(&[
+            ("test-token", test_support::watchlist_xml(&[("1", "Heat", 1995), ("2", "Ronin", 1998)])),
+            ("partner-token", test_support::watchlist_xml(&[("1", "Heat", 1995), ("3", "Collateral", 2004)])),
+        ]);
+        let mut config = test_support::plex_config(&test_support::serve(metadata).await, &test_support::unreachable_url().await);
+        config.accounts = Some(vec![
+            PlexAccountConfig { label: "partner".to_string(), token: "partner-token".to_string() },
+            PlexAccountConfig { label: "kid".to_string(), token: "expired-token".to_string() },
+        ]);
+        PlexClient::new(test_support::http_client(), config)
+    }
+
+    #[tokio::test]
+    async fn accounts_are_merged_deduped_and_labelled() {
+        let client = household_client().await;
+        let logs = test_support::Logs::default();
+        let _guard = logs.capture();
+
+        let watchlists = client.get_all_accounts_watchlist(None).await.unwrap();
+        let items = merge_duplicate_items(watchlists.items);
+
+        let owners: Vec<(&str, &str)> = items.iter().map(|i| (i.item.title.as_str(), i.user_id.as_str())).collect();
+        assert_eq!(owners, [("Heat", "self"), ("Ronin", "self"), ("Collateral", "partner")]);
+        let skipped = logs.lines().iter().filter(|l| l.contains("skipping it this sync")).count();
+        assert_eq!(skipped, 1);
+        assert!(logs.lines().last().unwrap().contains("Failed to fetch watchlist for account kid"));
+        assert_eq!(watchlists.failed, ["kid"]);
+    }
+
+    #[tokio::test]
+    async fn truncated_watchlists_fail_instead_of_returning_partial_items() {
+        let truncated = r#"<MediaContainer size="2">
+            <Video ratingKey="1" type="movie" title="Heat" year="1995" guid="plex://movie/1" />
+            <Video ratingKey="2" type="movie" title="Ronin" year="1998""#;
+        let metadata_url = test_support::serve(test_support::plex_metadata(truncated.to_string())).await;
+        let config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+        let client = PlexClient::new(test_support::http_client(), config);
+
+        let err = client.get_watchlist().await.unwrap_err();
+
+        assert!(format!("{:#}", err).contains("malformed watchlist response"), "{:#}", err);
+    }
+
+    #[test]
+    fn only_complete_single_root_documents_are_well_formed() {
+        assert!(check_well_formed(r#"<MediaContainer><Video title="Heat"/></MediaContainer>"#).is_ok());
+        assert!(check_well_formed(r#"<MediaContainer><Video title="Heat"/>"#).is_err());
+        assert!(check_well_formed("<MediaContainer></Directory>").is_err());
+        assert!(check_well_formed("<MediaContainer/><MediaContainer/>").is_err());
+        assert!(check_well_formed("<MediaContainer/> trailing").is_err());
+        assert!(check_well_formed("").is_err());
+    }
+
+    #[test]
+    fn untyped_elements_take_their_type_from_the_guid() {
+        let xml = r#"<MediaContainer>
+            <Video title="Heat" ratingKey="1" year="1995" guid="plex://movie/5d776825880197001ec967c6"/>
+            <Directory title="The Wire" ratingKey="2" type="unknown" guid="tvdb://79126"/>
+            <Video title="Home Video" ratingKey="3" guid="local://3"/>
+        </MediaContainer>"#;
+
+        let items = client().parse_xml_watchlist(xml).unwrap();
+
+        let parsed: Vec<(&str, &ItemType)> = items.iter().map(|w| (w.item.title.as_str(), &w.item.item_type)).collect();
+        assert_eq!(parsed, [("Heat", &ItemType::Movie), ("The Wire", &ItemType::Show)]);
+    }
+
+    #[tokio::test]
+    async fn resolve_metadata_fills_in_ids_for_items_without_any() {
+        let fetched: Arc<Mutex<Vec<String>>> = Arc::default();
+        let recorded = Arc::clone(&fetched);
+        let metadata = axum::Router::new().route(
+            "/library/metadata/{key}",
+            axum::routing::get(move |axum::extract::Path(key): axum::extract::Path<String>| async move {
+                recorded.lock().unwrap().push(key);
+                r#"<MediaContainer><Video ratingKey="Heat-1995" type="movie">
+                    <Guid id="imdb://tt0113277"/><Guid id="tmdb://949"/><Guid id="tvdb://1234"/>
+                </Video></MediaContainer>"#
+            }),
+        );
+        let mut config = test_support::plex_config(&test_support::serve(metadata).await, &test_support::unreachable_url().await);
+        config.resolve_metadata = Some(true);
+        let client = PlexClient::new(test_support::http_client(), config);
+        let watchlist_item = |item: Item| WatchlistItem { item, added_at: chrono::Utc::now(), user_id: "self".to_string() };
+        let mut known = test_support::item(ItemType::Movie, "Ronin", 1998);
+        known.tmdb_id = Some(8195);
+        let mut items = vec![watchlist_item(test_support::item(ItemType::Movie, "Heat", 1995)), watchlist_item(known)];
+
+        client.resolve_metadata(&mut items).await;
+        client.resolve_metadata(&mut items[..1]).await;
+
+        let heat = &items[0].item;
+        assert_eq!((heat.imdb_id.as_deref(), heat.tmdb_id, heat.tvdb_id), (Some("tt0113277"), Some(949), Some(1234)));
+        assert_eq!((items[1].item.imdb_id.as_deref(), items[1].item.tmdb_id), (None, Some(8195)));
+        assert_eq!(*fetched.lock().unwrap(), ["Heat-1995"], "only items without ids are fetched, and only once");
+    }
+
+    #[test]
+    fn zero_ids_in_metadata_guids_are_ignored() {
+        let ids = parse_item_metadata(r#"<Video><Guid id="tmdb://0"/><Guid id="tvdb://-3"/><Guid id="tmdb://949"/></Video>"#);
+
+        assert_eq!((ids.tmdb_id, ids.tvdb_id), (Some(949), None));
+    }
+
+    #[test]
+    fn a_rating_key_repeated_in_one_response_yields_one_item() {
+        let xml = test_support::watchlist_xml(&[("1", "Heat", 1995), ("2", "Ronin", 1998), ("1", "Heat", 1995)]);
+
+        let items = client().parse_xml_watchlist(&xml).unwrap();
+
+        let keys: Vec<&str> = items.iter().map(|w| w.item.id.as_str()).collect();
+        assert_eq!(keys, ["1", "2"]);
+    }
+
+    #[test]
+    fn only_unambiguous_guid_schemes_imply_a_type() {
+        assert_eq!(infer_type_from_guid("tmdb://movie/949"), Some(ItemType::Movie));
+        assert_eq!(infer_type_from_guid("TVDB://79126"), Some(ItemType::Show));
+        assert_eq!(infer_type_from_guid("tmdb://tv/1438"), Some(ItemType::Show));
+        assert_eq!(infer_type_from_guid("imdb://tt0113277"), None);
+        assert_eq!(infer_type_from_guid("tmdb://949"), None);
+    }
+
+    #[tokio::test]
+    async fn client_identifier_is_generated_once_and_reused_across_clients() {
+        let seen: Arc<Mutex<Vec<String>>> = Arc::default();
+        let recorded = Arc::clone(&seen);
+        let xml = test_support::watchlist_xml(&[("1", "Heat", 1995)]);
+        let router = axum::Router::new().route(
+            "/library/sections/watchlist/all",
+            axum::routing::get(move |headers: axum::http::HeaderMap| async move {
+                let identifier = headers.get("x-plex-client-identifier").and_then(|v| v.to_str().ok());
+                recorded.lock().unwrap().push(identifier.unwrap_or_default().to_string());
+                xml
+            }),
+        );
+        let metadata_url = test_support::serve(router).await;
+        let path = test_support::temp_path("plex-client-identifier");
+        let _ = std::fs::remove_file(&path);
+
+        for _ in 0..2 {
+            let mut config = test_support::plex_config(&metadata_url, &test_support::unreachable_url().await);
+            config.client_identifier = Some(load_client_identifier(&path));
+            PlexClient::new(test_support::http_client(), config).get_watchlist().await.unwrap();
+        }
+        let persisted = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert!(uuid::Uuid::parse_str(&persisted).is_ok(), "{}", persisted);
+        assert_eq!(*seen, [persisted.clone(), persisted]);
+    }
+
+    #[tokio::test]
+    async fn friends_list_is_reused_within_the_cache_ttl() {
+        let (community, friends_queries) = test_support::plex_community(&[("f1", "alice"), ("f2", "bob")], &[]);
+        let community_url = test_support::serve(community).await;
+        let config = test_support::plex_config(&test_support::unreachable_url().await, &community_url);
+        let status = SyncStatus::new();
+
+        // Clients are built per sync, so the cache has to outlive them
+        for _ in 0..2 {
+            let client = PlexClient::new(test_support::http_client(), config.clone()).with_friends_cache(status.clone());
+            assert_eq!(client.get_friends_watchlists().await.unwrap().complete().unwrap().len(), 2);
+        }
+
+        assert_eq!(friends_queries.lock().unwrap().iter().filter(|q| *q == "friends").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn friends_cache_of_zero_fetches_the_list_every_time() {
+        let (community, friends_queries) = test_support::plex_community(&[("f1", "alice")], &[]);
+        let community_url = test_support::serve(community).await;
+        let mut config = test_support::plex_config(&test_support::unreachable_url().await, &community_url);
+        config.friends_cache_seconds = Some(0);
+        let client = PlexClient::new(test_support::http_client(), config).with_friends_cache(SyncStatus::new());
+
+        client.get_friends_watchlists().await.unwrap();
+        client.get_friends_watchlists().await.unwrap();
+
+        assert_eq!(friends_queries.lock().unwrap().iter().filter(|q| *q == "friends").count(), 2);
+    }
+
+    #[test]
+    fn item_metadata_reads_ids_and_genres() {
+        let xml = r#"<MediaContainer><Video ratingKey="1" title="Free Solo">
+            <Genre tag="Documentary"/><Genre tag="Adventure"/><Genre tag="Documentary"/>
+            <Guid id="imdb://tt7775622"/><Guid id="tmdb://515042"/>
+        </Video></MediaContainer>"#;
+
+        let metadata = parse_item_metadata(xml);
+
+        assert_eq!(metadata.imdb_id.as_deref(), Some("tt7775622"));
+        assert_eq!(metadata.tmdb_id, Some(515042));
+        assert_eq!(metadata.genres, ["Documentary", "Adventure"]);
     }
 }
@@ -1,13 +1,31 @@
+use crate::arr::{self, ArrApi, LibraryIds, QueuedSearches};
 use crate::config::RadarrConfig;
 use crate::http::HttpClient;
-use crate::models::{Item, ItemType, QualityProfile, RootFolder, Tag};
+use crate::models::{positive_id, AddOutcome, Item, ItemType, QualityProfile, RootFolder, SkipReason, SystemStatus};
+use crate::template::TemplateValues;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use tracing::{error, info, instrument, warn};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, instrument, warn};
 
 pub struct RadarrClient {
     http: HttpClient,
     config: RadarrConfig,
+    /// Searches queued by nonBlockingAdd that haven't been waited on yet
+    searches: QueuedSearches,
+    /// Library ids for duplicate checks, loaded on first use. Clients are
+    /// built per sync, so the library is read at most once per cycle.
+    existing: Mutex<Option<ExistingMovies>>,
+}
+
+#[derive(Debug, Default)]
+struct ExistingMovies {
+    all: LibraryIds,
+    /// Movies that already have a file, nothing left for us to do
+    downloaded: LibraryIds,
+    unmonitored: LibraryIds,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,12 +59,12 @@ struct RadarrAddOptions {
 #[derive(Debug, Clone, Deserialize)]
 struct RadarrLookupResult {
     title: String,
-    #[serde(rename = "originalTitle")]
+    #[serde(rename = "originalTitle", default)]
     original_title: String,
     #[serde(rename = "sortTitle")]
     sort_title: String,
     year: Option<i32>,
-    #[serde(rename = "tmdbId", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "tmdbId", default, deserialize_with = "positive_id", skip_serializing_if = "Option::is_none")]
     tmdb_id: Option<i32>,
     #[serde(rename = "imdbId", skip_serializing_if = "Option::is_none")]
     imdb_id: Option<String>,
@@ -54,70 +72,205 @@ struct RadarrLookupResult {
     extra_fields: serde_json::Value,
 }
 
+impl RadarrLookupResult {
+    /// Runtime in minutes, `None` when unknown (TMDB reports 0 for that)
+    fn runtime_minutes(&self) -> Option<u64> {
+        self.extra_fields.get("runtime").and_then(|v| v.as_u64()).filter(|&m| m > 0)
+    }
+
+}
+
+impl arr::LookupCandidate for RadarrLookupResult {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn year(&self) -> Option<i32> {
+        self.year
+    }
+
+    fn has_id(&self) -> bool {
+        self.tmdb_id.is_some()
+    }
+
+    /// TMDB popularity
+    fn popularity(&self) -> f64 {
+        self.extra_fields.get("popularity").and_then(|v| v.as_f64()).unwrap_or(0.0)
+    }
+}
+
 #[derive(Debug, Deserialize)]
-struct RadarrMovieSimple {
-    #[serde(rename = "tmdbId")]
+pub struct RadarrMovieSimple {
+    id: Option<i64>,
+    #[serde(rename = "tmdbId", default, deserialize_with = "positive_id")]
     tmdb_id: Option<i32>,
+    #[serde(rename = "imdbId")]
+    imdb_id: Option<String>,
+    #[serde(rename = "hasFile")]
+    has_file: Option<bool>,
+    monitored: Option<bool>,
 }
 
 impl RadarrClient {
     pub fn new(http: HttpClient, config: RadarrConfig) -> Self {
-        Self { http, config }
+        let http = match config.timeout_seconds {
+            Some(secs) => http.with_timeout(Duration::from_secs(secs)),
+            None => http,
+        };
+        let http = match config.retry {
+            Some(ref retry) => http.with_retry_policy(retry.apply_to(http.retry_policy())),
+            None => http,
+        };
+        let http = arr::accept_json(http);
+        Self { http, config, searches: QueuedSearches::default(), existing: Mutex::new(None) }
+    }
+
+    fn api(&self) -> ArrApi<'_> {
+        ArrApi {
+            service: "Radarr",
+            http: &self.http,
+            base_url: &self.config.base_url,
+            api_key: &self.config.api_key,
+            api_version: self.config.api_version.as_deref().unwrap_or(arr::DEFAULT_API_VERSION),
+            searches: &self.searches,
+        }
+    }
+
+    /// Wait for the searches queued by nonBlockingAdd during this sync
+    pub async fn wait_for_searches(&self) {
+        self.api().wait_for_searches().await
     }
 
     #[instrument(skip(self))]
     pub async fn get_quality_profiles(&self) -> Result<Vec<QualityProfile>> {
-        let url = format!("{}/api/v3/qualityprofile?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("qualityprofile", &[]);
         
         self.http.get_json(&url).await
     }
 
     #[instrument(skip(self))]
     pub async fn get_root_folders(&self) -> Result<Vec<RootFolder>> {
-        let url = format!("{}/api/v3/rootfolder?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("rootfolder", &[]);
         
         self.http.get_json(&url).await
     }
 
     #[instrument(skip(self))]
-    pub async fn get_tags(&self) -> Result<Vec<Tag>> {
-        let url = format!("{}/api/v3/tag?apikey={}", 
-                         self.config.base_url, self.config.api_key);
-        
-        self.http.get_json(&url).await
+    pub async fn get_system_status(&self) -> Result<SystemStatus> {
+        self.api().get_system_status().await
     }
 
     #[instrument(skip(self))]
     pub async fn get_movies(&self) -> Result<Vec<RadarrMovieSimple>> {
-        let url = format!("{}/api/v3/movie?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("movie", &[]);
         
         self.http.get_json(&url).await
     }
 
+    /// Of the `ids` we believe were just added, return those Radarr does not
+    /// actually have
+    #[instrument(skip(self, ids))]
+    pub async fn missing_movie_ids(&self, ids: &[i64]) -> Result<Vec<i64>> {
+        let existing: HashSet<i64> = self.get_movies().await?.into_iter().filter_map(|e| e.id).collect();
+        Ok(ids.iter().copied().filter(|id| !existing.contains(id)).collect())
+    }
+
+    /// Run `f` against the library ids, loading them on first use
+    async fn with_existing<R>(&self, f: impl FnOnce(&ExistingMovies) -> R) -> Result<R> {
+        let mut existing = self.existing.lock().await;
+        if existing.is_none() {
+            let mut movies = ExistingMovies::default();
+            for movie in self.get_movies().await? {
+                movies.all.insert(movie.id, None, movie.tmdb_id, movie.imdb_id.as_deref());
+                if movie.has_file.unwrap_or(false) {
+                    movies.downloaded.insert(movie.id, None, movie.tmdb_id, movie.imdb_id.as_deref());
+                }
+                if !movie.monitored.unwrap_or(true) {
+                    movies.unmonitored.insert(movie.id, None, movie.tmdb_id, movie.imdb_id.as_deref());
+                }
+            }
+            debug!("Loaded {} existing movies from Radarr ({} downloaded)",
+                   movies.all.len(), movies.downloaded.len());
+            *existing = Some(movies);
+        }
+        Ok(f(existing.get_or_insert_with(ExistingMovies::default)))
+    }
+
     #[instrument(skip(self, item))]
-    pub async fn add_movie(&self, item: &Item) -> Result<()> {
+    pub async fn add_movie(&self, item: &Item) -> Result<AddOutcome> {
         if item.item_type != ItemType::Movie {
             warn!("Attempted to add non-movie item to Radarr: {}", item.title);
-            return Ok(());
+            return Ok(AddOutcome::Skipped(SkipReason::WrongType));
+        }
+
+        // Plex sometimes already gives us the ids; a movie that is on disk
+        // needs nothing more, so skip it before spending a lookup on it
+        let plex_imdb_ids: Vec<&str> = item.imdb_id.as_deref().into_iter().collect();
+        if item.tmdb_id.is_some() || !plex_imdb_ids.is_empty() {
+            let downloaded = self.with_existing(|e| e.downloaded.matching(None, item.tmdb_id, &plex_imdb_ids)).await?;
+            if let Some(matched) = downloaded {
+                debug!("Movie '{}' ({}) is already downloaded in Radarr, skipping", item.title, matched);
+                return Ok(AddOutcome::Skipped(SkipReason::AlreadyExists));
+            }
         }
 
         info!("Adding movie to Radarr: {}", item.title);
         
         // First, lookup the movie to get TMDB ID and other metadata
-        let lookup_result = self.lookup_movie(&item.title, item.year).await?;
-
-        // Check if movie already exists in Radarr
-        if let Some(tmdb_id) = lookup_result.tmdb_id {
-            let existing_movies = self.get_movies().await?;
-            if existing_movies.iter().any(|m| m.tmdb_id == Some(tmdb_id)) {
-                info!("Movie '{}' (TMDB: {}) already exists in Radarr, skipping", lookup_result.title, tmdb_id);
-                return Ok(());
+        let mut lookup = self.lookup_movie(&item.title, item.year).await?;
+        for pattern in self.config.strip_patterns.iter().flatten() {
+            if !matches!(lookup, Err(SkipReason::NotFound)) {
+                break;
+            }
+            if let Some(stripped) = arr::strip_title_pattern(&item.title, pattern) {
+                info!("Retrying lookup for '{}' as '{}' (stripPatterns: '{}')", item.title, stripped, pattern);
+                lookup = self.lookup_movie(&stripped, item.year).await?;
+            }
+        }
+        let lookup_result = match lookup {
+            Ok(lookup_result) => lookup_result,
+            Err(reason) => return Ok(AddOutcome::Skipped(reason)),
+        };
+
+        if arr::is_adult(&lookup_result.extra_fields) && !self.config.allow_adult_content.unwrap_or(false) {
+            info!("Skipping adult movie '{}' (allowAdultContent is disabled)", lookup_result.title);
+            return Ok(AddOutcome::Skipped(SkipReason::Filtered("adult content".to_string())));
+        }
+
+        if let Some(min_runtime) = self.config.min_runtime_minutes {
+            match lookup_result.runtime_minutes() {
+                Some(runtime) if runtime < min_runtime => {
+                    info!("Skipping movie '{}', runtime {}m is under minRuntimeMinutes ({}m)", lookup_result.title, runtime, min_runtime);
+                    return Ok(AddOutcome::Skipped(SkipReason::Filtered(format!("runtime under {} minutes", min_runtime))));
+                }
+                Some(_) => {}
+                None => debug!("No runtime known for '{}', not applying minRuntimeMinutes", lookup_result.title),
             }
         }
 
+        // Check if movie already exists in Radarr by TMDB or IMDb id
+        let imdb_ids: Vec<&str> = [item.imdb_id.as_deref(), lookup_result.imdb_id.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if let Some(matched) = self.with_existing(|e| e.all.matching(None, lookup_result.tmdb_id, &imdb_ids)).await? {
+            if self.config.search_unmonitored_existing.unwrap_or(false) {
+                let unmonitored = self.with_existing(|e| e.unmonitored.matching(None, lookup_result.tmdb_id, &imdb_ids)).await?;
+                if let Some(id) = unmonitored.and_then(|m| m.id) {
+                    self.monitor_and_search(id).await?;
+                    // Nothing new was added, so it counts as existing
+                    info!("Movie '{}' ({}) was unmonitored in Radarr, monitored it and queued a search", lookup_result.title, matched);
+                    return Ok(AddOutcome::Skipped(SkipReason::AlreadyExists));
+                }
+            }
+            if let (true, Some(id), Some(profile)) = (self.config.update_existing_profile.unwrap_or(false), matched.id, &self.config.quality_profile) {
+                self.api().update_quality_profile("movie", id, profile, &lookup_result.title).await?;
+            }
+            info!("Movie '{}' ({}) already exists in Radarr, skipping", lookup_result.title, matched);
+            return Ok(AddOutcome::Skipped(SkipReason::AlreadyExists));
+        }
+
         let quality_profiles = self.get_quality_profiles().await?;
         let root_folders = self.get_root_folders().await?;
         
@@ -134,46 +287,85 @@ impl RadarrClient {
             quality_profiles.first().map(|p| p.id).unwrap_or(1)
         };
 
-        let root_folder_path = if let Some(ref folder) = self.config.root_folder {
+        let root_folder_path = if let Some(ref template) = self.config.root_folder_template {
+            let values = TemplateValues {
+                title: &lookup_result.title,
+                year: lookup_result.year,
+                genre: arr::first_genre(&lookup_result.extra_fields),
+            };
+            let allow_any = self.config.allow_any_root_folder.unwrap_or(false);
+            arr::templated_root_folder("Radarr", template, &values, &root_folders, allow_any)?
+        } else if let Some(ref folder) = self.config.root_folder {
             folder.clone()
         } else {
-            root_folders
-                .first()
-                .map(|f| f.path.clone())
-                .unwrap_or_else(|| "/mnt/shared/movies".to_string())
+            arr::fallback_root_folder("Radarr", &root_folders, self.config.default_movie_root.as_ref(), "defaultMovieRoot", &item.title)?
         };
 
-        let tag_ids = if let Some(ref tags) = self.config.tags {
-            self.resolve_tag_ids(tags).await.unwrap_or_default()
+        let mut tag_ids = if let Some(ref tags) = self.config.tags {
+            self.api().resolve_tag_ids(tags).await.unwrap_or_default()
         } else {
             Vec::new()
         };
 
+        if self.config.mirror_plex_labels.unwrap_or(false) && !item.labels.is_empty() {
+            match self.api().ensure_tag_ids(&item.labels).await {
+                Ok(label_tag_ids) => {
+                    tag_ids.extend(label_tag_ids);
+                    tag_ids.sort_unstable();
+                    tag_ids.dedup();
+                }
+                Err(e) => warn!("Failed to mirror Plex labels {:?} as tags: {}", item.labels, e),
+            }
+        }
+
         info!("Using quality profile ID: {}, root folder: {}", quality_profile_id, root_folder_path);
 
+        // With nonBlockingAdd the add skips the search (which can hold the response)
+        // and the search is queued as a separate command afterwards
+        let non_blocking_add = self.config.non_blocking_add.unwrap_or(false);
+
         let movie = RadarrMovie {
             title: lookup_result.title.clone(),
-            original_title: lookup_result.original_title,
-            sort_title: lookup_result.sort_title,
+            // Plex's original title fills the gap when the lookup has none
+            original_title: match item.original_title {
+                Some(ref plex_original) if lookup_result.original_title.trim().is_empty() => plex_original.clone(),
+                _ => lookup_result.original_title,
+            },
+            sort_title: if self.config.normalize_sort_title.unwrap_or(false) {
+                arr::normalized_sort_title(&lookup_result.title)
+            } else {
+                lookup_result.sort_title
+            },
             year: lookup_result.year.unwrap_or(0),
             tmdb_id: lookup_result.tmdb_id,
             imdb_id: lookup_result.imdb_id,
             quality_profile_id,
             root_folder_path,
             add_options: RadarrAddOptions {
-                search_for_movie: true,
+                search_for_movie: !non_blocking_add,
             },
             monitored: true,
             tags: tag_ids,
         };
 
-        let url = format!("{}/api/v3/movie?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("movie", &[]);
         
-        match self.http.post_json::<serde_json::Value, _>(&url, &movie).await {
-            Ok(_) => {
+        let payload = arr::build_payload(&movie, &lookup_result.extra_fields, self.config.payload_mode.unwrap_or_default(), self.config.extra_add_fields.as_ref())?;
+
+        match self.http.post_json::<serde_json::Value, _>(&url, &payload).await {
+            Ok(added) => {
                 info!("Successfully added movie: {}", lookup_result.title);
-                Ok(())
+                let id = added.get("id").and_then(|id| id.as_i64());
+                if let Some(existing) = self.existing.lock().await.as_mut() {
+                    existing.all.insert(id, None, movie.tmdb_id, movie.imdb_id.as_deref());
+                }
+                if non_blocking_add {
+                    match id {
+                        Some(id) => self.api().queue_search(serde_json::json!({ "name": "MoviesSearch", "movieIds": [id] })).await,
+                        None => warn!("Added movie '{}' has no id, cannot queue search", lookup_result.title),
+                    }
+                }
+                Ok(AddOutcome::Added(id))
             }
             Err(e) => {
                 error!("Failed to add movie '{}': {}", lookup_result.title, e);
@@ -183,35 +375,604 @@ impl RadarrClient {
     }
 
     #[instrument(skip(self))]
-    async fn lookup_movie(&self, title: &str, year: Option<i32>) -> Result<RadarrLookupResult> {
+    async fn lookup_movie(&self, title: &str, year: Option<i32>) -> Result<std::result::Result<RadarrLookupResult, SkipReason>> {
         let search_term = if let Some(year) = year {
             format!("{} {}", title, year)
         } else {
             title.to_string()
         };
         
-        let url = format!("{}/api/v3/movie/lookup?term={}&apikey={}", 
-                         self.config.base_url, 
-                         urlencoding::encode(&search_term),
-                         self.config.api_key);
+        let url = self.api().build_url("movie/lookup", &[("term", &search_term)]);
         
         info!("Looking up movie: {}", search_term);
         
         let results: Vec<RadarrLookupResult> = self.http.get_json(&url).await?;
         
-        if let Some(result) = results.first() {
-            info!("Found movie: {} (TMDB: {:?})", result.title, result.tmdb_id);
-            Ok(result.clone())
-        } else {
-            Err(anyhow::anyhow!("Movie not found in lookup: {}", search_term))
+        if results.is_empty() {
+            warn!("Movie not found in lookup: {}", search_term);
+            return Ok(Err(SkipReason::NotFound));
+        }
+
+        let result = match arr::pick_lookup_result(&results, title, year, self.config.ambiguous_match.unwrap_or_default()) {
+            Ok(result) => result,
+            Err(reason) => return Ok(Err(reason)),
+        };
+
+        info!("Found movie: {} ({:?}) (TMDB: {:?})", result.title, result.year, result.tmdb_id);
+        Ok(Ok(result.clone()))
+    }
+
+    /// Flip an existing movie to monitored, then search for it
+    async fn monitor_and_search(&self, id: i64) -> Result<()> {
+        let url = self.api().build_url(&format!("movie/{}", id), &[]);
+
+        let mut movie: serde_json::Value = self.http.get_json(&url).await?;
+        movie["monitored"] = serde_json::Value::Bool(true);
+        let _: serde_json::Value = self.http.put_json(&url, &movie).await?;
+
+        self.api().queue_search(serde_json::json!({ "name": "MoviesSearch", "movieIds": [id] })).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AmbiguousMatch;
+    use crate::test_support::{self, Arr};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+
+    /// Radarr stand-in whose lookup finds an adult-flagged "Heat"
+    async fn adult_lookup(extra: &str) -> (RadarrClient, test_support::Writes) {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{
+                "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat",
+                "year": 1995, "tmdbId": 949, "adult": true
+            }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, extra));
+        (client, writes)
+    }
+
+    #[tokio::test]
+    async fn timeout_seconds_overrides_the_global_request_timeout() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup_delay: Duration::from_secs(10),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "timeoutSeconds: 1\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+        let started = std::time::Instant::now();
+
+        let result = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn full_payload_mode_posts_the_lookup_fields_too() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{
+                "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat",
+                "year": 1995, "tmdbId": 949, "runtime": 170
+            }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "payloadMode: full\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+
+        client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes[0].body["runtime"], 170);
+        assert_eq!(writes[0].body["qualityProfileId"], 1);
+        assert_eq!(writes[0].body["rootFolderPath"], "/media");
+    }
+
+    #[tokio::test]
+    async fn extra_add_fields_are_merged_into_the_post_body() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(
+            &base_url,
+            "extraAddFields:\n  minimumAvailability: released\n  addOptions:\n    monitor: movieOnly\n",
+        );
+        let client = RadarrClient::new(test_support::http_client(), config);
+
+        client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes[0].body["minimumAvailability"], "released");
+        assert_eq!(writes[0].body["addOptions"]["monitor"], "movieOnly");
+        assert_eq!(writes[0].body["addOptions"]["searchForMovie"], true);
+        assert_eq!(writes[0].body["tmdbId"], 949);
+    }
+
+    #[tokio::test]
+    async fn adult_movies_are_skipped_by_default() {
+        let (client, writes) = adult_lookup("").await;
+
+        let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::Filtered("adult content".to_string())));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn adult_movies_are_added_when_allowed() {
+        let (client, writes) = adult_lookup("allowAdultContent: true\n").await;
+
+        let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Added(Some(1)));
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].body["tmdbId"], 949);
+    }
+
+    #[tokio::test]
+    async fn movies_under_min_runtime_are_filtered_unless_the_runtime_is_unknown() {
+        for (runtime, expected) in [
+            (25, AddOutcome::Skipped(SkipReason::Filtered("runtime under 40 minutes".to_string()))),
+            (170, AddOutcome::Added(Some(1))),
+            (0, AddOutcome::Added(Some(1))),
+        ] {
+            let (router, _) = test_support::arr("movie", Arr {
+                lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949, "runtime": runtime }]),
+                ..Arr::default()
+            });
+            let base_url = test_support::serve(router).await;
+            let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, "minRuntimeMinutes: 40\n"));
+
+            let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+            assert_eq!(outcome, expected, "runtime {}", runtime);
+        }
+    }
+
+    #[test]
+    fn zero_or_negative_tmdb_ids_are_read_as_absent() {
+        let lookup: RadarrLookupResult = serde_json::from_value(json!({ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "tmdbId": 0 })).unwrap();
+        assert_eq!(lookup.tmdb_id, None);
+
+        let movie: RadarrMovieSimple = serde_json::from_value(json!({ "id": 1, "tmdbId": -1 })).unwrap();
+        assert_eq!(movie.tmdb_id, None);
+
+        let movie: RadarrMovieSimple = serde_json::from_value(json!({ "id": 1, "tmdbId": 949 })).unwrap();
+        assert_eq!(movie.tmdb_id, Some(949));
+    }
+
+    #[tokio::test]
+    async fn root_folder_templates_expand_per_movie_within_the_root_folders() {
+        let lookup = json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949, "genres": ["Crime", "Drama"] }]);
+        for (extra, expected) in [
+            ("rootFolderTemplate: /media/{genre}/{first_letter}\n", Some("/media/Crime/H")),
+            ("rootFolderTemplate: /elsewhere/{title}\n", None),
+            ("rootFolderTemplate: /elsewhere/{title}\nallowAnyRootFolder: true\n", Some("/elsewhere/Heat")),
+        ] {
+            let (router, writes) = test_support::arr("movie", Arr { lookup: lookup.clone(), ..Arr::default() });
+            let base_url = test_support::serve(router).await;
+            let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, extra));
+
+            let result = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await;
+
+            let writes = writes.lock().unwrap();
+            match expected {
+                Some(path) => assert_eq!(writes[0].body["rootFolderPath"], path, "{}", extra),
+                None => assert!(result.is_err() && writes.is_empty(), "{}", extra),
+            }
         }
     }
 
-    async fn resolve_tag_ids(&self, tag_names: &[String]) -> Result<Vec<i32>> {
-        let tags = self.get_tags().await?;
-        Ok(tag_names
-            .iter()
-            .filter_map(|name| tags.iter().find(|t| t.label == *name).map(|t| t.id))
-            .collect())
+    async fn client_for(arr: Arr) -> (RadarrClient, test_support::Writes) {
+        let (router, writes) = test_support::arr("movie", arr);
+        let base_url = test_support::serve(router).await;
+        (RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, "")), writes)
+    }
+
+    #[tokio::test]
+    async fn shows_are_skipped_as_the_wrong_type() {
+        let (client, _) = client_for(Arr::default()).await;
+
+        let outcome = client.add_movie(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::WrongType));
+    }
+
+    #[tokio::test]
+    async fn movies_the_lookup_cannot_find_are_skipped_as_not_found() {
+        let (client, _) = client_for(Arr::default()).await;
+
+        let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::NotFound));
+    }
+
+    #[tokio::test]
+    async fn movies_already_in_radarr_are_skipped_as_existing() {
+        let (client, writes) = client_for(Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            existing: json!([{ "id": 7, "tmdbId": 949 }]),
+            ..Arr::default()
+        })
+        .await;
+
+        let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_existing_profile_puts_only_when_enabled_and_the_profile_differs() {
+        let cases = [
+            ("updateExistingProfile: true\n", 4, true),
+            ("updateExistingProfile: true\n", 1, false),
+            ("", 4, false),
+        ];
+        for (extra, current_profile, expect_put) in cases {
+            let (router, writes) = test_support::arr("movie", Arr {
+                lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+                existing: json!([{ "id": 7, "tmdbId": 949, "title": "Heat", "qualityProfileId": current_profile }]),
+                ..Arr::default()
+            });
+            let base_url = test_support::serve(router).await;
+            let config = test_support::radarr_config(&base_url, &format!("qualityProfile: Any\n{}", extra));
+            let client = RadarrClient::new(test_support::http_client(), config);
+
+            let outcome = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+            assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+            let writes = writes.lock().unwrap();
+            if expect_put {
+                assert_eq!(writes.len(), 1);
+                assert_eq!(writes[0].path, "/movie/7");
+                assert_eq!(writes[0].body["qualityProfileId"], 1);
+                assert_eq!(writes[0].body["title"], "Heat");
+            } else {
+                assert!(writes.is_empty(), "{:?} on profile {}: {:?}", extra, current_profile, *writes);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn plex_labels_are_mirrored_as_tags_creating_missing_ones() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            tags: json!([{ "id": 10, "label": "kids" }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "mirrorPlexLabels: true\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+        let mut item = test_support::item(ItemType::Movie, "Heat", 1995);
+        item.labels = vec!["Kids".to_string(), "Sci Fi".to_string()];
+
+        client.add_movie(&item).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!((writes[0].path.as_str(), &writes[0].body["label"]), ("/tag", &json!("sci-fi")));
+        assert_eq!(writes[1].body["tags"], json!([1, 10]));
+    }
+
+    #[tokio::test]
+    async fn plex_labels_are_ignored_unless_mirrored() {
+        let (client, writes) = client_for(Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            ..Arr::default()
+        })
+        .await;
+        let mut item = test_support::item(ItemType::Movie, "Heat", 1995);
+        item.labels = vec!["Kids".to_string()];
+
+        client.add_movie(&item).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].body["tags"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn non_blocking_add_returns_before_the_search_is_queued() {
+        let gate = Arc::new(Notify::new());
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            command_gate: Some(Arc::clone(&gate)),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "nonBlockingAdd: true\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+
+        // Radarr holds the search command until the gate opens, so the add
+        // can only return if it doesn't wait for the search
+        let item = test_support::item(ItemType::Movie, "Heat", 1995);
+        let outcome = crate::http::with_request_id("item-1".to_string(), async {
+            tokio::time::timeout(Duration::from_secs(5), client.add_movie(&item)).await
+        })
+        .await
+        .expect("add waited on the search")
+        .unwrap();
+        assert_eq!(outcome, AddOutcome::Added(Some(1)));
+        assert!(writes.lock().unwrap().iter().all(|w| w.path != "/command"));
+
+        gate.notify_one();
+        client.wait_for_searches().await;
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes[0].body["addOptions"]["searchForMovie"], false);
+        let search = writes.iter().find(|w| w.path == "/command").expect("search was never queued");
+        assert_eq!(search.body, json!({ "name": "MoviesSearch", "movieIds": [1] }));
+        // The search outlives the add but still carries its request id
+        assert_eq!(search.request_id.as_deref(), Some("item-1"));
+    }
+
+    #[tokio::test]
+    async fn movies_matching_the_item_imdb_id_are_skipped_as_existing() {
+        let (client, writes) = client_for(Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995 }]),
+            existing: json!([{ "id": 7, "imdbId": "tt0113277" }]),
+            ..Arr::default()
+        })
+        .await;
+        let mut item = test_support::item(ItemType::Movie, "Heat", 1995);
+        item.imdb_id = Some("tt0113277".to_string());
+
+        let outcome = client.add_movie(&item).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_library_is_read_once_per_client_for_duplicate_checks() {
+        let library_reads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (client, writes) = client_for(Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            existing: json!([{ "id": 7, "tmdbId": 603, "imdbId": "tt0133093" }]),
+            library_reads: Arc::clone(&library_reads),
+            ..Arr::default()
+        })
+        .await;
+        let item = test_support::item(ItemType::Movie, "Heat", 1995);
+
+        let outcomes = [client.add_movie(&item).await.unwrap(), client.add_movie(&item).await.unwrap()];
+
+        // The second add is caught by the set the first one updated, without
+        // reading the library again
+        assert_eq!(outcomes, [AddOutcome::Added(Some(1)), AddOutcome::Skipped(SkipReason::AlreadyExists)]);
+        assert_eq!(library_reads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(writes.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn movies_already_on_disk_are_skipped_before_any_lookup() {
+        // The lookup finds nothing, so only an early skip reports AlreadyExists
+        let (client, _) = client_for(Arr {
+            existing: json!([{ "id": 7, "tmdbId": 949, "hasFile": true }, { "id": 8, "tmdbId": 9802, "hasFile": false }]),
+            ..Arr::default()
+        })
+        .await;
+        let mut filed = test_support::item(ItemType::Movie, "Heat", 1995);
+        filed.tmdb_id = Some(949);
+        let mut unfiled = test_support::item(ItemType::Movie, "The Rock", 1996);
+        unfiled.tmdb_id = Some(9802);
+
+        assert_eq!(client.add_movie(&filed).await.unwrap(), AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert_eq!(client.add_movie(&unfiled).await.unwrap(), AddOutcome::Skipped(SkipReason::NotFound));
+    }
+
+    #[tokio::test]
+    async fn unmonitored_existing_movies_are_monitored_and_searched_when_enabled() {
+        let arr = || Arr {
+            lookup: json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]),
+            existing: json!([{ "id": 7, "tmdbId": 949, "hasFile": false, "monitored": false }]),
+            ..Arr::default()
+        };
+        let heat = test_support::item(ItemType::Movie, "Heat", 1995);
+
+        let (client, writes) = client_for(arr()).await;
+        assert_eq!(client.add_movie(&heat).await.unwrap(), AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert!(writes.lock().unwrap().is_empty());
+
+        let (router, writes) = test_support::arr("movie", arr());
+        let base_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, "searchUnmonitoredExisting: true"));
+
+        let outcome = client.add_movie(&heat).await.unwrap();
+        client.wait_for_searches().await;
+
+        // Nothing new was added, so it still counts as existing
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        let writes = writes.lock().unwrap();
+        let paths: Vec<&str> = writes.iter().map(|w| w.path.as_str()).collect();
+        assert_eq!(paths, ["/movie/7", "/command"]);
+        assert_eq!(writes[0].body["monitored"], true);
+        assert_eq!(writes[1].body, json!({ "name": "MoviesSearch", "movieIds": [7] }));
+    }
+
+    fn remake(title: &str, year: i32, tmdb_id: i32, popularity: f64) -> serde_json::Value {
+        json!({
+            "title": title, "originalTitle": title, "sortTitle": title.to_lowercase(),
+            "year": year, "tmdbId": tmdb_id, "popularity": popularity
+        })
+    }
+
+    fn picked(results: &[serde_json::Value], title: &str, year: Option<i32>, policy: AmbiguousMatch) -> std::result::Result<Option<i32>, SkipReason> {
+        let results: Vec<RadarrLookupResult> = serde_json::from_value(json!(results)).unwrap();
+        arr::pick_lookup_result(&results, title, year, policy).map(|r| r.tmdb_id)
+    }
+
+    #[test]
+    fn remakes_are_told_apart_by_the_item_year() {
+        let results = [remake("Dune", 2021, 438631, 50.0), remake("Dune", 1984, 841, 90.0)];
+
+        assert_eq!(picked(&results, "Dune", Some(1984), AmbiguousMatch::Skip), Ok(Some(841)));
+        assert_eq!(picked(&results, "dune", Some(2021), AmbiguousMatch::Skip), Ok(Some(438631)));
+        // Several remakes and none from the item's year
+        assert_eq!(picked(&results, "Dune", Some(2000), AmbiguousMatch::MostPopular), Err(SkipReason::Ambiguous));
+    }
+
+    #[test]
+    fn remakes_without_an_item_year_follow_the_ambiguous_match_policy() {
+        let results = [remake("Dune", 2021, 438631, 50.0), remake("Dune", 1984, 841, 90.0)];
+
+        assert_eq!(picked(&results, "Dune", None, AmbiguousMatch::Skip), Err(SkipReason::Ambiguous));
+        assert_eq!(picked(&results, "Dune", None, AmbiguousMatch::First), Ok(Some(438631)));
+        assert_eq!(picked(&results, "Dune", None, AmbiguousMatch::MostPopular), Ok(Some(841)));
+    }
+
+    #[test]
+    fn a_lone_title_match_wins_over_the_first_result() {
+        let results = [remake("Dune: Part Two", 2024, 693134, 90.0), remake("Dune", 2021, 438631, 50.0)];
+
+        assert_eq!(picked(&results, "Dune", Some(2021), AmbiguousMatch::Skip), Ok(Some(438631)));
+        assert_eq!(picked(&results, "Dune", None, AmbiguousMatch::Skip), Ok(Some(438631)));
+        // No result carries the title as given, so Radarr's ranking stands
+        assert_eq!(picked(&results, "Dune (1984)", None, AmbiguousMatch::Skip), Ok(Some(693134)));
+        assert_eq!(picked(&[], "Dune", None, AmbiguousMatch::MostPopular), Err(SkipReason::NotFound));
+    }
+
+    #[tokio::test]
+    async fn a_remake_is_added_by_the_item_year_or_skipped_without_one() {
+        let (client, writes) = client_for(Arr {
+            lookup: json!([remake("Dune", 2021, 438631, 50.0), remake("Dune", 1984, 841, 90.0)]),
+            ..Arr::default()
+        })
+        .await;
+
+        let undated = Item { year: None, ..test_support::item(ItemType::Movie, "Dune", 1984) };
+        assert_eq!(client.add_movie(&undated).await.unwrap(), AddOutcome::Skipped(SkipReason::Ambiguous));
+        assert!(writes.lock().unwrap().is_empty());
+
+        let dated = test_support::item(ItemType::Movie, "Dune", 1984);
+        assert!(matches!(client.add_movie(&dated).await.unwrap(), AddOutcome::Added(_)));
+        assert_eq!(writes.lock().unwrap()[0].body["tmdbId"], 841);
+    }
+
+    #[tokio::test]
+    async fn normalize_sort_title_replaces_the_lookups_sort_title() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            lookup: json!([{ "title": "The Matrix", "originalTitle": "The Matrix", "sortTitle": "matrix the", "year": 1999, "tmdbId": 603 }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "normalizeSortTitle: true\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+
+        client.add_movie(&test_support::item(ItemType::Movie, "The Matrix", 1999)).await.unwrap();
+
+        assert_eq!(writes.lock().unwrap()[0].body["sortTitle"], "matrix");
+    }
+
+    #[tokio::test]
+    async fn plex_original_title_is_sent_only_when_the_lookup_has_none() {
+        let (client, writes) = client_for(Arr {
+            term_lookups: HashMap::from([
+                ("Amelie 2001".to_string(), json!([{ "title": "Amelie", "sortTitle": "amelie", "year": 2001, "tmdbId": 194 }])),
+                ("Heat 1995".to_string(), json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }])),
+            ]),
+            ..Arr::default()
+        })
+        .await;
+        let original = |title: &str, year| Item {
+            original_title: Some(format!("{} (Plex)", title)),
+            ..test_support::item(ItemType::Movie, title, year)
+        };
+
+        client.add_movie(&original("Amelie", 2001)).await.unwrap();
+        client.add_movie(&original("Heat", 1995)).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes[0].body["originalTitle"], "Amelie (Plex)");
+        assert_eq!(writes[1].body["originalTitle"], "Heat");
+    }
+
+    #[tokio::test]
+    async fn strip_patterns_retry_a_lookup_that_found_nothing() {
+        let (router, writes) = test_support::arr("movie", Arr {
+            term_lookups: HashMap::from([(
+                "Alien 1979".to_string(),
+                json!([{ "title": "Alien", "originalTitle": "Alien", "sortTitle": "alien", "year": 1979, "tmdbId": 348 }]),
+            )]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "stripPatterns: [\"Extended Edition\", \"Director's Cut\"]\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+        let logs = test_support::Logs::default();
+
+        let outcome = {
+            let _guard = logs.capture_at(tracing::Level::INFO);
+            client.add_movie(&test_support::item(ItemType::Movie, "Alien - Director's Cut", 1979)).await.unwrap()
+        };
+
+        assert!(matches!(outcome, AddOutcome::Added(_)));
+        assert_eq!(writes.lock().unwrap()[0].body["tmdbId"], 348);
+        assert!(logs.lines().iter().any(|l| l.contains("as 'Alien' (stripPatterns: 'Director's Cut')")), "{:?}", logs.lines());
+    }
+
+    #[tokio::test]
+    async fn without_any_root_folder_the_add_fails_instead_of_guessing_one() {
+        let heat = json!([{ "title": "Heat", "originalTitle": "Heat", "sortTitle": "heat", "year": 1995, "tmdbId": 949 }]);
+        let (router, writes) = test_support::arr("movie", Arr { lookup: heat.clone(), root_folders: json!([]), ..Arr::default() });
+        let base_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, ""));
+
+        let err = client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap_err();
+
+        assert!(err.to_string().contains("Radarr has no root folders and neither rootFolder nor defaultMovieRoot is set"), "{}", err);
+        assert!(writes.lock().unwrap().is_empty());
+
+        let (router, writes) = test_support::arr("movie", Arr { lookup: heat, root_folders: json!([]), ..Arr::default() });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::radarr_config(&base_url, "defaultMovieRoot: /data/movies\n");
+        let client = RadarrClient::new(test_support::http_client(), config);
+
+        client.add_movie(&test_support::item(ItemType::Movie, "Heat", 1995)).await.unwrap();
+
+        assert_eq!(writes.lock().unwrap()[0].body["rootFolderPath"], "/data/movies");
+    }
+
+    #[tokio::test]
+    async fn requests_ask_for_json() {
+        let accept: Arc<std::sync::Mutex<Option<String>>> = Arc::default();
+        let recorded = Arc::clone(&accept);
+        let router = axum::Router::new().route(
+            "/api/v3/system/status",
+            axum::routing::get(move |headers: axum::http::HeaderMap| async move {
+                *recorded.lock().unwrap() = headers.get("accept").and_then(|v| v.to_str().ok()).map(str::to_string);
+                axum::Json(json!({ "version": "5.2.6" }))
+            }),
+        );
+        let base_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, ""));
+
+        client.get_system_status().await.unwrap();
+
+        assert_eq!(accept.lock().unwrap().as_deref(), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn a_404_on_system_status_is_reported_as_an_api_version_mismatch() {
+        let (router, _) = test_support::arr("movie", Arr::default());
+        let base_url = test_support::serve(router).await;
+        let client = RadarrClient::new(test_support::http_client(), test_support::radarr_config(&base_url, "apiVersion: v5\n"));
+
+        let err = client.get_system_status().await.unwrap_err();
+
+        assert!(err.to_string().contains("API version mismatch: Radarr has no /api/v5/system/status"), "{:#}", err);
     }
 }
@@ -0,0 +1,89 @@
+use crate::status::{ServiceHealth, SkippedItem, SyncStatus};
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    plex_token_valid: Option<bool>,
+    /// Last pre-flight result per backend, empty until the first sync
+    services: BTreeMap<String, ServiceHealth>,
+}
+
+pub async fn serve(bind: String, status: SyncStatus) -> Result<()> {
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .route("/status/skipped", get(skipped))
+        .with_state(status);
+
+    let listener = tokio::net::TcpListener::bind(&bind).await?;
+    info!("Status server listening on {}", bind);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn skipped(State(status): State<SyncStatus>) -> Json<Vec<SkippedItem>> {
+    Json(status.skipped())
+}
+
+/// A rejected Plex token stops every sync and fails the check with a 503.
+/// A single unreachable backend only marks it degraded, so monitoring can
+/// tell which integration broke from `services`.
+async fn health(State(status): State<SyncStatus>) -> (StatusCode, Json<HealthResponse>) {
+    let plex_token_valid = status.plex_token_valid();
+    let services = status.services();
+
+    if plex_token_valid == Some(false) {
+        let body = HealthResponse { status: "degraded", plex_token_valid, services };
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(body));
+    }
+
+    let status = if services.values().all(|s| s.up) { "ok" } else { "degraded" };
+    (StatusCode::OK, Json(HealthResponse { status, plex_token_valid, services }))
+}
+
+/// Prometheus text format gauges for Plex token validity and each backend
+async fn metrics(State(status): State<SyncStatus>) -> String {
+    crate::metrics::prometheus(&status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn health_is_degraded_while_the_plex_token_is_rejected() {
+        let status = SyncStatus::new();
+        assert_eq!(health(State(status.clone())).await.0, StatusCode::OK);
+
+        status.set_plex_token_valid(false);
+        let (code, Json(body)) = health(State(status.clone())).await;
+        assert_eq!((code, body.status), (StatusCode::SERVICE_UNAVAILABLE, "degraded"));
+
+        status.set_plex_token_valid(true);
+        assert_eq!(health(State(status)).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_down_backend_degrades_health_and_reads_zero_in_metrics() {
+        let status = SyncStatus::new();
+        status.record_service("radarr", Some("5.2.6".to_string()));
+        status.record_service("sonarr", None);
+
+        let (code, Json(body)) = health(State(status.clone())).await;
+        assert_eq!((code, body.status), (StatusCode::OK, "degraded"));
+        assert!(!body.services["sonarr"].up);
+
+        let metrics = metrics(State(status)).await;
+        assert!(metrics.contains("watchlistarr_service_up{service=\"radarr\"} 1\n"), "{}", metrics);
+        assert!(metrics.contains("watchlistarr_service_up{service=\"sonarr\"} 0\n"), "{}", metrics);
+    }
+}
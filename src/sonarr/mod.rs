@@ -1,15 +1,27 @@
+use crate::arr::{self, ArrApi, ExistingMatch, LibraryIds, QueuedSearches};
 use crate::config::SonarrConfig;
-use crate::http::HttpClient;
-use crate::models::{Item, ItemType, QualityProfile, RootFolder, Tag};
+use crate::http::{HttpClient, HttpStatusError};
+use crate::models::{positive_id, AddOutcome, Item, ItemType, QualityProfile, RootFolder, SkipReason, SystemStatus};
+use crate::template::TemplateValues;
 use anyhow::Result;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::Mutex;
 use tracing::{debug, error, info, instrument, warn};
 
 pub struct SonarrClient {
     http: HttpClient,
     config: SonarrConfig,
+    /// Searches queued by nonBlockingAdd that haven't been waited on yet
+    searches: QueuedSearches,
+    /// Library ids for duplicate checks, loaded on first use. Clients are
+    /// built per sync, so the library is read at most once per cycle.
+    existing: Mutex<Option<LibraryIds>>,
 }
 
+
 #[derive(Debug, Serialize)]
 struct SonarrSeries {
     title: String,
@@ -30,6 +42,18 @@ struct SonarrSeries {
     add_options: SonarrAddOptions,
     monitored: bool,
     tags: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seasons: Option<Vec<SonarrSeason>>,
+    /// Left out when unset so Sonarr's own default applies
+    #[serde(rename = "seasonFolder", skip_serializing_if = "Option::is_none")]
+    season_folder: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+struct SonarrSeason {
+    #[serde(rename = "seasonNumber")]
+    season_number: i32,
+    monitored: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -45,116 +69,277 @@ struct SonarrLookupResult {
     #[serde(rename = "sortTitle")]
     sort_title: String,
     year: Option<i32>,
-    #[serde(rename = "tvdbId")]
+    #[serde(rename = "tvdbId", default, deserialize_with = "positive_id")]
     tvdb_id: Option<i32>,
     #[serde(rename = "imdbId")]
     imdb_id: Option<String>,
-    #[serde(rename = "tmdbId")]
+    #[serde(rename = "tmdbId", default, deserialize_with = "positive_id")]
     tmdb_id: Option<i32>,
     #[serde(flatten)]
     extra_fields: serde_json::Value,
 }
 
+impl SonarrLookupResult {
+    /// Regular seasons (specials excluded), `None` when the lookup has no season list
+    fn season_count(&self) -> Option<u64> {
+        let seasons = self.extra_fields.get("seasons")?.as_array()?;
+        Some(seasons
+            .iter()
+            .filter(|s| s.get("seasonNumber").and_then(|n| n.as_i64()).is_some_and(|n| n > 0))
+            .count() as u64)
+    }
+
+    /// Episode count when the lookup carries statistics, which not every
+    /// Sonarr version includes for series that aren't in the library
+    fn episode_count(&self) -> Option<u64> {
+        let statistics = self.extra_fields.get("statistics")?;
+        statistics
+            .get("totalEpisodeCount")
+            .or_else(|| statistics.get("episodeCount"))
+            .and_then(|v| v.as_u64())
+    }
+}
+
+impl arr::LookupCandidate for SonarrLookupResult {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn year(&self) -> Option<i32> {
+        self.year
+    }
+
+    fn has_id(&self) -> bool {
+        self.tvdb_id.is_some()
+    }
+
+    /// Sonarr's lookup has no popularity, the rating's vote count stands in
+    fn popularity(&self) -> f64 {
+        self.extra_fields.pointer("/ratings/votes").and_then(|v| v.as_f64()).unwrap_or(0.0)
+    }
+}
+
 #[derive(Debug, Deserialize)]
-struct SonarrSeriesSimple {
-    #[serde(rename = "tvdbId")]
+pub struct SonarrSeriesSimple {
+    id: Option<i64>,
+    #[serde(rename = "tvdbId", default, deserialize_with = "positive_id")]
     tvdb_id: Option<i32>,
-    #[serde(rename = "tmdbId")]
+    #[serde(rename = "tmdbId", default, deserialize_with = "positive_id")]
     tmdb_id: Option<i32>,
+    #[serde(rename = "imdbId")]
+    imdb_id: Option<String>,
 }
 
 impl SonarrClient {
     pub fn new(http: HttpClient, config: SonarrConfig) -> Self {
-        Self { http, config }
+        let http = match config.timeout_seconds {
+            Some(secs) => http.with_timeout(Duration::from_secs(secs)),
+            None => http,
+        };
+        let http = match config.retry {
+            Some(ref retry) => http.with_retry_policy(retry.apply_to(http.retry_policy())),
+            None => http,
+        };
+        let http = arr::accept_json(http);
+        Self { http, config, searches: QueuedSearches::default(), existing: Mutex::new(None) }
+    }
+
+    fn api(&self) -> ArrApi<'_> {
+        ArrApi {
+            service: "Sonarr",
+            http: &self.http,
+            base_url: &self.config.base_url,
+            api_key: &self.config.api_key,
+            api_version: self.config.api_version.as_deref().unwrap_or(arr::DEFAULT_API_VERSION),
+            searches: &self.searches,
+        }
+    }
+
+    /// Wait for the searches queued by nonBlockingAdd during this sync
+    pub async fn wait_for_searches(&self) {
+        self.api().wait_for_searches().await
     }
 
     #[instrument(skip(self))]
     pub async fn get_quality_profiles(&self) -> Result<Vec<QualityProfile>> {
-        let url = format!("{}/api/v3/qualityprofile?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("qualityprofile", &[]);
         
         self.http.get_json(&url).await
     }
 
     #[instrument(skip(self))]
     pub async fn get_root_folders(&self) -> Result<Vec<RootFolder>> {
-        let url = format!("{}/api/v3/rootfolder?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("rootfolder", &[]);
         
         self.http.get_json(&url).await
     }
 
     #[instrument(skip(self))]
-    pub async fn get_tags(&self) -> Result<Vec<Tag>> {
-        let url = format!("{}/api/v3/tag?apikey={}", 
-                         self.config.base_url, self.config.api_key);
-        
-        self.http.get_json(&url).await
+    pub async fn get_system_status(&self) -> Result<SystemStatus> {
+        self.api().get_system_status().await
     }
 
     #[instrument(skip(self))]
     pub async fn get_series(&self) -> Result<Vec<SonarrSeriesSimple>> {
-        let url = format!("{}/api/v3/series?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("series", &[]);
         
         self.http.get_json(&url).await
     }
 
+    /// Of the `ids` we believe were just added, return those Sonarr does not
+    /// actually have
+    #[instrument(skip(self, ids))]
+    pub async fn missing_series_ids(&self, ids: &[i64]) -> Result<Vec<i64>> {
+        let existing: HashSet<i64> = self.get_series().await?.into_iter().filter_map(|e| e.id).collect();
+        Ok(ids.iter().copied().filter(|id| !existing.contains(id)).collect())
+    }
+
+    /// Which id (if any) matches a series already in Sonarr
+    async fn find_existing(&self, tvdb_id: Option<i32>, tmdb_id: Option<i32>, imdb_ids: &[&str]) -> Result<Option<ExistingMatch>> {
+        let mut existing = self.existing.lock().await;
+        if existing.is_none() {
+            let mut series = LibraryIds::default();
+            for s in self.get_series().await? {
+                series.insert(s.id, s.tvdb_id, s.tmdb_id, s.imdb_id.as_deref());
+            }
+            debug!("Loaded {} existing series from Sonarr", series.len());
+            *existing = Some(series);
+        }
+
+        Ok(existing.as_ref().and_then(|existing| existing.matching(tvdb_id, tmdb_id, imdb_ids)))
+    }
+
     #[instrument(skip(self))]
-    async fn lookup_series(&self, title: &str, year: Option<i32>) -> Result<SonarrLookupResult> {
+    async fn lookup_series(&self, title: &str, year: Option<i32>, tvdb_id: Option<i32>, tmdb_id: Option<i32>) -> Result<std::result::Result<SonarrLookupResult, SkipReason>> {
+        // An exact id lookup avoids picking the wrong show for common names
+        if let Some(tvdb_id) = tvdb_id {
+            if let Some(result) = self.lookup_series_by_id("tvdb", tvdb_id).await? {
+                return Ok(Ok(result));
+            }
+        } else if let Some(tmdb_id) = tmdb_id.filter(|_| self.config.tmdb_lookup.unwrap_or(true)) {
+            // Sonarr adds by TVDB id, which the tmdb: match carries
+            match self.lookup_series_by_id("tmdb", tmdb_id).await? {
+                Some(result) if result.tvdb_id.is_some() => {
+                    info!("Resolved TMDB id {} to TVDB id {:?}", tmdb_id, result.tvdb_id);
+                    return Ok(Ok(result));
+                }
+                Some(_) => debug!("Series for TMDB id {} has no TVDB id, falling back to title search", tmdb_id),
+                None => {}
+            }
+        }
+
         let search_term = if let Some(year) = year {
             format!("{} {}", title, year)
         } else {
             title.to_string()
         };
         
-        let url = format!("{}/api/v3/series/lookup?term={}&apikey={}", 
-                         self.config.base_url, 
-                         urlencoding::encode(&search_term),
-                         self.config.api_key);
+        let url = self.api().build_url("series/lookup", &[("term", &search_term)]);
         
         info!("Looking up series: {}", search_term);
         
         let results: Vec<SonarrLookupResult> = self.http.get_json(&url).await?;
         
-        if let Some(result) = results.first() {
-            info!("Found series: {} (TVDB: {:?}, TMDB: {:?})", result.title, result.tvdb_id, result.tmdb_id);
-            Ok(result.clone())
-        } else {
-            Err(anyhow::anyhow!("Series not found in lookup: {}", search_term))
+        if results.is_empty() {
+            warn!("Series not found in lookup: {}", search_term);
+            return Ok(Err(SkipReason::NotFound));
+        }
+
+        let result = match arr::pick_lookup_result(&results, title, year, self.config.ambiguous_match.unwrap_or_default()) {
+            Ok(result) => result,
+            Err(reason) => return Ok(Err(reason)),
+        };
+
+        info!("Found series: {} (TVDB: {:?}, TMDB: {:?})", result.title, result.tvdb_id, result.tmdb_id);
+        Ok(Ok(result.clone()))
+    }
+
+    /// Lookup by `<source>:<id>`, `None` when Sonarr has no match
+    async fn lookup_series_by_id(&self, source: &str, id: i32) -> Result<Option<SonarrLookupResult>> {
+        let url = self.api().build_url("series/lookup", &[("term", &format!("{}:{}", source, id))]);
+
+        info!("Looking up series by {} id: {}", source.to_uppercase(), id);
+
+        let results: Vec<SonarrLookupResult> = self.http.get_json(&url).await?;
+        match results.into_iter().next() {
+            Some(result) => {
+                info!("Found series: {} (TVDB: {:?}, TMDB: {:?})", result.title, result.tvdb_id, result.tmdb_id);
+                Ok(Some(result))
+            }
+            None => {
+                debug!("No series for {} id {}, falling back to title search", source.to_uppercase(), id);
+                Ok(None)
+            }
         }
     }
 
     #[instrument(skip(self, item))]
-    pub async fn add_series(&self, item: &Item) -> Result<()> {
+    pub async fn add_series(&self, item: &Item) -> Result<AddOutcome> {
         if item.item_type != ItemType::Show {
             warn!("Attempted to add non-show item to Sonarr: {}", item.title);
-            return Ok(());
+            return Ok(AddOutcome::Skipped(SkipReason::WrongType));
         }
 
         info!("Adding series to Sonarr: {}", item.title);
 
         // First, lookup the series to get TVDB/TMDB ID and other metadata
-        let lookup_result = self.lookup_series(&item.title, item.year).await?;
+        let mut lookup = self.lookup_series(&item.title, item.year, item.tvdb_id, item.tmdb_id).await?;
+        for pattern in self.config.strip_patterns.iter().flatten() {
+            if !matches!(lookup, Err(SkipReason::NotFound)) {
+                break;
+            }
+            if let Some(stripped) = arr::strip_title_pattern(&item.title, pattern) {
+                info!("Retrying lookup for '{}' as '{}' (stripPatterns: '{}')", item.title, stripped, pattern);
+                lookup = self.lookup_series(&stripped, item.year, None, None).await?;
+            }
+        }
+        let lookup_result = match lookup {
+            Ok(lookup_result) => lookup_result,
+            Err(reason) => return Ok(AddOutcome::Skipped(reason)),
+        };
 
-        // Check if series already exists in Sonarr
-        let existing_series = self.get_series().await?;
-        
-        // Check for duplicates using both TVDB and TMDB IDs
-        if let Some(tvdb_id) = lookup_result.tvdb_id {
-            if existing_series.iter().any(|s| s.tvdb_id == Some(tvdb_id)) {
-                info!("Series '{}' (TVDB: {}) already exists in Sonarr, skipping", lookup_result.title, tvdb_id);
-                return Ok(());
+        if arr::is_adult(&lookup_result.extra_fields) && !self.config.allow_adult_content.unwrap_or(false) {
+            info!("Skipping adult series '{}' (allowAdultContent is disabled)", lookup_result.title);
+            return Ok(AddOutcome::Skipped(SkipReason::Filtered("adult content".to_string())));
+        }
+
+        if let Some(min_seasons) = self.config.min_seasons {
+            match lookup_result.season_count() {
+                Some(seasons) if seasons < min_seasons => {
+                    info!("Skipping series '{}', {} seasons is under minSeasons ({})", lookup_result.title, seasons, min_seasons);
+                    return Ok(AddOutcome::Skipped(SkipReason::Filtered(format!("fewer than {} seasons", min_seasons))));
+                }
+                Some(_) => {}
+                None => debug!("No season list for '{}', not applying minSeasons", lookup_result.title),
             }
         }
-        
-        if let Some(tmdb_id) = lookup_result.tmdb_id {
-            if existing_series.iter().any(|s| s.tmdb_id == Some(tmdb_id)) {
-                info!("Series '{}' (TMDB: {}) already exists in Sonarr, skipping", lookup_result.title, tmdb_id);
-                return Ok(());
+
+        if let Some(min_episodes) = self.config.min_episodes {
+            match lookup_result.episode_count() {
+                Some(episodes) if episodes < min_episodes => {
+                    info!("Skipping series '{}', {} episodes is under minEpisodes ({})", lookup_result.title, episodes, min_episodes);
+                    return Ok(AddOutcome::Skipped(SkipReason::Filtered(format!("fewer than {} episodes", min_episodes))));
+                }
+                Some(_) => {}
+                None => debug!("No episode count for '{}', not applying minEpisodes", lookup_result.title),
             }
         }
 
+        // Check for duplicates using TVDB, TMDB and IMDb IDs. Sonarr can also
+        // track a series by IMDb id alone.
+        let imdb_ids: Vec<&str> = [item.imdb_id.as_deref(), lookup_result.imdb_id.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if let Some(matched) = self.find_existing(lookup_result.tvdb_id, lookup_result.tmdb_id, &imdb_ids).await? {
+            if let (true, Some(id), Some(profile)) = (self.config.update_existing_profile.unwrap_or(false), matched.id, &self.config.quality_profile) {
+                self.api().update_quality_profile("series", id, profile, &lookup_result.title).await?;
+            }
+            info!("Series '{}' ({}) already exists in Sonarr, skipping", lookup_result.title, matched);
+            return Ok(AddOutcome::Skipped(SkipReason::AlreadyExists));
+        }
+
         let quality_profiles = self.get_quality_profiles().await?;
         let root_folders = self.get_root_folders().await?;
         
@@ -171,26 +356,58 @@ impl SonarrClient {
             quality_profiles.first().map(|p| p.id).unwrap_or(1)
         };
 
-        let root_folder_path = if let Some(ref folder) = self.config.root_folder {
+        let root_folder_path = if let Some(ref template) = self.config.root_folder_template {
+            let values = TemplateValues {
+                title: &lookup_result.title,
+                year: lookup_result.year,
+                genre: arr::first_genre(&lookup_result.extra_fields),
+            };
+            let allow_any = self.config.allow_any_root_folder.unwrap_or(false);
+            arr::templated_root_folder("Sonarr", template, &values, &root_folders, allow_any)?
+        } else if let Some(ref folder) = self.config.root_folder {
             folder.clone()
         } else {
-            root_folders
-                .first()
-                .map(|f| f.path.clone())
-                .unwrap_or_else(|| "/tv".to_string())
+            arr::fallback_root_folder("Sonarr", &root_folders, self.config.default_show_root.as_ref(), "defaultShowRoot", &item.title)?
         };
 
-        let tag_ids = if let Some(ref tags) = self.config.tags {
-            self.resolve_tag_ids(tags).await.unwrap_or_default()
+        let mut tag_ids = if let Some(ref tags) = self.config.tags {
+            self.api().resolve_tag_ids(tags).await.unwrap_or_default()
         } else {
             Vec::new()
         };
 
+        if self.config.mirror_plex_labels.unwrap_or(false) && !item.labels.is_empty() {
+            match self.api().ensure_tag_ids(&item.labels).await {
+                Ok(label_tag_ids) => {
+                    tag_ids.extend(label_tag_ids);
+                    tag_ids.sort_unstable();
+                    tag_ids.dedup();
+                }
+                Err(e) => warn!("Failed to mirror Plex labels {:?} as tags: {}", item.labels, e),
+            }
+        }
+
         info!("Using quality profile ID: {}, root folder: {}", quality_profile_id, root_folder_path);
 
+        // Explicit season numbers take precedence over the monitor enum
+        let seasons = self.build_seasons(&lookup_result.extra_fields);
+        let monitor = if seasons.is_some() {
+            "none".to_string()
+        } else {
+            self.config.season_monitoring.clone().unwrap_or_else(|| "all".to_string())
+        };
+
+        // With nonBlockingAdd the add skips the search (which can hold the response)
+        // and the search is queued as a separate command afterwards
+        let non_blocking_add = self.config.non_blocking_add.unwrap_or(false);
+
         let series = SonarrSeries {
             title: lookup_result.title.clone(),
-            sort_title: lookup_result.sort_title,
+            sort_title: if self.config.normalize_sort_title.unwrap_or(false) {
+                arr::normalized_sort_title(&lookup_result.title)
+            } else {
+                lookup_result.sort_title
+            },
             year: lookup_result.year.unwrap_or(0),
             tvdb_id: lookup_result.tvdb_id,
             imdb_id: lookup_result.imdb_id,
@@ -198,20 +415,42 @@ impl SonarrClient {
             quality_profile_id,
             root_folder_path,
             add_options: SonarrAddOptions {
-                monitor: self.config.season_monitoring.clone().unwrap_or_else(|| "all".to_string()),
-                search_for_missing_episodes: true,
+                monitor,
+                search_for_missing_episodes: !non_blocking_add,
             },
             monitored: true,
             tags: tag_ids,
+            seasons,
+            season_folder: self.config.season_folder,
         };
 
-        let url = format!("{}/api/v3/series?apikey={}", 
-                         self.config.base_url, self.config.api_key);
+        let url = self.api().build_url("series", &[]);
         
-        match self.http.post_json::<serde_json::Value, _>(&url, &series).await {
-            Ok(_) => {
+        let payload = arr::build_payload(&series, &lookup_result.extra_fields, self.config.payload_mode.unwrap_or_default(), self.config.extra_add_fields.as_ref())?;
+
+        match self.http.post_json::<serde_json::Value, _>(&url, &payload).await {
+            Ok(added) => {
                 info!("Successfully added series: {}", lookup_result.title);
-                Ok(())
+                let id = added.get("id").and_then(|id| id.as_i64());
+                if let Some(existing) = self.existing.lock().await.as_mut() {
+                    existing.insert(id, series.tvdb_id, series.tmdb_id, series.imdb_id.as_deref());
+                }
+                if non_blocking_add {
+                    match id {
+                        Some(id) => self.api().queue_search(serde_json::json!({ "name": "SeriesSearch", "seriesId": id })).await,
+                        None => warn!("Added series '{}' has no id, cannot queue search", lookup_result.title),
+                    }
+                }
+                Ok(AddOutcome::Added(id))
+            }
+            // The library check matches on ids from our lookup; Sonarr can
+            // still know the series under another id and reject the add
+            Err(e) if is_series_exists_error(&e) => {
+                info!("Series '{}' already exists in Sonarr (reported on add), skipping", lookup_result.title);
+                if let Some(existing) = self.existing.lock().await.as_mut() {
+                    existing.insert(None, series.tvdb_id, series.tmdb_id, series.imdb_id.as_deref());
+                }
+                Ok(AddOutcome::Skipped(SkipReason::AlreadyExists))
             }
             Err(e) => {
                 error!("Failed to add series '{}': {}", lookup_result.title, e);
@@ -220,11 +459,313 @@ impl SonarrClient {
         }
     }
 
-    async fn resolve_tag_ids(&self, tag_names: &[String]) -> Result<Vec<i32>> {
-        let tags = self.get_tags().await?;
-        Ok(tag_names
-            .iter()
-            .filter_map(|name| tags.iter().find(|t| t.label == *name).map(|t| t.id))
-            .collect())
+    /// Seasons from the lookup with only the configured `monitorSeasons`
+    /// monitored. `None` leaves the seasons to Sonarr, which an empty list
+    /// would not.
+    fn build_seasons(&self, lookup: &serde_json::Value) -> Option<Vec<SonarrSeason>> {
+        let monitor_seasons = self.config.monitor_seasons.as_ref()?;
+
+        let seasons: Vec<SonarrSeason> = lookup
+            .get("seasons")
+            .and_then(|v| v.as_array())
+            .map(|seasons| {
+                seasons
+                    .iter()
+                    .filter_map(|season| season.get("seasonNumber").and_then(|n| n.as_i64()))
+                    .map(|number| SonarrSeason {
+                        season_number: number as i32,
+                        monitored: monitor_seasons.contains(&(number as i32)),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if seasons.is_empty() {
+            warn!("Lookup returned no seasons, monitorSeasons {:?} cannot be applied", monitor_seasons);
+            return None;
+        }
+
+        Some(seasons)
+    }
+}
+
+/// Whether an add failed Sonarr's "series already added" validation, which
+/// comes back as a 400 with a list of validation failures
+fn is_series_exists_error(error: &anyhow::Error) -> bool {
+    if !HttpStatusError::is_status(error, StatusCode::BAD_REQUEST) {
+        return false;
+    }
+    let Some(failures) = HttpStatusError::body_of(error)
+        .and_then(|body| serde_json::from_str::<Vec<serde_json::Value>>(body).ok())
+    else {
+        return false;
+    };
+
+    failures.iter().any(|failure| {
+        failure.get("errorCode").and_then(|c| c.as_str()) == Some("SeriesExistsValidator")
+            || failure
+                .get("errorMessage")
+                .and_then(|m| m.as_str())
+                .is_some_and(|m| m.contains("already been added"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, Arr};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn monitor_seasons_monitors_only_the_listed_seasons() {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([{
+                "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126,
+                "seasons": [{ "seasonNumber": 0 }, { "seasonNumber": 1 }, { "seasonNumber": 2 }]
+            }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let config = test_support::sonarr_config(&base_url, "monitorSeasons: [1]\n");
+        let client = SonarrClient::new(test_support::http_client(), config);
+
+        client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes[0].body["addOptions"]["monitor"], "none");
+        assert_eq!(
+            writes[0].body["seasons"],
+            json!([
+                { "seasonNumber": 0, "monitored": false },
+                { "seasonNumber": 1, "monitored": true },
+                { "seasonNumber": 2, "monitored": false }
+            ])
+        );
+    }
+
+    /// Two "Shameless" shows from 2011 the year can't tell apart, behind a
+    /// differently titled first result
+    async fn year_tie(extra_config: &str) -> (SonarrClient, test_support::Writes) {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([
+                { "title": "Shameless (US)", "sortTitle": "shameless us", "year": 2011, "tvdbId": 161511, "ratings": { "votes": 900 } },
+                { "title": "Shameless", "sortTitle": "shameless", "year": 2011, "tvdbId": 100, "ratings": { "votes": 40 } },
+                { "title": "Shameless", "sortTitle": "shameless", "year": 2011, "tvdbId": 200, "ratings": { "votes": 700 } },
+            ]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, extra_config));
+        (client, writes)
+    }
+
+    fn added_tvdb_ids(writes: &test_support::Writes) -> Vec<serde_json::Value> {
+        writes.lock().unwrap().iter().filter(|w| w.path == "/series").map(|w| w.body["tvdbId"].clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn a_year_tie_follows_the_ambiguous_match_policy() {
+        let show = test_support::item(ItemType::Show, "Shameless", 2011);
+
+        let (client, writes) = year_tie("").await;
+        assert_eq!(client.add_series(&show).await.unwrap(), AddOutcome::Skipped(SkipReason::Ambiguous));
+        assert!(added_tvdb_ids(&writes).is_empty());
+
+        let (client, writes) = year_tie("ambiguousMatch: first").await;
+        assert!(matches!(client.add_series(&show).await.unwrap(), AddOutcome::Added(_)));
+        assert_eq!(added_tvdb_ids(&writes), [100]);
+
+        let (client, writes) = year_tie("ambiguousMatch: most_popular").await;
+        assert!(matches!(client.add_series(&show).await.unwrap(), AddOutcome::Added(_)));
+        assert_eq!(added_tvdb_ids(&writes), [200]);
+
+        // Neither "Shameless" is from 2004
+        let (client, writes) = year_tie("ambiguousMatch: first").await;
+        let show = test_support::item(ItemType::Show, "Shameless", 2004);
+        assert_eq!(client.add_series(&show).await.unwrap(), AddOutcome::Skipped(SkipReason::Ambiguous));
+        assert!(added_tvdb_ids(&writes).is_empty());
+    }
+
+    /// Sonarr that only knows Game of Thrones by its `tmdb:` term
+    async fn tmdb_only(extra_config: &str) -> (SonarrClient, test_support::Writes) {
+        let (router, writes) = test_support::arr("series", Arr {
+            term_lookups: [(
+                "tmdb:1399".to_string(),
+                json!([{ "title": "Game of Thrones", "sortTitle": "game of thrones", "year": 2011, "tvdbId": 121361, "tmdbId": 1399 }]),
+            )]
+            .into(),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, extra_config));
+        (client, writes)
+    }
+
+    #[tokio::test]
+    async fn tmdb_only_show_is_added_by_the_tvdb_id_its_tmdb_lookup_carries() {
+        let mut show = test_support::item(ItemType::Show, "Game of Thrones", 2011);
+        show.tmdb_id = Some(1399);
+
+        let (client, writes) = tmdb_only("").await;
+        assert!(matches!(client.add_series(&show).await.unwrap(), AddOutcome::Added(_)));
+        assert_eq!(writes.lock().unwrap()[0].body["tvdbId"], 121361);
+
+        let (client, writes) = tmdb_only("tmdbLookup: false").await;
+        assert_eq!(client.add_series(&show).await.unwrap(), AddOutcome::Skipped(SkipReason::NotFound));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn monitor_seasons_without_a_season_list_leaves_seasons_to_sonarr() {
+        let config = test_support::sonarr_config("http://127.0.0.1:1", "monitorSeasons: [1]");
+        let client = SonarrClient::new(test_support::http_client(), config);
+
+        assert!(client.build_seasons(&json!({})).is_none());
+        assert!(client.build_seasons(&json!({ "seasons": [] })).is_none());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn series_tracked_only_by_imdb_id_are_skipped_as_existing() {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "imdbId": "tt0306414" }]),
+            existing: json!([{ "id": 4, "imdbId": "TT0306414" }]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, ""));
+
+        let outcome = client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+
+        assert_eq!(outcome, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn shows_with_a_tvdb_id_are_looked_up_by_the_tvdb_term() {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 1 }]),
+            term_lookups: [(
+                "tvdb:79126".to_string(),
+                json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 }]),
+            )]
+            .into(),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, ""));
+        let mut show = test_support::item(ItemType::Show, "The Wire", 2002);
+        show.tvdb_id = Some(79126);
+
+        client.add_series(&show).await.unwrap();
+
+        assert_eq!(writes.lock().unwrap()[0].body["tvdbId"], 79126);
+    }
+
+    async fn filtered_series(lookup: serde_json::Value, config: &str) -> (AddOutcome, usize) {
+        let (router, writes) = test_support::arr("series", Arr { lookup: json!([lookup]), ..Arr::default() });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, config));
+
+        let outcome = client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+        let writes = writes.lock().unwrap().len();
+        (outcome, writes)
+    }
+
+    #[tokio::test]
+    async fn series_under_min_seasons_or_min_episodes_are_filtered() {
+        let series = json!({
+            "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126,
+            "seasons": [{ "seasonNumber": 0 }, { "seasonNumber": 1 }],
+            "statistics": { "totalEpisodeCount": 13 }
+        });
+
+        let (outcome, writes) = filtered_series(series.clone(), "minSeasons: 2\n").await;
+        assert_eq!((outcome, writes), (AddOutcome::Skipped(SkipReason::Filtered("fewer than 2 seasons".to_string())), 0));
+
+        let (outcome, writes) = filtered_series(series.clone(), "minEpisodes: 20\n").await;
+        assert_eq!((outcome, writes), (AddOutcome::Skipped(SkipReason::Filtered("fewer than 20 episodes".to_string())), 0));
+
+        let (outcome, writes) = filtered_series(series, "minSeasons: 1\nminEpisodes: 13\n").await;
+        assert_eq!((outcome, writes), (AddOutcome::Added(Some(1)), 1));
+    }
+
+    #[tokio::test]
+    async fn series_without_season_or_episode_data_are_not_filtered() {
+        let series = json!({ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 });
+
+        let (outcome, writes) = filtered_series(series, "minSeasons: 2\nminEpisodes: 20\n").await;
+
+        assert_eq!((outcome, writes), (AddOutcome::Added(Some(1)), 1));
+    }
+
+    #[tokio::test]
+    async fn season_folder_is_sent_only_when_configured() {
+        let series = json!({ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 });
+
+        for (config, expected) in [("seasonFolder: true\n", Some(true)), ("seasonFolder: false\n", Some(false)), ("", None)] {
+            let (router, writes) = test_support::arr("series", Arr { lookup: json!([series]), ..Arr::default() });
+            let base_url = test_support::serve(router).await;
+            let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, config));
+
+            client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap();
+
+            let writes = writes.lock().unwrap();
+            assert_eq!(writes[0].body.get("seasonFolder").and_then(|v| v.as_bool()), expected, "{:?}", config);
+        }
+    }
+
+    #[tokio::test]
+    async fn sonarrs_series_exists_validation_error_is_already_exists() {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 }]),
+            add_rejection: Some(json!([{
+                "propertyName": "TvdbId",
+                "errorMessage": "This series has already been added",
+                "attemptedValue": 79126,
+                "severity": "error",
+                "errorCode": "SeriesExistsValidator",
+                "formattedMessagePlaceholderValues": { "propertyName": "Tvdb Id", "propertyValue": 79126 }
+            }])),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, ""));
+        let item = test_support::item(ItemType::Show, "The Wire", 2002);
+
+        let first = client.add_series(&item).await.unwrap();
+        let second = client.add_series(&item).await.unwrap();
+
+        assert_eq!(first, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert_eq!(second, AddOutcome::Skipped(SkipReason::AlreadyExists));
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn other_add_validation_errors_still_fail() {
+        let (router, _) = test_support::arr("series", Arr {
+            lookup: json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 }]),
+            add_rejection: Some(json!([{ "propertyName": "RootFolderPath", "errorMessage": "Folder is not writable", "errorCode": "FolderWritableValidator" }])),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, ""));
+
+        assert!(client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn without_any_root_folder_the_add_fails_instead_of_guessing_one() {
+        let (router, writes) = test_support::arr("series", Arr {
+            lookup: json!([{ "title": "The Wire", "sortTitle": "wire", "year": 2002, "tvdbId": 79126 }]),
+            root_folders: json!([]),
+            ..Arr::default()
+        });
+        let base_url = test_support::serve(router).await;
+        let client = SonarrClient::new(test_support::http_client(), test_support::sonarr_config(&base_url, ""));
+
+        let err = client.add_series(&test_support::item(ItemType::Show, "The Wire", 2002)).await.unwrap_err();
+
+        assert!(err.to_string().contains("Sonarr has no root folders and neither rootFolder nor defaultShowRoot is set"), "{}", err);
+        assert!(writes.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::models::{ItemType, SkipReason, WatchlistItem};
+use crate::plex::PlexFriend;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Notify, OwnedMutexGuard};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedItem {
+    pub title: String,
+    pub item_type: ItemType,
+    pub user: String,
+    pub reason: SkipReason,
+    pub skipped_at: DateTime<Utc>,
+}
+
+impl SkippedItem {
+    pub fn new(watchlist_item: &WatchlistItem, reason: SkipReason) -> Self {
+        Self {
+            title: watchlist_item.item.title.clone(),
+            item_type: watchlist_item.item.item_type.clone(),
+            user: watchlist_item.user_id.clone(),
+            reason,
+            skipped_at: Utc::now(),
+        }
+    }
+}
+
+/// Outcome counts of one sync run, for logging and programmatic callers
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub added: usize,
+    /// Skipped for any reason other than not being found
+    pub skipped: usize,
+    pub not_found: usize,
+    pub failed: usize,
+    pub deleted: usize,
+    /// Left for the next sync once the cycle time budget ran out
+    pub deferred: usize,
+    pub duration: Duration,
+}
+
+/// Result of the most recent reachability check of one backend
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceHealth {
+    pub up: bool,
+    /// Version the backend reported, when it was reachable
+    pub version: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct StatusInner {
+    skipped: Vec<SkippedItem>,
+    plex_token_valid: Option<bool>,
+    consecutive_skipped_ticks: u32,
+    consecutive_failures: u32,
+    /// Scheduled syncs are held off until this point after repeated failures
+    backoff_until: Option<Instant>,
+    /// Item ids a backend lookup could not find, and when
+    not_found: HashMap<String, Instant>,
+    /// When a sync last finished an item or a scheduled tick
+    last_progress: Option<Instant>,
+    /// Plex friends list, and when it was fetched
+    friends: Option<(Instant, Vec<PlexFriend>)>,
+    /// Keyed by service name ("radarr", "sonarr", "overseerr")
+    services: BTreeMap<String, ServiceHealth>,
+}
+
+/// In-memory view of the most recent sync cycle, shared with the status server
+#[derive(Debug, Clone, Default)]
+pub struct SyncStatus {
+    inner: Arc<RwLock<StatusInner>>,
+    /// Held for the duration of a sync so RSS and full syncs never overlap
+    sync_lock: Arc<Mutex<()>>,
+    /// Woken on every `record_progress`
+    progress: Arc<Notify>,
+}
+
+impl SyncStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the skipped items with those from the cycle that just finished
+    pub fn record_skipped(&self, skipped: Vec<SkippedItem>) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.skipped = skipped;
+    }
+
+    pub fn skipped(&self) -> Vec<SkippedItem> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.skipped.clone()
+    }
+
+    pub fn set_plex_token_valid(&self, valid: bool) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.plex_token_valid = Some(valid);
+    }
+
+    /// `None` until the first token ping has completed
+    pub fn plex_token_valid(&self) -> Option<bool> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.plex_token_valid
+    }
+
+    /// Record a backend's pre-flight result: its version when up, `None` when down
+    pub fn record_service(&self, service: &str, version: Option<String>) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        let health = ServiceHealth {
+            up: version.is_some(),
+            version,
+            checked_at: Utc::now(),
+        };
+        inner.services.insert(service.to_string(), health);
+    }
+
+    pub fn services(&self) -> BTreeMap<String, ServiceHealth> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.services.clone()
+    }
+
+    /// Claim the sync lock, or `None` when another sync is still running
+    pub fn try_begin_sync(&self) -> Option<OwnedMutexGuard<()>> {
+        Arc::clone(&self.sync_lock).try_lock_owned().ok()
+    }
+
+    /// Count ticks lost to a still-running sync, returning the consecutive total
+    pub fn record_skipped_ticks(&self, ticks: u32) -> u32 {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.consecutive_skipped_ticks += ticks;
+        inner.consecutive_skipped_ticks
+    }
+
+    pub fn reset_skipped_ticks(&self) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.consecutive_skipped_ticks = 0;
+    }
+
+    /// Count a failed sync, returning the consecutive total
+    pub fn record_sync_failure(&self) -> u32 {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.consecutive_failures += 1;
+        inner.consecutive_failures
+    }
+
+    /// Hold off scheduled syncs for `delay`
+    pub fn back_off(&self, delay: Duration) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.backoff_until = Some(Instant::now() + delay);
+    }
+
+    pub fn record_sync_success(&self) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.consecutive_failures = 0;
+        inner.backoff_until = None;
+    }
+
+    /// Time left before scheduled syncs may run again, if backing off
+    pub fn backoff_remaining(&self) -> Option<Duration> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner
+            .backoff_until
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Remember that a lookup for `item_id` found nothing
+    pub fn record_not_found(&self, item_id: &str) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.not_found.insert(item_id.to_string(), Instant::now());
+    }
+
+    /// Remember the Plex friends list that was just fetched
+    pub fn record_friends(&self, friends: Vec<PlexFriend>) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.friends = Some((Instant::now(), friends));
+    }
+
+    /// The Plex friends list, if it was fetched within `ttl`
+    pub fn friends_within(&self, ttl: Duration) -> Option<Vec<PlexFriend>> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        let (fetched_at, friends) = inner.friends.as_ref()?;
+        (fetched_at.elapsed() < ttl).then(|| friends.clone())
+    }
+
+    /// How long ago `item_id` was not found, if that is within `cooldown`.
+    /// Expired entries are dropped so the item gets looked up again.
+    pub fn not_found_within(&self, item_id: &str, cooldown: Duration) -> Option<Duration> {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        let age = inner.not_found.get(item_id)?.elapsed();
+        if age < cooldown {
+            Some(age)
+        } else {
+            inner.not_found.remove(item_id);
+            None
+        }
+    }
+
+    /// Note that syncing moved forward, which keeps the systemd watchdog fed
+    pub fn record_progress(&self) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.last_progress = Some(Instant::now());
+        drop(inner);
+        self.progress.notify_waiters();
+    }
+
+    /// `None` until a sync has made progress
+    pub fn last_progress(&self) -> Option<Instant> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.last_progress
+    }
+
+    /// Wait until a sync has made progress, returning straight away if one has
+    pub async fn first_progress(&self) {
+        loop {
+            // Created before the check so a wake-up in between isn't lost
+            let progressed = self.progress.notified();
+            if self.last_progress().is_some() {
+                return;
+            }
+            progressed.await;
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! systemd readiness and watchdog notifications. Everything here is a no-op
+//! unless systemd started the process with `NOTIFY_SOCKET` set (Type=notify).
+
+use crate::status::SyncStatus;
+use anyhow::Result;
+use sd_notify::NotifyState;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// A message for the service manager
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// The service is up, with a human readable status
+    Ready(String),
+    /// Keep-alive for the watchdog
+    Watchdog,
+}
+
+/// Where readiness and watchdog messages go: systemd in production, a
+/// recorder in tests
+pub trait Notifier: Send + Sync + 'static {
+    fn send(&self, message: &Message) -> std::io::Result<()>;
+
+    /// How often the service manager expects a watchdog ping, if at all
+    fn watchdog_timeout(&self) -> Option<Duration>;
+}
+
+/// The notify socket systemd passes in `NOTIFY_SOCKET`
+pub struct Systemd;
+
+impl Notifier for Systemd {
+    fn send(&self, message: &Message) -> std::io::Result<()> {
+        if std::env::var_os("NOTIFY_SOCKET").is_none() {
+            return Ok(());
+        }
+        match message {
+            Message::Ready(status) => sd_notify::notify(false, &[NotifyState::Ready, NotifyState::Status(status)]),
+            Message::Watchdog => sd_notify::notify(false, &[NotifyState::Watchdog]),
+        }
+    }
+
+    fn watchdog_timeout(&self) -> Option<Duration> {
+        let mut usec = 0;
+        sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+    }
+}
+
+/// Report ready once the first sync has made progress (straight away when
+/// nothing is `scheduled`), then ping the watchdog at half its timeout, but
+/// only while syncs keep making progress. A sync stuck on a hung request
+/// stops the pings, so systemd restarts us instead of waiting forever.
+pub async fn supervise(notifier: impl Notifier, status: SyncStatus, scheduled: bool) -> Result<()> {
+    if scheduled {
+        status.first_progress().await;
+    }
+    let ready = if scheduled { "First sync completed" } else { "Running, scheduled syncs disabled" };
+    match notifier.send(&Message::Ready(ready.to_string())) {
+        Ok(()) => info!("Notified systemd that the service is ready"),
+        Err(e) => warn!("Failed to notify systemd of readiness: {}", e),
+    }
+
+    let Some(timeout) = notifier.watchdog_timeout() else {
+        return Ok(());
+    };
+    let period = timeout / 2;
+    info!("systemd watchdog enabled, pinging every {}s while syncs make progress", period.as_secs_f64());
+
+    let mut last_check = Instant::now();
+    loop {
+        sleep(period).await;
+
+        let progressed = !scheduled || status.last_progress().is_some_and(|at| at >= last_check);
+        last_check = Instant::now();
+        if !progressed {
+            warn!("No sync progress in the last {}s, withholding the systemd watchdog ping", period.as_secs_f64());
+            continue;
+        }
+
+        match notifier.send(&Message::Watchdog) {
+            Ok(()) => debug!("Pinged systemd watchdog"),
+            Err(e) => warn!("Failed to ping systemd watchdog: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records what it is sent instead of talking to systemd
+    #[derive(Clone, Default)]
+    struct Recorder {
+        sent: Arc<Mutex<Vec<Message>>>,
+        watchdog: Option<Duration>,
+    }
+
+    impl Notifier for Recorder {
+        fn send(&self, message: &Message) -> std::io::Result<()> {
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+
+        fn watchdog_timeout(&self) -> Option<Duration> {
+            self.watchdog
+        }
+    }
+
+    impl Recorder {
+        fn sent(&self) -> Vec<Message> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn ready_waits_for_the_first_sync_and_pings_follow_progress() {
+        let recorder = Recorder { watchdog: Some(Duration::from_millis(400)), ..Recorder::default() };
+        let status = SyncStatus::new();
+        let task = tokio::spawn(supervise(recorder.clone(), status.clone(), true));
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(recorder.sent().is_empty(), "ready before any sync: {:?}", recorder.sent());
+
+        status.record_progress();
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(recorder.sent(), [Message::Ready("First sync completed".to_string())]);
+
+        // No progress since ready, so the first watchdog period passes unpinged
+        sleep(Duration::from_millis(250)).await;
+        assert_eq!(recorder.sent().len(), 1, "{:?}", recorder.sent());
+
+        status.record_progress();
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(recorder.sent().last(), Some(&Message::Watchdog));
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn without_scheduled_syncs_ready_is_immediate_and_pings_are_unconditional() {
+        let recorder = Recorder { watchdog: Some(Duration::from_millis(100)), ..Recorder::default() };
+        let task = tokio::spawn(supervise(recorder.clone(), SyncStatus::new(), false));
+
+        sleep(Duration::from_millis(180)).await;
+        task.abort();
+
+        let sent = recorder.sent();
+        assert_eq!(sent[0], Message::Ready("Running, scheduled syncs disabled".to_string()));
+        assert!(sent[1..].iter().all(|m| *m == Message::Watchdog) && sent.len() >= 3, "{:?}", sent);
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+/// Item metadata a root folder template can refer to
+pub struct TemplateValues<'a> {
+    pub title: &'a str,
+    pub year: Option<i32>,
+    pub genre: Option<&'a str>,
+}
+
+/// Characters that would split or break a path segment on Linux or Windows
+const UNSAFE_PATH_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Expand `{title}`, `{year}`, `{genre}` and `{first_letter}` in `template`.
+/// Values are stripped of path separators so a title can never add a
+/// directory level; unknown placeholders are left as they are.
+pub fn expand_root_folder(template: &str, values: &TemplateValues) -> String {
+    let first_letter = match values.title.chars().find(|c| c.is_alphanumeric()) {
+        Some(c) if c.is_alphabetic() => c.to_uppercase().to_string(),
+        _ => "#".to_string(),
+    };
+    let year = values.year.map(|y| y.to_string()).unwrap_or_default();
+
+    template
+        .replace("{title}", &path_segment(values.title))
+        .replace("{year}", &year)
+        .replace("{genre}", &path_segment(values.genre.unwrap_or("Unknown")))
+        .replace("{first_letter}", &first_letter)
+}
+
+fn path_segment(value: &str) -> String {
+    value.replace(UNSAFE_PATH_CHARS, "").trim().to_string()
+}
+
+/// Whether `path` is one of `root_folders` or inside one of them
+pub fn is_within_root_folders<'a>(path: &str, mut root_folders: impl Iterator<Item = &'a str>) -> bool {
+    let path = Path::new(path);
+    root_folders.any(|root| path.starts_with(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_expand_from_the_item() {
+        let values = TemplateValues { title: "Heat", year: Some(1995), genre: Some("Crime") };
+
+        assert_eq!(expand_root_folder("/media/movies/{first_letter}", &values), "/media/movies/H");
+        assert_eq!(expand_root_folder("/media/{genre}/{title} ({year})", &values), "/media/Crime/Heat (1995)");
+    }
+
+    #[test]
+    fn values_cannot_add_directory_levels_and_missing_ones_fall_back() {
+        let values = TemplateValues { title: "24: Live/Another Day", year: None, genre: None };
+
+        assert_eq!(expand_root_folder("/tv/{first_letter}/{genre}/{title}", &values), "/tv/#/Unknown/24 LiveAnother Day");
+        assert_eq!(expand_root_folder("/tv/{other}", &values), "/tv/{other}");
+    }
+
+    #[test]
+    fn paths_must_sit_inside_a_root_folder() {
+        let roots = ["/media/movies", "/media/kids"];
+
+        assert!(is_within_root_folders("/media/movies/H", roots.into_iter()));
+        assert!(!is_within_root_folders("/media/moviesX/H", roots.into_iter()));
+        assert!(!is_within_root_folders("/elsewhere/H", roots.into_iter()));
+    }
+}
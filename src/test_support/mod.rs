@@ -0,0 +1,375 @@
+//! Local stand-ins for Plex, Sonarr, Radarr and Overseerr, shared by the unit tests
+
+use crate::config::{PlexConfig, RadarrConfig, SonarrConfig};
+use crate::http::{HttpClient, RedirectPolicy, RetryPolicy};
+use crate::models::{Item, ItemType};
+use axum::extract::{Json, Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::Router;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::subscriber::DefaultGuard;
+use tracing::Level;
+
+/// Serve `router` on a free local port, returning its base URL
+pub async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", address)
+}
+
+/// Base URL of a local port nothing listens on
+pub async fn unreachable_url() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{}", address)
+}
+
+/// Client that doesn't retry, so failing stand-ins answer straight away
+pub fn http_client() -> HttpClient {
+    HttpClient::new(RedirectPolicy::default()).with_retry(RetryPolicy { max_attempts: 1, backoff: Duration::ZERO }, None)
+}
+
+/// Plex watchlist XML with one `<Video>` per (rating key, title, year)
+pub fn watchlist_xml(movies: &[(&str, &str, i32)]) -> String {
+    let videos: String = movies
+        .iter()
+        .map(|(key, title, year)| {
+            format!(r#"<Video ratingKey="{key}" type="movie" title="{title}" year="{year}" guid="plex://movie/{key}"/>"#)
+        })
+        .collect();
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?><MediaContainer size="{}">{}</MediaContainer>"#, movies.len(), videos)
+}
+
+/// Plex metadata API serving `xml` as the owner's watchlist
+pub fn plex_metadata(xml: String) -> Router {
+    Router::new().route("/library/sections/watchlist/all", get(move || async move { xml }))
+}
+
+/// Plex metadata API serving a watchlist per account token; any other token
+/// is rejected with a 401
+pub fn plex_metadata_by_token(watchlists: &[(&str, String)]) -> Router {
+    let watchlists: HashMap<String, String> = watchlists.iter().map(|(token, xml)| (token.to_string(), xml.clone())).collect();
+
+    Router::new().route(
+        "/library/sections/watchlist/all",
+        get(move |Query(query): Query<HashMap<String, String>>| async move {
+            match query.get("X-Plex-Token").and_then(|token| watchlists.get(token)) {
+                Some(xml) => xml.clone().into_response(),
+                None => StatusCode::UNAUTHORIZED.into_response(),
+            }
+        }),
+    )
+}
+
+/// Plex Media Server with a movie library holding one item per guid, paged
+/// by `X-Plex-Container-Start` and `X-Plex-Container-Size`, and a music
+/// library that has no route, so reading it fails
+pub fn plex_server(guids: Vec<String>) -> Router {
+    let sections = r#"<MediaContainer size="2">
+        <Directory key="1" type="movie" title="Movies"/>
+        <Directory key="2" type="artist" title="Music"/>
+    </MediaContainer>"#;
+    let page = move |Query(query): Query<HashMap<String, String>>| async move {
+        let param = |name: &str| query.get(name).and_then(|v| v.parse::<usize>().ok());
+        let start = param("X-Plex-Container-Start").unwrap_or(0);
+        let size = param("X-Plex-Container-Size").unwrap_or(guids.len());
+        let videos: String = guids
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(size)
+            .map(|(key, guid)| format!(r#"<Video ratingKey="{key}" type="movie" title="Movie {key}" guid="{guid}"/>"#))
+            .collect();
+        format!(r#"<MediaContainer size="{}">{}</MediaContainer>"#, guids.len().saturating_sub(start).min(size), videos)
+    };
+
+    Router::new()
+        .route("/library/sections", get(move || async move { sections }))
+        .route("/library/sections/1/all", get(page))
+}
+
+/// Every query a Plex community stand-in received: "friends" for the friends
+/// list, otherwise the id of the friend whose watchlist was asked for
+pub type Queries = Arc<Mutex<Vec<String>>>;
+
+/// Plex community GraphQL API with `friends` as (id, username), each with
+/// one movie on their watchlist, except the `failing` ids whose watchlist
+/// requests get a 500
+pub fn plex_community(friends: &[(&str, &str)], failing: &[&str]) -> (Router, Queries) {
+    let friends: Vec<(String, String)> = friends.iter().map(|(id, name)| (id.to_string(), name.to_string())).collect();
+    let failing: Vec<String> = failing.iter().map(|id| id.to_string()).collect();
+    let queries: Queries = Arc::default();
+    let recorded = Arc::clone(&queries);
+
+    let handler = move |Json(request): Json<serde_json::Value>| {
+        let friends = friends.clone();
+        let failing = failing.clone();
+        let recorded = Arc::clone(&recorded);
+        async move {
+            let query = request["query"].as_str().unwrap_or_default();
+            if query.contains("allFriendsV2") {
+                recorded.lock().unwrap().push("friends".to_string());
+                let entries: Vec<_> = friends
+                    .iter()
+                    .map(|(id, username)| serde_json::json!({ "user": { "id": id, "username": username } }))
+                    .collect();
+                return Json(serde_json::json!({ "data": { "allFriendsV2": entries } })).into_response();
+            }
+
+            let uuid = request["variables"]["uuid"].as_str().unwrap_or_default().to_string();
+            recorded.lock().unwrap().push(uuid.clone());
+            if failing.contains(&uuid) {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Json(serde_json::json!({ "data": { "user": { "watchlist": {
+                "nodes": [{ "id": format!("{}-movie", uuid), "title": format!("Movie of {}", uuid), "type": "MOVIE", "year": 2020 }],
+                "pageInfo": { "hasNextPage": false, "endCursor": null }
+            } } } }))
+            .into_response()
+        }
+    };
+
+    (Router::new().route("/", post(handler)), queries)
+}
+
+/// Plex config pointed at local stand-ins for the metadata and community APIs
+pub fn plex_config(metadata_url: &str, community_url: &str) -> PlexConfig {
+    let mut config: PlexConfig = serde_yaml::from_str("token: test-token").unwrap();
+    config.metadata_url = Some(metadata_url.to_string());
+    config.community_url = Some(community_url.to_string());
+    config
+}
+
+/// What a Radarr or Sonarr stand-in answers with
+#[derive(Default)]
+pub struct Arr {
+    /// Body of `<resource>/lookup`; empty when null
+    pub lookup: serde_json::Value,
+    /// Bodies of `<resource>/lookup` for particular terms, ahead of `lookup`
+    pub term_lookups: HashMap<String, serde_json::Value>,
+    /// Body of `GET <resource>`; empty when null
+    pub existing: serde_json::Value,
+    /// Body of `GET tag`; empty when null
+    pub tags: serde_json::Value,
+    /// Body of `GET rootfolder`; a single "/media" folder when null
+    pub root_folders: serde_json::Value,
+    /// How long the lookup takes to answer
+    pub lookup_delay: Duration,
+    /// When set, commands are held until this is notified
+    pub command_gate: Option<Arc<Notify>>,
+    /// Counts `GET <resource>` library reads
+    pub library_reads: Arc<AtomicUsize>,
+    /// Counts `<resource>/lookup` requests
+    pub lookups: Arc<AtomicUsize>,
+    /// When set, adding a `<resource>` is refused with a 400 and this body
+    pub add_rejection: Option<serde_json::Value>,
+}
+
+/// A write the stand-in received
+#[derive(Debug, Clone)]
+pub struct Write {
+    pub path: String,
+    pub body: serde_json::Value,
+    /// X-Request-Id the write was sent with
+    pub request_id: Option<String>,
+}
+
+impl Write {
+    fn new(path: String, headers: &HeaderMap, body: &serde_json::Value) -> Self {
+        let request_id = headers.get("x-request-id").and_then(|v| v.to_str().ok()).map(str::to_string);
+        Self { path, body: body.clone(), request_id }
+    }
+}
+
+pub type Writes = Arc<Mutex<Vec<Write>>>;
+
+/// Radarr (`resource` "movie") or Sonarr ("series") v3 API with one quality
+/// profile and root folder, recording every resource or tag it is asked to
+/// create or update and every command it is sent
+pub fn arr(resource: &str, arr: Arr) -> (Router, Writes) {
+    let writes: Writes = Arc::default();
+    let lookup = if arr.lookup.is_null() { serde_json::json!([]) } else { arr.lookup };
+    let existing = if arr.existing.is_null() { serde_json::json!([]) } else { arr.existing };
+    let tags = if arr.tags.is_null() { serde_json::json!([]) } else { arr.tags };
+    let root_folders = if arr.root_folders.is_null() { serde_json::json!([{ "id": 1, "path": "/media" }]) } else { arr.root_folders };
+    let term_lookups = arr.term_lookups;
+    let lookup_delay = arr.lookup_delay;
+    let command_gate = arr.command_gate;
+    let library_reads = arr.library_reads;
+    let lookups = arr.lookups;
+    let add_rejection = arr.add_rejection;
+
+    // Echo the body back with an id, as Sonarr and Radarr do for created resources
+    let created = |path: String| {
+        let writes = Arc::clone(&writes);
+        move |headers: HeaderMap, Json(mut body): Json<serde_json::Value>| async move {
+            let mut writes = writes.lock().unwrap();
+            writes.push(Write::new(path, &headers, &body));
+            body["id"] = serde_json::json!(writes.len());
+            Json(body)
+        }
+    };
+    let command = {
+        let writes = Arc::clone(&writes);
+        move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+            if let Some(gate) = command_gate {
+                gate.notified().await;
+            }
+            writes.lock().unwrap().push(Write::new("/command".to_string(), &headers, &body));
+            Json(serde_json::json!({ "id": 1 }))
+        }
+    };
+
+    let add = {
+        let create = created(format!("/{}", resource));
+        move |headers: HeaderMap, body: Json<serde_json::Value>| async move {
+            match add_rejection {
+                Some(rejection) => (StatusCode::BAD_REQUEST, Json(rejection)).into_response(),
+                None => create(headers, body).await.into_response(),
+            }
+        }
+    };
+
+    let updated = {
+        let writes = Arc::clone(&writes);
+        let path = format!("/{}", resource);
+        move |Path(id): Path<i64>, headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+            writes.lock().unwrap().push(Write::new(format!("{}/{}", path, id), &headers, &body));
+            Json(body)
+        }
+    };
+    let by_id = {
+        let existing = existing.clone();
+        move |Path(id): Path<i64>| async move {
+            let entry = existing.as_array().and_then(|all| all.iter().find(|e| e["id"] == id).cloned());
+            entry.map(Json).ok_or(StatusCode::NOT_FOUND)
+        }
+    };
+
+    let router = Router::new()
+        .route("/api/v3/system/status", get(|| async { Json(serde_json::json!({ "version": "4.0.0" })) }))
+        .route("/api/v3/qualityprofile", get(|| async { Json(serde_json::json!([{ "id": 1, "name": "Any" }])) }))
+        .route("/api/v3/rootfolder", get(move || async move { Json(root_folders) }))
+        .route("/api/v3/tag", get(move || async move { Json(tags) }).post(created("/tag".to_string())))
+        .route(&format!("/api/v3/{}", resource), get(move || async move {
+            library_reads.fetch_add(1, Ordering::SeqCst);
+            Json(existing)
+        }).post(add))
+        .route(&format!("/api/v3/{}/lookup", resource), get(move |Query(query): Query<HashMap<String, String>>| async move {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(lookup_delay).await;
+            let term = query.get("term").map(String::as_str).unwrap_or_default();
+            Json(term_lookups.get(term).cloned().unwrap_or(lookup))
+        }))
+        .route(&format!("/api/v3/{}/{{id}}", resource), get(by_id).put(updated))
+        .route("/api/v3/command", post(command));
+
+    (router, writes)
+}
+
+/// Overseerr API answering searches with `results` and recording requests,
+/// which it rejects with 409 as already requested when `requested` is set.
+/// Requests without the `x-api-key` header get a 401.
+pub fn overseerr(results: serde_json::Value, requested: bool) -> (Router, Writes) {
+    let writes: Writes = Arc::default();
+    let recorded = Arc::clone(&writes);
+
+    let request = move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+        if headers.get("x-api-key").is_none() {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        recorded.lock().unwrap().push(Write::new("/request".to_string(), &headers, &body));
+        if requested {
+            return (StatusCode::CONFLICT, Json(serde_json::json!({ "message": "Request already exists" }))).into_response();
+        }
+        Json(body).into_response()
+    };
+
+    let router = Router::new()
+        .route("/api/v1/status", get(|| async { Json(serde_json::json!({ "version": "1.33.2" })) }))
+        .route("/api/v1/search", get(move || async move { Json(serde_json::json!({ "results": results })) }))
+        .route("/api/v1/request", post(request));
+
+    (router, writes)
+}
+
+/// Radarr config for a stand-in at `base_url`, plus any `extra` YAML keys
+pub fn radarr_config(base_url: &str, extra: &str) -> RadarrConfig {
+    serde_yaml::from_str(&format!("baseUrl: {}\napikey: test-key\n{}", base_url, extra)).unwrap()
+}
+
+/// Sonarr config for a stand-in at `base_url`, plus any `extra` YAML keys
+pub fn sonarr_config(base_url: &str, extra: &str) -> SonarrConfig {
+    serde_yaml::from_str(&format!("baseUrl: {}\napikey: test-key\n{}", base_url, extra)).unwrap()
+}
+
+/// Watchlist item with only a title, year and type, as if Plex gave no ids
+pub fn item(item_type: ItemType, title: &str, year: i32) -> Item {
+    Item {
+        id: format!("{}-{}", title, year),
+        title: title.to_string(),
+        year: Some(year),
+        item_type,
+        guid: None,
+        imdb_id: None,
+        tmdb_id: None,
+        tvdb_id: None,
+        labels: Vec::new(),
+        original_title: None,
+        genres: Vec::new(),
+    }
+}
+
+/// Path in the temp dir for a file named `name`, unique to this test run
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("watchlistarr-{}-{}", std::process::id(), name))
+}
+
+/// Warnings and errors logged on the current thread while captured
+#[derive(Clone, Default)]
+pub struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    /// Capture until the returned guard is dropped. Tests run on a
+    /// current-thread runtime, so this sees the tasks they spawn too.
+    pub fn capture(&self) -> DefaultGuard {
+        self.capture_at(Level::WARN)
+    }
+
+    /// Like `capture`, but down to `level` rather than just warnings
+    pub fn capture_at(&self, level: Level) -> DefaultGuard {
+        let logs = self.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_ansi(false)
+            .without_time()
+            .with_writer(move || logs.clone())
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let bytes = self.0.lock().unwrap();
+        String::from_utf8_lossy(&bytes).lines().map(str::to_string).collect()
+    }
+}
+
+impl std::io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}